@@ -0,0 +1,242 @@
+//! Caching what's parsed from files, for processes which parse many.
+
+use crate::error::Error;
+use crate::io::read_file;
+use crate::options::ParseOptions;
+use crate::parsed::{parse_bundled, Parsed};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// What was parsed from each of a bounded number of files, kept until the
+/// file is modified.
+///
+/// Programs borrow from their source, so the cache keeps what's made from
+/// each file's `Parsed` bundle instead, like its diagnostics or its printed
+/// form. Once the cache is full, the entry used least recently is evicted
+/// to make room for the next.
+#[derive(Debug)]
+pub struct ParseCache<T> {
+    capacity: usize,
+    options: ParseOptions,
+    entries: HashMap<PathBuf, Entry<T>>,
+    /// Counts uses of the cache, to find the entry used least recently.
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry<T> {
+    modified: SystemTime,
+    last_used: u64,
+    value: T,
+}
+
+impl<T> ParseCache<T> {
+    /// Make an empty cache holding at most `capacity` files, or one if
+    /// `capacity` is zero, which parses them with the default options.
+    pub fn new(capacity: usize) -> ParseCache<T> {
+        ParseCache::with_options(capacity, ParseOptions::default())
+    }
+
+    /// Make an empty cache holding at most `capacity` files, or one if
+    /// `capacity` is zero, which parses them with the given options.
+    pub fn with_options(capacity: usize, options: ParseOptions) -> ParseCache<T> {
+        ParseCache {
+            capacity: capacity.max(1),
+            options,
+            entries: HashMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Get what was made from the file, reading and parsing it again, and
+    /// passing the result to `make`, only if it was modified since.
+    ///
+    /// If the file can't be read or doesn't parse, any stale entry for it
+    /// is dropped.
+    pub fn get(
+        &mut self,
+        path: impl AsRef<Path>,
+        make: impl FnOnce(Parsed<'_>) -> T,
+    ) -> Result<&T> {
+        let path = path.as_ref();
+        self.clock += 1;
+
+        let modified = match modified(path) {
+            Ok(modified) => modified,
+            Err(error) => {
+                self.entries.remove(path);
+                return Err(error.into());
+            }
+        };
+
+        if !matches!(self.entries.get(path), Some(entry) if entry.modified == modified) {
+            self.entries.remove(path);
+
+            let source = read_file(path)?;
+            let value = make(parse_bundled(&source, self.options)?);
+            let entry = Entry {
+                modified,
+                last_used: self.clock,
+                value,
+            };
+
+            self.entries.insert(path.to_path_buf(), entry);
+            self.evict();
+        }
+
+        let entry = self
+            .entries
+            .get_mut(path)
+            .expect("the entry was just checked or inserted");
+        entry.last_used = self.clock;
+        Ok(&entry.value)
+    }
+
+    /// Drop the entry for the file, so it's parsed again next time,
+    /// returning whether there was one.
+    pub fn invalidate(&mut self, path: impl AsRef<Path>) -> bool {
+        self.entries.remove(path.as_ref()).is_some()
+    }
+
+    /// Whether the file has an entry, whether or not it's still fresh.
+    pub fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.entries.contains_key(path.as_ref())
+    }
+
+    /// The number of files with entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no files have entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Evict the entries used least recently until the cache fits.
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(path, _)| path.clone());
+
+            if let Some(path) = oldest {
+                self.entries.remove(&path);
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Result<SystemTime, Error> {
+    path.metadata()
+        .and_then(|metadata| metadata.modified())
+        .map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::time::Duration;
+
+    /// An empty directory for the test's files.
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tin-cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("should create the directory");
+        dir
+    }
+
+    /// Write a file with the given modification time, in seconds, since
+    /// some file systems only keep whole seconds.
+    fn write(path: &Path, source: &str, modified: u64) {
+        fs::write(path, source).expect("should write the file");
+        File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| {
+                file.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(modified))
+            })
+            .expect("should set the modification time");
+    }
+
+    fn function_count(parsed: Parsed<'_>) -> usize {
+        parsed.program().functions().count()
+    }
+
+    #[test]
+    fn reparses_only_modified_files() {
+        let dir = scratch_dir("reparse");
+        let path = dir.join("main.tin");
+        let mut cache = ParseCache::new(4);
+        let mut parses = 0;
+
+        write(&path, "fn main() { }", 1);
+
+        for _ in 0..2 {
+            let count = cache
+                .get(&path, |parsed| {
+                    parses += 1;
+                    function_count(parsed)
+                })
+                .expect("should parse");
+            assert_eq!(*count, 1);
+        }
+        assert_eq!(parses, 1);
+
+        write(&path, "fn main() { }\nfn other() { }", 2);
+        assert_eq!(*cache.get(&path, function_count).unwrap(), 2);
+
+        assert!(cache.invalidate(&path));
+        assert!(!cache.invalidate(&path));
+        assert!(cache.is_empty());
+
+        cache
+            .get(&path, |parsed| {
+                parses += 1;
+                function_count(parsed)
+            })
+            .expect("should parse");
+        assert_eq!(parses, 2);
+
+        // A file which stops parsing loses its stale entry.
+        write(&path, "fn main() {", 3);
+        assert!(cache.get(&path, function_count).is_err());
+        assert!(!cache.contains(&path));
+
+        fs::remove_dir_all(dir).expect("should clean up");
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_file() {
+        let dir = scratch_dir("evict");
+        let paths: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|name| dir.join(format!("{}.tin", name)))
+            .collect();
+
+        for path in &paths {
+            write(path, "fn main() { }", 1);
+        }
+
+        let mut cache = ParseCache::new(2);
+        cache.get(&paths[0], function_count).unwrap();
+        cache.get(&paths[1], function_count).unwrap();
+        cache.get(&paths[0], function_count).unwrap();
+        cache.get(&paths[2], function_count).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(&paths[0]));
+        assert!(!cache.contains(&paths[1]));
+        assert!(cache.contains(&paths[2]));
+
+        fs::remove_dir_all(dir).expect("should clean up");
+    }
+}
@@ -0,0 +1,156 @@
+//! Conditional compilation with `when` attributes.
+//!
+//! An item marked `#[when(cond)]` is only kept if its condition holds for
+//! the active flags. Conditions are made of flag names, `true` and `false`,
+//! and the `not`, `and`, `or`, and `xor` operators, like
+//! `#[when(unix and not test)]`.
+
+use crate::hir::*;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The reasons a `when` attribute may fail to evaluate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgError {
+    /// A `when` attribute didn't have exactly one condition.
+    WrongArity(String),
+    /// A condition used something other than flags, booleans, and logic.
+    InvalidCondition(String),
+}
+
+impl StdError for CfgError {}
+
+impl Display for CfgError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        use CfgError::*;
+
+        match self {
+            WrongArity(item) => write!(f, "`when` on `{}` needs exactly one condition", item),
+            InvalidCondition(item) => write!(f, "invalid `when` condition on `{}`", item),
+        }
+    }
+}
+
+/// Remove every item whose `when` conditions don't hold for the active flags.
+///
+/// Items with several `when` attributes are kept only if all of them hold.
+/// If any condition is invalid, the program is left unchanged.
+pub fn prune(program: &mut Program<'_>, active_flags: &[&str]) -> Result<(), CfgError> {
+    let mut keep = Vec::with_capacity(program.0.len());
+
+    for stmt in &program.0 {
+        keep.push(match stmt {
+            TopStmt::FnDecl(decl) => is_active(decl, active_flags)?,
+            _ => true,
+        });
+    }
+
+    let mut keep = keep.into_iter();
+    program.0.retain(|_| keep.next().unwrap_or(true));
+    Ok(())
+}
+
+/// Whether every `when` condition on the function holds.
+pub fn is_active(decl: &FnDecl<'_>, active_flags: &[&str]) -> Result<bool, CfgError> {
+    for attr in decl.meta.attrs.iter().filter(|attr| attr.name.0 == "when") {
+        let cond = match &attr.args[..] {
+            [cond] => cond,
+            _ => return Err(CfgError::WrongArity(decl.name.0.to_string())),
+        };
+
+        match eval(cond, active_flags) {
+            Some(true) => {}
+            Some(false) => return Ok(false),
+            None => return Err(CfgError::InvalidCondition(decl.name.0.to_string())),
+        }
+    }
+
+    Ok(true)
+}
+
+fn eval(cond: &Expr<'_>, active_flags: &[&str]) -> Option<bool> {
+    match cond {
+        Expr::Ident(Ident(flag)) => Some(active_flags.contains(flag)),
+        Expr::Literal(Literal::Bool(Bool(value, _))) => Some(*value),
+        Expr::FnCall(FnCall { name, args }) => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, active_flags))
+                .collect::<Option<Vec<_>>>()?;
+
+            match (name.0, &args[..]) {
+                ("not", [operand]) => Some(!operand),
+                ("and", [lhs, rhs]) => Some(*lhs && *rhs),
+                ("or", [lhs, rhs]) => Some(*lhs || *rhs),
+                ("xor", [lhs, rhs]) => Some(lhs ^ rhs),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    const SRC: &str = "\
+#[when(unix and not test)]
+fn open() { }
+
+#[when(windows)]
+#[inline]
+fn open() { }
+
+fn main() { open(); }
+";
+
+    fn pruned(flags: &[&str]) -> Vec<usize> {
+        let mut program = parse(SRC).expect("should parse");
+        prune(&mut program, flags).expect("conditions are valid");
+        program
+            .functions()
+            .map(|decl| decl.meta.attrs.len())
+            .collect()
+    }
+
+    #[test]
+    fn parses_when_attributes() {
+        let program = parse(SRC).expect("should parse");
+        let decl = program.functions().next().unwrap();
+
+        let attrs = &decl.meta().attrs;
+
+        assert_eq!(attrs.len(), 1);
+        assert_eq!(attrs[0].name.0, "when");
+        assert!(matches!(
+            &attrs[0].args[..],
+            [cond] if matches!(&**cond, Expr::FnCall(FnCall { name: Ident("and"), .. }))
+        ));
+    }
+
+    #[test]
+    fn prunes_under_different_flags() {
+        // Each function is identified by how many attributes it has.
+        assert_eq!(pruned(&["unix"]), vec![1, 0]);
+        assert_eq!(pruned(&["unix", "test"]), vec![0]);
+        assert_eq!(pruned(&["windows"]), vec![2, 0]);
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        let mut program = parse("#[when(1 + 2)] fn f() { }").expect("should parse");
+        assert_eq!(
+            prune(&mut program, &[]),
+            Err(CfgError::InvalidCondition("f".to_string()))
+        );
+        assert_eq!(program.0.len(), 1);
+
+        let mut program = parse("#[when] fn f() { }").expect("should parse");
+        assert_eq!(
+            prune(&mut program, &[]),
+            Err(CfgError::WrongArity("f".to_string()))
+        );
+    }
+}
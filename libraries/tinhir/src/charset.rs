@@ -0,0 +1,272 @@
+//! The classes of characters the parser tests for.
+//!
+//! These run for nearly every character of the source, so they're small
+//! enough to inline, and take anything they depend on from the options as
+//! an argument rather than looking it up each time. Every parser uses them,
+//! so names, numbers, and the lexer all agree on which characters belong
+//! where.
+
+/// Whether the character can start an identifier.
+///
+/// This is an ASCII letter, or any letter if `unicode` identifiers are
+/// allowed.
+#[inline]
+pub(crate) fn is_ident_start(c: char, unicode: bool) -> bool {
+    if unicode {
+        c.is_alphabetic()
+    } else {
+        c.is_ascii_alphabetic()
+    }
+}
+
+/// Whether the character can continue an identifier.
+///
+/// Identifiers may contain operator characters and `_` after the first
+/// character, as in `some-name` or `a+b`, and any letter or digit if
+/// `unicode` identifiers are allowed.
+#[inline]
+pub(crate) fn is_ident_continue(c: char, unicode: bool) -> bool {
+    let alphanumeric = if unicode {
+        c.is_alphanumeric()
+    } else {
+        c.is_ascii_alphanumeric()
+    };
+
+    alphanumeric || c == '_' || is_operator_char(c)
+}
+
+/// Whether the character can continue the name of a type, which unlike an
+/// identifier can't contain operator characters other than `-`.
+#[inline]
+pub(crate) fn is_ty_continue(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Whether the character is an operator character an identifier may
+/// contain, but not end with.
+#[inline]
+pub(crate) fn is_operator_char(c: char) -> bool {
+    matches!(c, '-' | '+' | '>' | '<' | '*')
+}
+
+/// Whether the character is an ASCII digit in the given radix.
+///
+/// Integers are written in radix 2, 10, or 16, which are checked directly.
+/// Any other radix up to 36 works too, by way of `char::is_digit`.
+#[inline]
+pub(crate) fn is_digit_in_radix(c: char, radix: u32) -> bool {
+    match radix {
+        2 => matches!(c, '0' | '1'),
+        10 => c.is_ascii_digit(),
+        16 => c.is_ascii_hexdigit(),
+        _ => c.is_digit(radix),
+    }
+}
+
+/// How many terminal columns the character takes up.
+///
+/// Combining marks, the medial and final Hangul jamo, and other zero-width
+/// characters join the character before them, so they take none. East
+/// Asian wide and fullwidth characters, like CJK ideographs, Hangul
+/// syllables, and emoji, take two. The ranges follow the Unicode East Asian
+/// Width and combining mark tables for every block source is likely to
+/// use.
+///
+/// Widths are per character, so an emoji sequence joined with U+200D is
+/// as wide as its parts, where a terminal which joins it shows two columns.
+pub(crate) fn display_width(c: char) -> usize {
+    match c as u32 {
+        // Combining marks.
+        0x0300..=0x036F
+        | 0x0483..=0x0489
+        | 0x0591..=0x05BD
+        | 0x05BF
+        | 0x05C1..=0x05C2
+        | 0x05C4..=0x05C5
+        | 0x05C7
+        | 0x0610..=0x061A
+        | 0x064B..=0x065F
+        | 0x0670
+        | 0x06D6..=0x06DC
+        | 0x06DF..=0x06E4
+        | 0x06E7..=0x06E8
+        | 0x06EA..=0x06ED
+        | 0x0900..=0x0902
+        | 0x093A
+        | 0x093C
+        | 0x0941..=0x0948
+        | 0x094D
+        | 0x0951..=0x0957
+        | 0x0962..=0x0963
+        | 0x0E31
+        | 0x0E34..=0x0E3A
+        | 0x0E47..=0x0E4E
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x20D0..=0x20FF
+        | 0x302A..=0x302D
+        | 0x3099..=0x309A
+        | 0xFE20..=0xFE2F
+        // Medial and final Hangul jamo, which join the initial before them.
+        | 0x1160..=0x11FF
+        | 0xD7B0..=0xD7FF
+        // Zero-width spaces, joiners, and marks, and variation selectors.
+        | 0x200B..=0x200F
+        | 0x2060..=0x2064
+        | 0xFE00..=0xFE0F
+        | 0xFEFF
+        | 0xE0100..=0xE01EF => 0,
+        // Initial Hangul jamo.
+        0x1100..=0x115F
+        | 0xA960..=0xA97F
+        // Symbols shown as emoji.
+        | 0x231A..=0x231B
+        | 0x2329..=0x232A
+        | 0x23E9..=0x23EC
+        | 0x23F0
+        | 0x23F3
+        | 0x25FD..=0x25FE
+        | 0x2614..=0x2615
+        | 0x2648..=0x2653
+        | 0x267F
+        | 0x2693
+        | 0x26A1
+        | 0x26AA..=0x26AB
+        | 0x26BD..=0x26BE
+        | 0x26C4..=0x26C5
+        | 0x26CE
+        | 0x26D4
+        | 0x26EA
+        | 0x26F2..=0x26F3
+        | 0x26F5
+        | 0x26FA
+        | 0x26FD
+        | 0x2705
+        | 0x270A..=0x270B
+        | 0x2728
+        | 0x274C
+        | 0x274E
+        | 0x2753..=0x2755
+        | 0x2757
+        | 0x2795..=0x2797
+        | 0x27B0
+        | 0x27BF
+        | 0x2B1B..=0x2B1C
+        | 0x2B50
+        | 0x2B55
+        // CJK, kana, Yi, and Hangul syllables.
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE10..=0xFE19
+        | 0xFE30..=0xFE6F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x16FE0..=0x16FE4
+        | 0x17000..=0x18CFF
+        | 0x1AFF0..=0x1B2FF
+        // Emoji.
+        | 0x1F004
+        | 0x1F0CF
+        | 0x1F18E
+        | 0x1F191..=0x1F19A
+        | 0x1F200..=0x1F251
+        | 0x1F300..=0x1F64F
+        | 0x1F680..=0x1F6FF
+        | 0x1F7E0..=0x1F7EB
+        | 0x1F90C..=0x1F9FF
+        | 0x1FA70..=0x1FAFF
+        // The supplementary ideographic planes.
+        | 0x20000..=0x2FFFD
+        | 0x30000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_ident_starts() {
+        assert!(is_ident_start('a', false));
+        assert!(is_ident_start('Z', false));
+        assert!(!is_ident_start('0', false));
+        assert!(!is_ident_start('-', false));
+        assert!(!is_ident_start('_', false));
+        assert!(!is_ident_start('é', false));
+
+        assert!(is_ident_start('é', true));
+        assert!(!is_ident_start('٣', true));
+    }
+
+    #[test]
+    fn classifies_ident_continues() {
+        for c in "az09-_+><*".chars() {
+            assert!(is_ident_continue(c, false), "{:?} should continue", c);
+        }
+
+        for c in "=/.:( é".chars() {
+            assert!(!is_ident_continue(c, false), "{:?} shouldn't continue", c);
+        }
+
+        assert!(is_ident_continue('é', true));
+        assert!(is_ident_continue('٣', true));
+    }
+
+    #[test]
+    fn classifies_type_name_characters() {
+        assert!(is_ty_continue('-'));
+        assert!(is_ty_continue('_'));
+        assert!(is_ty_continue('9'));
+        assert!(!is_ty_continue('+'));
+        assert!(!is_ty_continue('>'));
+    }
+
+    #[test]
+    fn classifies_operator_characters() {
+        for c in "-+><*".chars() {
+            assert!(is_operator_char(c), "{:?} should be an operator", c);
+        }
+
+        for c in "_=/^a".chars() {
+            assert!(!is_operator_char(c), "{:?} shouldn't be an operator", c);
+        }
+    }
+
+    #[test]
+    fn measures_display_width() {
+        assert_eq!(display_width('a'), 1);
+        assert_eq!(display_width('é'), 1);
+        assert_eq!(display_width('\u{301}'), 0);
+        assert_eq!(display_width('漢'), 2);
+        assert_eq!(display_width('한'), 2);
+        assert_eq!(display_width('Ａ'), 2);
+        assert_eq!(display_width('\u{1FAE0}'), 2);
+        assert_eq!(display_width('\u{1F680}'), 2);
+        assert_eq!(display_width('\u{A960}'), 2);
+        assert_eq!(display_width('\u{D7B0}'), 0);
+        assert_eq!(display_width('\u{200D}'), 0);
+        assert_eq!(display_width('\u{5D1}'), 1);
+        assert_eq!(display_width('\u{5B0}'), 0);
+    }
+
+    #[test]
+    fn classifies_digits_by_radix() {
+        assert!(is_digit_in_radix('1', 2));
+        assert!(!is_digit_in_radix('2', 2));
+        assert!(is_digit_in_radix('0', 10));
+        assert!(is_digit_in_radix('9', 10));
+        assert!(!is_digit_in_radix('a', 10));
+        assert!(!is_digit_in_radix('٣', 10));
+        assert!(is_digit_in_radix('f', 16));
+        assert!(is_digit_in_radix('F', 16));
+        assert!(!is_digit_in_radix('g', 16));
+        assert!(is_digit_in_radix('7', 8));
+        assert!(!is_digit_in_radix('8', 8));
+    }
+}
@@ -0,0 +1,96 @@
+//! The parsers for Tin's smallest pieces, for building larger grammars.
+//!
+//! These are `nom` parsers, so they compose with `nom`'s combinators. None
+//! of them skip leading whitespace, and they reject input the same way the
+//! full parser does, so `ident` won't accept a keyword.
+//!
+//! They parse with the default `ParseOptions`. Wrap them in `with_options`
+//! to parse with others, like keywords spelled differently.
+//!
+//! ```
+//! use nom::{bytes::complete::tag, sequence::separated_pair};
+//! use tinhir::combinators::{ident, int, ParseResult};
+//! use tinhir::{Ident, Int};
+//!
+//! /// A setting, like `retries=3`.
+//! fn setting(input: &str) -> ParseResult<&str, (Ident<'_>, Int<'_>)> {
+//!     separated_pair(ident, tag("="), int)(input)
+//! }
+//!
+//! let (rest, (name, value)) = setting("retries=3;").unwrap();
+//! assert_eq!((name.0, value.0, rest), ("retries", 3, ";"));
+//! assert!(setting("if=3").is_err());
+//! ```
+
+use crate::hir::*;
+use crate::options::ParseOptions;
+use crate::parse;
+use nom::{error::VerboseError, IResult};
+
+/// The result of a parser, with `nom`'s verbose errors.
+pub type ParseResult<I, O> = IResult<I, O, VerboseError<I>>;
+
+/// Run a parser with the given options.
+///
+/// ```
+/// use tinhir::combinators::{ident, with_options};
+/// use tinhir::{Keyword, KeywordConfig, ParseOptions};
+///
+/// let keywords = KeywordConfig::default().disable(Keyword::Loop);
+/// let options = ParseOptions::default().keywords(keywords);
+///
+/// assert!(ident("loop").is_err());
+/// assert!(with_options(options, ident)("loop").is_ok());
+/// ```
+pub fn with_options<'a, O>(
+    options: ParseOptions,
+    parser: impl Fn(&'a str) -> ParseResult<&'a str, O>,
+) -> impl Fn(&'a str) -> ParseResult<&'a str, O> {
+    move |input| parse::with_options(options, || parser(input))
+}
+
+/// An identifier, like `count` or `is-empty?`, which isn't a keyword.
+pub fn ident(input: &str) -> ParseResult<&str, Ident<'_>> {
+    parse::ident(input)
+}
+
+/// A type name, which may have generic arguments, like `Map<Str, Int>`.
+pub fn ty(input: &str) -> ParseResult<&str, Ty<'_>> {
+    parse::ty(input)
+}
+
+/// `true` or `false`.
+pub fn bool(input: &str) -> ParseResult<&str, Bool<'_>> {
+    parse::bool(input)
+}
+
+/// An integer, in decimal, or in hexadecimal or binary with a `0x` or `0b`
+/// prefix.
+pub fn int(input: &str) -> ParseResult<&str, Int<'_>> {
+    parse::int(input)
+}
+
+/// A floating point number, like `1.5` or `2e10`.
+pub fn float(input: &str) -> ParseResult<&str, Float<'_>> {
+    parse::float(input)
+}
+
+/// A string, like `"hi\n"` or `r"C:\path"`.
+pub fn ustr(input: &str) -> ParseResult<&str, UStr<'_>> {
+    parse::ustr(input)
+}
+
+/// A byte string, like `b"hi\x00"`.
+pub fn bstr(input: &str) -> ParseResult<&str, BStr<'_>> {
+    parse::bstr(input)
+}
+
+/// A character, like `'a'` or `'\n'`.
+pub fn char(input: &str) -> ParseResult<&str, Char<'_>> {
+    parse::char(input)
+}
+
+/// A symbol, like `:none`.
+pub fn symbol(input: &str) -> ParseResult<&str, Symbol<'_>> {
+    parse::symbol(input)
+}
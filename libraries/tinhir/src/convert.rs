@@ -0,0 +1,209 @@
+//! Converting literals into Rust values.
+//!
+//! This is useful when Tin is used for configuration, to pull typed values
+//! out of a parsed program.
+
+use crate::hir::*;
+use crate::parse::{byte_escape, escape};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A literal couldn't be converted, because it's the wrong kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiteralTypeError {
+    /// The kind of literal needed.
+    pub expected: &'static str,
+    /// The kind of literal found.
+    pub found: &'static str,
+}
+
+impl StdError for LiteralTypeError {}
+
+impl Display for LiteralTypeError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl<'prgrm> Literal<'prgrm> {
+    /// A description of the kind of literal, for use in messages.
+    pub fn kind_name(&self) -> &'static str {
+        use Literal::*;
+
+        match self {
+            Bool(_) => "a boolean",
+            Int(_) => "an integer",
+            Float(_) => "a float",
+            UStr(_) => "a string",
+            BStr(_) => "a byte string",
+            Char(_) => "a character",
+            Symbol(_) => "a symbol",
+            Array(_) => "an array",
+            Tuple(_) => "a tuple",
+            Map(_) => "a map",
+        }
+    }
+}
+
+impl<'prgrm> UStr<'prgrm> {
+    /// The contents of the string, with any escapes replaced by the
+    /// characters they stand for.
+    pub fn value(&self) -> Cow<'prgrm, str> {
+        if self.is_raw() || !self.0.contains('\\') {
+            return Cow::Borrowed(self.0);
+        }
+
+        let mut value = String::with_capacity(self.0.len());
+        let mut rest = self.0;
+
+        while let Some(backslash) = rest.find('\\') {
+            value.push_str(&rest[..backslash]);
+
+            // The string already parsed, so its escapes are all valid.
+            match escape(&rest[backslash..]) {
+                Ok((after, c)) => {
+                    value.push(c);
+                    rest = after;
+                }
+                Err(_) => {
+                    value.push('\\');
+                    rest = &rest[backslash + 1..];
+                }
+            }
+        }
+
+        value.push_str(rest);
+        Cow::Owned(value)
+    }
+}
+
+impl<'prgrm> BStr<'prgrm> {
+    /// The bytes of the string, with any escapes replaced by the bytes they
+    /// stand for.
+    pub fn value(&self) -> Cow<'prgrm, [u8]> {
+        if !self.0.contains(&b'\\') {
+            return Cow::Borrowed(self.0);
+        }
+
+        // Byte strings are ASCII, so this can't fail.
+        let mut rest = std::str::from_utf8(self.0).unwrap_or("");
+        let mut value = Vec::with_capacity(rest.len());
+
+        while let Some(backslash) = rest.find('\\') {
+            value.extend_from_slice(&rest.as_bytes()[..backslash]);
+
+            // The string already parsed, so its escapes are all valid.
+            match byte_escape(&rest[backslash..]) {
+                Ok((after, byte)) => {
+                    value.push(byte);
+                    rest = after;
+                }
+                Err(_) => {
+                    value.push(b'\\');
+                    rest = &rest[backslash + 1..];
+                }
+            }
+        }
+
+        value.extend_from_slice(rest.as_bytes());
+        Cow::Owned(value)
+    }
+}
+
+/// Convert literals of one kind, failing for any other kind.
+macro_rules! try_from_literal {
+    ($ty:ty, $expected:expr, $pattern:pat => $value:expr) => {
+        impl<'a, 'prgrm> TryFrom<&'a Literal<'prgrm>> for $ty {
+            type Error = LiteralTypeError;
+
+            fn try_from(literal: &'a Literal<'prgrm>) -> Result<$ty, LiteralTypeError> {
+                match literal {
+                    $pattern => Ok($value),
+                    other => Err(LiteralTypeError {
+                        expected: $expected,
+                        found: other.kind_name(),
+                    }),
+                }
+            }
+        }
+    };
+}
+
+try_from_literal!(i64, "an integer", Literal::Int(Int(value, _)) => *value);
+try_from_literal!(f64, "a float", Literal::Float(Float(value, _)) => *value);
+try_from_literal!(bool, "a boolean", Literal::Bool(Bool(value, _)) => *value);
+try_from_literal!(char, "a character", Literal::Char(Char(value, _)) => *value);
+try_from_literal!(String, "a string", Literal::UStr(ustr) => ustr.value().into_owned());
+try_from_literal!(Vec<u8>, "a byte string", Literal::BStr(bstr) => bstr.value().into_owned());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    /// Parse the elements of an array literal.
+    fn with_literals(src: &str, check: impl FnOnce(&[&Literal<'_>])) {
+        let src = format!("fn main() {{ x = #[{}]; }}", src);
+        let program = parse(&src).expect("should parse");
+        let body = &program.functions().next().unwrap().body;
+
+        match &body.0[..] {
+            [Stmt::VarAssign(VarAssign { rhs, .. })] => match &**rhs {
+                Expr::Literal(Literal::Array(Array(items, _))) => {
+                    let literals: Vec<_> = items
+                        .iter()
+                        .map(|item| match &**item {
+                            Expr::Literal(literal) => literal,
+                            other => panic!("expected a literal, found {:?}", other),
+                        })
+                        .collect();
+                    check(&literals)
+                }
+                other => panic!("expected an array, found {:?}", other),
+            },
+            other => panic!("expected an assignment, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn converts_literals_to_primitives() {
+        with_literals(r#"42, 2.5, true, 'c', "a\tb", r"a\tb", b"hi""#, |items| {
+            assert_eq!(i64::try_from(items[0]), Ok(42));
+            assert_eq!(f64::try_from(items[1]), Ok(2.5));
+            assert_eq!(bool::try_from(items[2]), Ok(true));
+            assert_eq!(char::try_from(items[3]), Ok('c'));
+            assert_eq!(String::try_from(items[4]), Ok("a\tb".to_string()));
+            assert_eq!(String::try_from(items[5]), Ok("a\\tb".to_string()));
+            assert_eq!(Vec::<u8>::try_from(items[6]), Ok(b"hi".to_vec()));
+        });
+    }
+
+    #[test]
+    fn decodes_byte_escapes() {
+        with_literals(r#"b"\xFF\x00a", b"\377\0\12\101", b"\t\"\\""#, |items| {
+            assert_eq!(Vec::<u8>::try_from(items[0]), Ok(vec![0xFF, 0, b'a']));
+            assert_eq!(
+                Vec::<u8>::try_from(items[1]),
+                Ok(vec![0xFF, 0, b'\n', b'A'])
+            );
+            assert_eq!(Vec::<u8>::try_from(items[2]), Ok(b"\t\"\\".to_vec()));
+        });
+    }
+
+    #[test]
+    fn rejects_the_wrong_kind_of_literal() {
+        with_literals(r#""42", :name"#, |items| {
+            let error = i64::try_from(items[0]).unwrap_err();
+            assert_eq!(error.to_string(), "expected an integer, found a string");
+            assert_eq!(
+                bool::try_from(items[1]),
+                Err(LiteralTypeError {
+                    expected: "a boolean",
+                    found: "a symbol",
+                })
+            );
+        });
+    }
+}
@@ -0,0 +1,608 @@
+//! Rewrites of the HIR into simpler forms.
+//!
+//! None of these run as part of parsing, since they lose information about
+//! how the source was written. Backends which want a smaller language to
+//! handle can opt into them.
+
+use crate::error::Error;
+use crate::hir::*;
+use crate::parse::Keyword;
+use crate::print::print_by_value;
+use crate::visit::Node;
+use std::mem;
+
+/// Rewrite every `unless` into an `if`, and every `until` into a `while`,
+/// by negating their conditions with `not`.
+pub fn lower_negated(program: &mut Program<'_>) {
+    for stmt in &mut program.0 {
+        if let TopStmt::FnDecl(decl) = stmt {
+            lower_block(&mut decl.body);
+        }
+    }
+}
+
+impl<'prgrm> Program<'prgrm> {
+    /// Rewrite the program into a canonical form, so programs which differ
+    /// only in how they're written compare equal.
+    ///
+    /// Comments and doc comments are dropped, and negated conditionals are
+    /// lowered with `lower_negated`. Grouping parentheses never make it into
+    /// the HIR. Literals keep their spelling, so compare canonical programs
+    /// with `semantic_eq` to ignore it.
+    pub fn canonicalize(mut self) -> Program<'prgrm> {
+        self.0.retain(|stmt| !matches!(stmt, TopStmt::Comment(_)));
+
+        for stmt in &mut self.0 {
+            match stmt {
+                TopStmt::FnDecl(decl) => {
+                    decl.meta.doc = None;
+                    strip_block(&mut decl.body);
+                }
+                TopStmt::TyDecl(decl) => decl.meta.doc = None,
+                TopStmt::TyAlias(alias) => alias.meta.doc = None,
+                TopStmt::Comment(_) | TopStmt::Use(_) => {}
+            }
+        }
+
+        lower_negated(&mut self);
+        self
+    }
+
+    /// Whether the programs are the same, comparing literals by value
+    /// rather than by how they're written, so `0x10` equals `16`, and
+    /// `"\t"` equals a raw string holding a tab.
+    ///
+    /// Everything else is compared as `==` would, so canonicalize the
+    /// programs first to ignore comments and negated conditionals too.
+    pub fn semantic_eq(&self, other: &Program<'_>) -> bool {
+        print_by_value(self) == print_by_value(other)
+    }
+}
+
+/// Remove the comments from a block, and any blocks nested in it.
+///
+/// Doc comments and trailing comments are removed too, leaving the
+/// statements they're attached to.
+fn strip_block(block: &mut Block<'_>) {
+    block.0 = std::mem::take(&mut block.0)
+        .into_iter()
+        .filter_map(strip_stmt)
+        .collect();
+
+    for stmt in &mut block.0 {
+        match stmt {
+            Stmt::VarAssign(assign) => strip_expr(&mut assign.rhs),
+            Stmt::MultiAssign(assign) => {
+                assign.values.iter_mut().for_each(|value| strip_expr(value))
+            }
+            Stmt::CompoundAssign(assign) => strip_expr(&mut assign.rhs),
+            Stmt::Expr(expr) => strip_expr(expr),
+            Stmt::Comment(_) | Stmt::Documented(..) | Stmt::Trailed(..) => {}
+        }
+    }
+}
+
+fn strip_stmt(stmt: Stmt<'_>) -> Option<Stmt<'_>> {
+    match stmt {
+        Stmt::Comment(_) => None,
+        Stmt::Documented(_, stmt) | Stmt::Trailed(stmt, _) => strip_stmt(*stmt),
+        stmt => Some(stmt),
+    }
+}
+
+fn strip_expr(expr: &mut Expr<'_>) {
+    match expr {
+        Expr::If(cond, body, els)
+        | Expr::Unless(cond, body, els)
+        | Expr::While(cond, body, els)
+        | Expr::Until(cond, body, els) => {
+            strip_expr(cond);
+            strip_block(body);
+            els.iter_mut().for_each(strip_block);
+        }
+        Expr::For(item, iter, body, els) => {
+            strip_expr(item);
+            strip_expr(iter);
+            strip_block(body);
+            els.iter_mut().for_each(strip_block);
+        }
+        Expr::Loop(body) => strip_block(body),
+        Expr::Match(expr) => {
+            strip_expr(&mut expr.scrutinee);
+            expr.arms
+                .iter_mut()
+                .for_each(|arm| strip_block(&mut arm.body));
+        }
+        Expr::Lambda(lambda) => strip_block(&mut lambda.body),
+        Expr::Labeled(_, body) => strip_block(body),
+        Expr::Break(_, value, _) | Expr::Return(value, _) => {
+            if let Some(value) = value {
+                strip_expr(value);
+            }
+        }
+        Expr::FnCall(call) => call.args.iter_mut().for_each(|arg| strip_expr(arg)),
+        Expr::Try(expr) => strip_expr(expr),
+        Expr::Dot(lhs, rhs) | Expr::Index(lhs, rhs) => {
+            strip_expr(lhs);
+            strip_expr(rhs);
+        }
+        Expr::Literal(Literal::Map(Map(entries, _))) => {
+            for (_, value) in entries {
+                strip_expr(value);
+            }
+        }
+        Expr::Literal(Literal::Array(Array(items, _)))
+        | Expr::Literal(Literal::Tuple(Tuple(items))) => {
+            items.iter_mut().for_each(|item| strip_expr(item))
+        }
+        Expr::Continue(_) | Expr::Ident(_) | Expr::Literal(_) => {}
+    }
+}
+
+fn lower_block(block: &mut Block<'_>) {
+    block.0.iter_mut().for_each(lower_stmt);
+}
+
+fn lower_stmt(stmt: &mut Stmt<'_>) {
+    match stmt {
+        Stmt::VarAssign(assign) => lower_expr(&mut assign.rhs),
+        Stmt::MultiAssign(assign) => assign.values.iter_mut().for_each(|value| lower_expr(value)),
+        Stmt::CompoundAssign(assign) => lower_expr(&mut assign.rhs),
+        Stmt::Expr(expr) => lower_expr(expr),
+        Stmt::Documented(_, stmt) | Stmt::Trailed(stmt, _) => lower_stmt(stmt),
+        Stmt::Comment(_) => {}
+    }
+}
+
+fn lower_expr(expr: &mut Expr<'_>) {
+    // Lower the children first, so the conditions moved below are done.
+    match expr {
+        Expr::If(cond, then, els) | Expr::Unless(cond, then, els) => {
+            lower_expr(cond);
+            lower_block(then);
+            els.iter_mut().for_each(lower_block);
+        }
+        Expr::While(cond, body, els) | Expr::Until(cond, body, els) => {
+            lower_expr(cond);
+            lower_block(body);
+            els.iter_mut().for_each(lower_block);
+        }
+        Expr::For(item, iter, body, els) => {
+            lower_expr(item);
+            lower_expr(iter);
+            lower_block(body);
+            els.iter_mut().for_each(lower_block);
+        }
+        Expr::Loop(body) => lower_block(body),
+        Expr::Match(expr) => {
+            lower_expr(&mut expr.scrutinee);
+            expr.arms
+                .iter_mut()
+                .for_each(|arm| lower_block(&mut arm.body));
+        }
+        Expr::Lambda(lambda) => lower_block(&mut lambda.body),
+        Expr::Labeled(_, body) => lower_block(body),
+        Expr::Break(_, value, _) | Expr::Return(value, _) => {
+            if let Some(value) = value {
+                lower_expr(value);
+            }
+        }
+        Expr::FnCall(call) => call.args.iter_mut().for_each(|arg| lower_expr(arg)),
+        Expr::Try(expr) => lower_expr(expr),
+        Expr::Dot(lhs, rhs) | Expr::Index(lhs, rhs) => {
+            lower_expr(lhs);
+            lower_expr(rhs);
+        }
+        Expr::Literal(Literal::Map(Map(entries, _))) => {
+            for (_, value) in entries {
+                lower_expr(value);
+            }
+        }
+        Expr::Literal(Literal::Array(Array(items, _)))
+        | Expr::Literal(Literal::Tuple(Tuple(items))) => {
+            items.iter_mut().for_each(|item| lower_expr(item))
+        }
+        Expr::Continue(_) | Expr::Ident(_) | Expr::Literal(_) => {}
+    }
+
+    let placeholder = Expr::Continue("");
+
+    *expr = match mem::replace(expr, placeholder) {
+        Expr::Unless(cond, then, els) => Expr::If(negate(cond), then, els),
+        Expr::Until(cond, body, els) => Expr::While(negate(cond), body, els),
+        other => other,
+    };
+}
+
+fn negate(expr: BExpr<'_>) -> BExpr<'_> {
+    Box::new(Expr::FnCall(FnCall {
+        name: Ident(Keyword::Not.spelling()),
+        args: vec![expr],
+    }))
+}
+
+/// The temporaries holding the indices of a compound assignment's target.
+///
+/// Names can't start with `%`, so these never clash with the program's. That
+/// also means a lowered program which uses them doesn't parse once printed.
+const TEMPS: &[&str] = &[
+    "%index0", "%index1", "%index2", "%index3", "%index4", "%index5", "%index6", "%index7",
+];
+
+/// Rewrite every compound assignment, like `a[i] += 1`, into a plain
+/// assignment, like `a[i] = a[i] + 1`.
+///
+/// The target is still evaluated once, before the value. Each index in it,
+/// other than an integer or symbol, is first assigned to a temporary, like
+/// `%index0`, which both sides of the assignment then use.
+///
+/// There are eight temporaries, so if any target has more indices than
+/// that which need one, this fails without changing the program.
+pub fn compound_assign(program: &mut Program<'_>) -> Result<(), Error> {
+    let needed = program
+        .traverse()
+        .filter_map(|(_, node)| match node {
+            Node::CompoundAssign(CompoundAssign {
+                target: Pattern::Place(place),
+                ..
+            }) => temps_needed(place),
+            _ => None,
+        })
+        .max();
+
+    if let Some(needed) = needed.filter(|needed| *needed > TEMPS.len()) {
+        return Err(Error::TooManyIndices {
+            needed,
+            max: TEMPS.len(),
+        });
+    }
+
+    for body in program.function_bodies_mut() {
+        let stmts = mem::take(&mut body.0);
+
+        *body = Block(stmts).rewrite(&mut |mut expr| {
+            nested_blocks(&mut expr).into_iter().for_each(expand_block);
+            expr
+        });
+        expand_block(body);
+    }
+
+    Ok(())
+}
+
+/// The blocks directly inside an expression.
+fn nested_blocks<'a, 'prgrm>(expr: &'a mut Expr<'prgrm>) -> Vec<&'a mut Block<'prgrm>> {
+    match expr {
+        Expr::If(_, body, els)
+        | Expr::Unless(_, body, els)
+        | Expr::While(_, body, els)
+        | Expr::Until(_, body, els)
+        | Expr::For(_, _, body, els) => std::iter::once(body).chain(els).collect(),
+        Expr::Loop(body) | Expr::Labeled(_, body) => vec![body],
+        Expr::Match(expr) => expr.arms.iter_mut().map(|arm| &mut arm.body).collect(),
+        Expr::Lambda(lambda) => vec![&mut lambda.body],
+        _ => Vec::new(),
+    }
+}
+
+/// Lower the compound assignments directly in a block.
+fn expand_block(block: &mut Block<'_>) {
+    let mut stmts = Vec::with_capacity(block.0.len());
+
+    for stmt in mem::take(&mut block.0) {
+        expand_stmt(stmt, &mut stmts);
+    }
+
+    block.0 = stmts;
+}
+
+/// Lower a statement into `out`, keeping any doc comment on the first
+/// statement it lowers to and any trailing comment on the last.
+fn expand_stmt<'prgrm>(stmt: Stmt<'prgrm>, out: &mut Vec<Stmt<'prgrm>>) {
+    match stmt {
+        Stmt::CompoundAssign(assign) => expand_assign(assign, out),
+        Stmt::Documented(doc, stmt) => {
+            let first = out.len();
+            expand_stmt(*stmt, out);
+
+            let stmt = out.remove(first);
+            out.insert(first, Stmt::Documented(doc, Box::new(stmt)));
+        }
+        Stmt::Trailed(stmt, comment) => {
+            expand_stmt(*stmt, out);
+
+            if let Some(stmt) = out.pop() {
+                out.push(Stmt::Trailed(Box::new(stmt), comment));
+            }
+        }
+        stmt => out.push(stmt),
+    }
+}
+
+fn expand_assign<'prgrm>(assign: CompoundAssign<'prgrm>, out: &mut Vec<Stmt<'prgrm>>) {
+    let place = match assign.target {
+        Pattern::Ident(Ident(name)) => {
+            let value = Expr::FnCall(FnCall {
+                name: Ident(assign.op),
+                args: vec![Box::new(Expr::Ident(Ident(name))), assign.rhs],
+            });
+            out.push(plain_assign(Pattern::Ident(Ident(name)), value));
+            return;
+        }
+        Pattern::Place(place) if temps_needed(&place).is_some() => place,
+        _ => return out.push(Stmt::CompoundAssign(assign)),
+    };
+
+    let place = bind_indices(*place, &mut TEMPS.iter(), out);
+    let value = Expr::FnCall(FnCall {
+        name: Ident(assign.op),
+        args: vec![Box::new(copy_place(&place)), assign.rhs],
+    });
+    out.push(plain_assign(Pattern::Place(Box::new(place)), value));
+}
+
+fn plain_assign<'prgrm>(pattern: Pattern<'prgrm>, value: Expr<'prgrm>) -> Stmt<'prgrm> {
+    Stmt::VarAssign(VarAssign {
+        pattern,
+        ty: None,
+        rhs: Box::new(value),
+    })
+}
+
+/// Whether an index can be used on both sides without a temporary.
+fn is_constant_index(index: &Expr<'_>) -> bool {
+    matches!(
+        index,
+        Expr::Literal(Literal::Int(_)) | Expr::Literal(Literal::Symbol(_))
+    )
+}
+
+/// The number of temporaries needed for the indices of a place, if it's
+/// a name followed by only fields and indices, as the parser allows.
+fn temps_needed(place: &Expr<'_>) -> Option<usize> {
+    match place {
+        Expr::Ident(_) => Some(0),
+        Expr::Dot(lhs, field) if matches!(**field, Expr::Ident(_)) => temps_needed(lhs),
+        Expr::Index(base, index) => {
+            Some(temps_needed(base)? + usize::from(!is_constant_index(index)))
+        }
+        _ => None,
+    }
+}
+
+/// Assign each index in a place which isn't constant to a temporary, in
+/// source order, replacing the index with the temporary.
+fn bind_indices<'prgrm>(
+    place: Expr<'prgrm>,
+    temps: &mut std::slice::Iter<'_, &'static str>,
+    out: &mut Vec<Stmt<'prgrm>>,
+) -> Expr<'prgrm> {
+    match place {
+        Expr::Dot(lhs, field) => Expr::Dot(Box::new(bind_indices(*lhs, temps, out)), field),
+        Expr::Index(base, index) => {
+            let base = bind_indices(*base, temps, out);
+
+            let index = if is_constant_index(&index) {
+                index
+            } else {
+                let temp = temps.next().expect("the temporaries were counted");
+                out.push(plain_assign(Pattern::Ident(Ident(temp)), *index));
+                Box::new(Expr::Ident(Ident(temp)))
+            };
+
+            Expr::Index(Box::new(base), index)
+        }
+        place => place,
+    }
+}
+
+/// Copy a place whose indices have all been bound, which leaves only names
+/// and constants in it.
+fn copy_place<'prgrm>(place: &Expr<'prgrm>) -> Expr<'prgrm> {
+    match place {
+        Expr::Ident(Ident(name)) => Expr::Ident(Ident(name)),
+        Expr::Dot(lhs, field) => Expr::Dot(Box::new(copy_place(lhs)), Box::new(copy_place(field))),
+        Expr::Index(base, index) => {
+            Expr::Index(Box::new(copy_place(base)), Box::new(copy_place(index)))
+        }
+        Expr::Literal(Literal::Int(Int(value, src))) => {
+            Expr::Literal(Literal::Int(Int(*value, src)))
+        }
+        Expr::Literal(Literal::Symbol(Symbol(name))) => {
+            Expr::Literal(Literal::Symbol(Symbol(name)))
+        }
+        other => unreachable!("places only hold names and constants, not {:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::print::pretty_print;
+
+    /// Lower a `main` function, returning its only statement.
+    fn lower_main(src: &str) -> Expr<'_> {
+        let mut program = parse(src).expect("should parse");
+        lower_negated(&mut program);
+
+        match program.0.remove(0) {
+            TopStmt::FnDecl(mut decl) => match decl.body.0.remove(0) {
+                Stmt::Expr(expr) => *expr,
+                other => panic!("expected an expression, found {:?}", other),
+            },
+            other => panic!("expected a function, found {:?}", other),
+        }
+    }
+
+    /// Whether the expression is `not` applied to the named identifier.
+    fn is_negated(expr: &Expr<'_>, name: &str) -> bool {
+        match expr {
+            Expr::FnCall(FnCall {
+                name: Ident("not"),
+                args,
+            }) => matches!(&args[..], [arg] if matches!(**arg, Expr::Ident(Ident(n)) if n == name)),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn lowers_unless_to_if() {
+        match lower_main("fn main() { unless done { step(); } else { stop(); } }") {
+            Expr::If(cond, then, Some(els)) => {
+                assert!(is_negated(&cond, "done"));
+                assert_eq!(then.0.len(), 1);
+                assert_eq!(els.0.len(), 1);
+            }
+            other => panic!("expected an if, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lowers_until_to_while() {
+        match lower_main("fn main() { until done { step(); } }") {
+            Expr::While(cond, body, None) => {
+                assert!(is_negated(&cond, "done"));
+                assert_eq!(body.0.len(), 1);
+            }
+            other => panic!("expected a while, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lowers_nested_conditionals() {
+        let src = "fn main() { if a { } else unless b { until c { } } }";
+
+        match lower_main(src) {
+            Expr::If(cond, _, Some(els)) => {
+                assert!(matches!(*cond, Expr::Ident(Ident("a"))));
+
+                match &els.0[..] {
+                    [Stmt::Expr(chained)] => match &**chained {
+                        Expr::If(cond, then, None) => {
+                            assert!(is_negated(cond, "b"));
+                            assert!(matches!(
+                                &then.0[..],
+                                [Stmt::Expr(inner)] if matches!(&**inner, Expr::While(cond, _, None) if is_negated(cond, "c"))
+                            ));
+                        }
+                        other => panic!("expected an if, found {:?}", other),
+                    },
+                    other => panic!("expected a chained if, found {:?}", other),
+                }
+            }
+            other => panic!("expected an if, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn canonicalizes_equivalent_programs() {
+        let a = "\
+// The entry point.
+/// Runs until done.
+fn main() {
+    // Keep going.
+    until done {
+        x = (a + (b * 0x10));
+    }
+    unless ok { stop(\"\\t\"); }
+}
+";
+        let b = "fn main() { while not done { x = a + b * 16; } if not (ok) { stop(\"\t\"); } }";
+
+        let a = parse(a).expect("should parse").canonicalize();
+        let b = parse(b).expect("should parse").canonicalize();
+        assert!(a.semantic_eq(&b));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn compares_literals_by_spelling_unless_asked_for_values() {
+        let a = parse("fn main() { f(0x10, \"\\t\", 'a', b\"\\x41\"); }").expect("should parse");
+        let b = parse("fn main() { f(16, r\"\t\", '\\u{61}', b\"A\"); }").expect("should parse");
+        let c = parse("fn main() { f(16, \"\\t\", 'a', b\"B\"); }").expect("should parse");
+
+        assert_ne!(a, b);
+        assert!(a.semantic_eq(&b));
+        assert!(!a.semantic_eq(&c));
+        assert_eq!(
+            a,
+            parse("fn main() { f(0x10, \"\\t\", 'a', b\"\\x41\"); }").unwrap()
+        );
+    }
+
+    #[test]
+    fn canonical_forms_keep_differences() {
+        let a = parse("fn main() { until done { x = a - b; } }").expect("should parse");
+        let b = parse("fn main() { until done { x = b - a; } }").expect("should parse");
+        assert_ne!(a.canonicalize(), b.canonicalize());
+    }
+
+    /// Lower the compound assignments in a program, and print it.
+    fn lower_compound(src: &str) -> String {
+        let mut program = parse(src).expect("should parse");
+        compound_assign(&mut program).expect("should lower");
+        pretty_print(&program)
+    }
+
+    #[test]
+    fn rejects_targets_with_too_many_computed_indices() {
+        let src = "fn main() { a[0][b][c][d][e][f][g][h][i][j] += 1; x += 1; }";
+        let mut program = parse(src).expect("should parse");
+        let error = compound_assign(&mut program).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "a compound assignment's target has 9 computed indices, but at most 8 can be lowered"
+        );
+        assert_eq!(
+            pretty_print(&program),
+            "fn main() {\n    a[0][b][c][d][e][f][g][h][i][j] += 1;\n    x += 1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn lowers_compound_assignments_to_names() {
+        assert_eq!(
+            lower_compound("fn main() { x -= f(); }"),
+            "fn main() {\n    x = x - f();\n}\n"
+        );
+    }
+
+    #[test]
+    fn evaluates_compound_assignment_indices_once() {
+        assert_eq!(
+            lower_compound("fn main() { a[i] += 1; }"),
+            "fn main() {\n    %index0 = i;\n    a[%index0] = a[%index0] + 1;\n}\n"
+        );
+        assert_eq!(
+            lower_compound("fn main() { grid[next()][0].cells[:top] *= 2; }"),
+            "fn main() {\n    %index0 = next();\n    \
+             grid[%index0][0].cells[:top] = grid[%index0][0].cells[:top] * 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn lowers_nested_compound_assignments_and_keeps_comments() {
+        let src = "fn main() {\n\
+                   if go {\n\
+                   /// Step.\n\
+                   xs[i] %= 3; // wrapped\n\
+                   }\n\
+                   }";
+
+        assert_eq!(
+            lower_compound(src),
+            "fn main() {\n    if go {\n        /// Step.\n        %index0 = i;\n        \
+             xs[%index0] = xs[%index0] % 3; // wrapped\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn leaves_if_and_while_alone() {
+        match lower_main("fn main() { while go { } }") {
+            Expr::While(cond, _, None) => assert!(matches!(*cond, Expr::Ident(Ident("go")))),
+            other => panic!("expected a while, found {:?}", other),
+        }
+    }
+}
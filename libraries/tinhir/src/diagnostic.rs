@@ -0,0 +1,406 @@
+//! Errors and warnings about the source code.
+
+use crate::span::Span;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A single observation about the source code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// How serious the diagnostic is.
+    pub severity: Severity,
+    /// The part of the source the diagnostic is about.
+    pub span: Span,
+    /// What's wrong, in a form suitable to show users.
+    pub message: String,
+    /// Other parts of the source involved, each with a label saying how,
+    /// like where a name was first defined.
+    pub related: Vec<(Span, String)>,
+    /// The stable code for this kind of diagnostic, like `E0002`, which
+    /// `explain` gives a longer description of.
+    pub code: Option<&'static str>,
+}
+
+/// How serious a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something worth knowing about the code, which isn't a problem.
+    Note,
+    /// The code is probably fine, but may not do what was meant.
+    Warning,
+    /// The code can't be compiled.
+    Error,
+}
+
+impl Diagnostic {
+    /// Create a new error.
+    pub fn error(span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message: message.into(),
+            related: Vec::new(),
+            code: None,
+        }
+    }
+
+    /// Create a new note.
+    pub fn note(span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Note,
+            span,
+            message: message.into(),
+            related: Vec::new(),
+            code: None,
+        }
+    }
+
+    /// Create a new warning.
+    pub fn warning(span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            span,
+            message: message.into(),
+            related: Vec::new(),
+            code: None,
+        }
+    }
+
+    /// Add a label to another part of the source involved.
+    pub fn with_related(mut self, span: Span, label: impl Into<String>) -> Diagnostic {
+        self.related.push((span, label.into()));
+        self
+    }
+
+    /// Give the diagnostic its code, which should be one `explain` knows.
+    pub fn with_code(mut self, code: &'static str) -> Diagnostic {
+        self.code = Some(code);
+        self
+    }
+
+    /// Whether this diagnostic is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// The severity, followed by the code in brackets if there is one, like
+    /// `error[E0002]`.
+    pub fn heading(&self) -> String {
+        match self.code {
+            Some(code) => format!("{}[{}]", self.severity, code),
+            None => self.severity.to_string(),
+        }
+    }
+}
+
+/// Defines the code constants, along with a list of them all for tests.
+macro_rules! codes {
+    ($($(#[$attr:meta])* $name:ident = $code:literal;)*) => {
+        $($(#[$attr])* pub const $name: &str = $code;)*
+
+        /// Every code, by the name of its constant.
+        #[cfg(test)]
+        pub(crate) const ALL: &[(&str, &str)] = &[$((stringify!($name), $name)),*];
+    };
+}
+
+/// The diagnostic codes, by what they're for, so the code a diagnostic is
+/// given can be checked against `DIAGNOSTIC_CODES`.
+pub mod codes {
+    codes! {
+        /// The source doesn't follow the grammar.
+        SYNTAX = "E0001";
+        /// Two top-level items have the same name.
+        DUPLICATE_NAME = "E0002";
+        /// `main` takes parameters.
+        MAIN_PARAMETERS = "E0003";
+        /// `main` returns a type the entry point can't.
+        MAIN_RETURN_TYPE = "E0004";
+        /// A variable is assigned an `if` or `unless` with no `else`.
+        MISSING_ELSE = "E0005";
+        /// The `break`s in a loop give disagreeing values.
+        BREAK_VALUES = "E0006";
+        /// A name contains an operator character.
+        OPERATOR_IN_NAME = "W0001";
+        /// Two names differ only in `_` or `-`.
+        CONFUSABLE_NAMES = "W0002";
+        /// An assignment shadows a parameter.
+        SHADOWED_PARAMETER = "W0003";
+        /// An integer has leading zeros.
+        LEADING_ZEROS = "W0004";
+        /// A line is indented with both tabs and spaces.
+        MIXED_INDENTATION = "W0005";
+        /// A line is indented differently from earlier lines.
+        INCONSISTENT_INDENTATION = "W0006";
+        /// A `use` imports something already imported.
+        DUPLICATE_USE = "W0007";
+        /// A function calls itself.
+        RECURSION = "N0001";
+    }
+}
+
+/// Every diagnostic code, with a longer explanation of what it means and
+/// how to fix it.
+///
+/// Codes are never reused, so they can be searched for. Errors start with
+/// `E`, warnings with `W`, and notes with `N`.
+pub const DIAGNOSTIC_CODES: &[(&str, &str)] = &[
+    (
+        codes::SYNTAX,
+        "The source doesn't follow Tin's grammar, so it can't be parsed.
+
+The message says what the parser expected to find at that point. Often
+something earlier, like a missing `;` or `}`, is the real cause.",
+    ),
+    (
+        codes::DUPLICATE_NAME,
+        "Two functions, types, or aliases have the same name.
+
+    fn area() { 1 }
+    fn area() { 2 }
+
+Every top-level item needs its own name, so that uses of it aren't
+ambiguous. Rename or remove one of them.",
+    ),
+    (
+        codes::MAIN_PARAMETERS,
+        "`main` takes parameters, but the entry point takes none.
+
+    fn main(args: Array<Str>) { }
+
+Remove the parameters from `main`.",
+    ),
+    (
+        codes::MAIN_RETURN_TYPE,
+        "`main` returns a type the entry point can't.
+
+    fn main() -> Str { \"done\" }
+
+`main` may return nothing, or an `Int` to use as the exit code.",
+    ),
+    (
+        codes::MISSING_ELSE,
+        "A variable is assigned the value of an `if` or `unless` with no
+`else`, so it has no value when the condition fails.
+
+    size = if big { 10 };
+
+Add an `else` giving the value to use otherwise.",
+    ),
+    (
+        codes::BREAK_VALUES,
+        "The `break`s in a loop give values of different kinds, or some give a
+value and some don't.
+
+    x = loop { if a { break 1; } break :none; };
+
+A loop has a single value, so every `break` leaving it must agree.",
+    ),
+    (
+        codes::OPERATOR_IN_NAME,
+        "A name contains an operator character, so it's a single identifier.
+
+    total = a+b;
+
+Names may contain `+`, `*`, `<`, and `>`, so `a+b` is one name rather than
+an addition. Put spaces around the operator: `a + b`.",
+    ),
+    (
+        codes::CONFUSABLE_NAMES,
+        "Two names differ only in using `_` or `-`, so they're easily
+confused.
+
+    some_name = 1;
+    print(some-name);
+
+Pick one spelling and use it everywhere.",
+    ),
+    (
+        codes::SHADOWED_PARAMETER,
+        "An assignment has the same name as a parameter of the function, so
+the parameter's value can't be used after it.
+
+    fn greet(name: Str) { name = \"you\"; }
+
+Use a different name for the new variable.",
+    ),
+    (
+        codes::LEADING_ZEROS,
+        "An integer has leading zeros, which Tin ignores.
+
+    mode = 0755;
+
+This is 755, not octal. Remove the zeros, or write the number in another
+base, like `0x1ed`.",
+    ),
+    (
+        codes::MIXED_INDENTATION,
+        "A line is indented with both tabs and spaces.
+
+Indent each line with only one of them.",
+    ),
+    (
+        codes::INCONSISTENT_INDENTATION,
+        "A line is indented with tabs where earlier lines use spaces, or the
+other way around.
+
+Indent the whole file the same way.",
+    ),
+    (
+        codes::DUPLICATE_USE,
+        "A `use` imports something that's already imported, either by the
+same path or by a `*` import of its module.
+
+    use std:io:print;
+    use std:io:*;
+
+Remove one of the imports.",
+    ),
+    (
+        codes::RECURSION,
+        "A function calls itself, directly or through other functions.
+
+This is fine, as long as the recursion ends. Make sure there's a case
+which returns without calling back in.",
+    ),
+];
+
+/// Get the longer explanation of a diagnostic code, if there is such a
+/// code.
+pub fn explain(code: &str) -> Option<&'static str> {
+    DIAGNOSTIC_CODES
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map(|(_, explanation)| *explanation)
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        use Severity::*;
+
+        match self {
+            Note => write!(f, "note"),
+            Warning => write!(f, "warning"),
+            Error => write!(f, "error"),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}: {}", self.heading(), self.message)
+    }
+}
+
+/// Count how many of the diagnostics there are of each severity.
+///
+/// Severities with no diagnostics are left out.
+pub fn count_diagnostics_by_severity(diagnostics: &[Diagnostic]) -> BTreeMap<Severity, usize> {
+    let mut counts = BTreeMap::new();
+
+    for diagnostic in diagnostics {
+        *counts.entry(diagnostic.severity).or_insert(0) += 1;
+    }
+
+    counts
+}
+
+/// Summarize the diagnostics for users, like "2 errors, 1 warning", with
+/// the most serious first.
+pub fn summarize_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    let counts = count_diagnostics_by_severity(diagnostics);
+
+    if counts.is_empty() {
+        return "no problems found".to_string();
+    }
+
+    counts
+        .into_iter()
+        .rev()
+        .map(|(severity, count)| match count {
+            1 => format!("1 {}", severity),
+            _ => format!("{} {}s", count, severity),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn summarizes_by_severity() {
+        let span = Span::new(0, 0);
+        let diagnostics = vec![
+            Diagnostic::warning(span, "unused"),
+            Diagnostic::error(span, "bad"),
+            Diagnostic::error(span, "worse"),
+        ];
+
+        assert_eq!(summarize_diagnostics(&diagnostics), "2 errors, 1 warning");
+
+        let counts = count_diagnostics_by_severity(&diagnostics);
+        assert_eq!(counts.get(&Severity::Note), None);
+        assert_eq!(summarize_diagnostics(&[]), "no problems found");
+    }
+
+    #[test]
+    fn shows_codes_in_headings() {
+        let span = Span::new(0, 0);
+        let diagnostic = Diagnostic::error(span, "bad").with_code(codes::SYNTAX);
+
+        assert_eq!(diagnostic.to_string(), "error[E0001]: bad");
+        assert_eq!(Diagnostic::note(span, "fyi").to_string(), "note: fyi");
+    }
+
+    #[test]
+    fn explains_every_code() {
+        assert!(explain("E0002").unwrap().contains("have the same name"));
+        assert_eq!(explain("E9999"), None);
+
+        for (i, (code, explanation)) in DIAGNOSTIC_CODES.iter().enumerate() {
+            assert!(!explanation.is_empty());
+            assert!(DIAGNOSTIC_CODES[..i]
+                .iter()
+                .all(|(earlier, _)| earlier != code));
+        }
+    }
+
+    #[test]
+    fn every_code_has_an_explanation() {
+        for (name, code) in codes::ALL {
+            assert!(explain(code).is_some(), "`{}` has no explanation", name);
+        }
+
+        for (code, _) in DIAGNOSTIC_CODES {
+            assert!(codes::ALL.iter().any(|(_, known)| known == code));
+        }
+    }
+
+    #[test]
+    fn diagnostics_are_given_named_codes() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+
+        for entry in fs::read_dir(dir).expect("should read the sources") {
+            let path = entry.expect("should read the sources").path();
+
+            // This file defines the codes, and searches for their uses.
+            if path.ends_with("diagnostic.rs") {
+                continue;
+            }
+
+            let source = fs::read_to_string(&path).expect("should read the source");
+
+            for (index, call) in source.match_indices(".with_code(") {
+                assert!(
+                    source[index + call.len()..].starts_with("codes::"),
+                    "{} gives a code by value rather than by name",
+                    path.display()
+                );
+            }
+        }
+    }
+}
@@ -1,10 +1,40 @@
+use crate::span::Span;
 use std::error::Error as StdError;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io;
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum Error {
-    ParseFailed,
+    /// The input didn't parse.
+    ParseFailed(ParseError),
     NoFile,
+    /// A file couldn't be read.
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// The input wasn't valid UTF-8, starting at the given byte offset.
+    InvalidUtf8(usize),
+    /// A top-level statement was edited at an index past the end of the
+    /// program, which had `len` statements.
+    NoStatement {
+        index: usize,
+        len: usize,
+    },
+    /// A compound assignment's target had more indices which needed a
+    /// temporary to lower than there are temporaries.
+    TooManyIndices {
+        needed: usize,
+        max: usize,
+    },
+    /// A keyword was renamed to a spelling which isn't shaped like an
+    /// identifier.
+    NotAWord(&'static str),
+    /// A keyword was renamed to a spelling which already means something.
+    KeywordTaken(&'static str),
+    /// `--explain` was asked about a code there's no explanation of.
+    UnknownCode(String),
 }
 
 impl StdError for Error {
@@ -12,18 +42,107 @@ impl StdError for Error {
         use Error::*;
 
         match self {
-            ParseFailed | NoFile => None,
+            NoFile
+            | InvalidUtf8(_)
+            | NoStatement { .. }
+            | TooManyIndices { .. }
+            | NotAWord(_)
+            | KeywordTaken(_)
+            | UnknownCode(_) => None,
+            ParseFailed(error) => Some(error),
+            Io { source, .. } => Some(source),
         }
     }
 }
 
+/// With `{:#}`, the error is followed by each of its causes, separated by
+/// `: `.
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         use Error::*;
 
         match self {
-            ParseFailed => write!(f, "parse failed"),
-            NoFile => write!(f, "no input file"),
+            ParseFailed(_) => write!(f, "parse failed")?,
+            NoFile => write!(f, "no input file")?,
+            Io { path, .. } => write!(f, "failed to read {}", path.display())?,
+            InvalidUtf8(offset) => write!(f, "invalid UTF-8 at byte {}", offset)?,
+            NoStatement { index, len } => write!(
+                f,
+                "no top-level statement {} in a program of {}",
+                index, len
+            )?,
+            TooManyIndices { needed, max } => write!(
+                f,
+                "a compound assignment's target has {} computed indices, but at most {} can be lowered",
+                needed, max
+            )?,
+            NotAWord(spelling) => write!(
+                f,
+                "`{}` isn't shaped like an identifier, so it can't spell a keyword",
+                spelling
+            )?,
+            UnknownCode(code) => write!(f, "there's no diagnostic code `{}`", code)?,
+            KeywordTaken(spelling) => {
+                write!(f, "`{}` already spells a keyword or literal", spelling)?
+            }
         }
+
+        if f.alternate() {
+            let mut cause = self.source();
+
+            while let Some(error) = cause {
+                write!(f, ": {}", error)?;
+                cause = error.source();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Why the input didn't parse, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// What went wrong, in a form suitable to show users.
+    pub message: String,
+    /// Where in the input parsing failed.
+    pub span: Span,
+    /// Every context the parser was in when it failed, innermost first.
+    ///
+    /// The first is always the message.
+    pub context: Vec<&'static str>,
+    /// The line of the input parsing failed on.
+    pub snippet: String,
+}
+
+impl StdError for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternate_display_includes_causes() {
+        let error = Error::Io {
+            path: PathBuf::from("prog.tin"),
+            source: io::Error::new(io::ErrorKind::NotFound, "no such file"),
+        };
+
+        assert_eq!(error.to_string(), "failed to read prog.tin");
+        assert_eq!(
+            format!("{:#}", error),
+            "failed to read prog.tin: no such file"
+        );
+    }
+
+    #[test]
+    fn alternate_display_without_causes() {
+        assert_eq!(format!("{:#}", Error::NoFile), "no input file");
     }
 }
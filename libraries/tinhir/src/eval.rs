@@ -0,0 +1,269 @@
+//! Evaluation of constant expressions.
+//!
+//! Only literals and operators over them are constant. Integer arithmetic
+//! is checked, so overflow and division by zero are reported as errors
+//! rather than wrapping or panicking. Float arithmetic follows IEEE 754,
+//! producing infinities and NaN, unless evaluating strictly.
+
+use crate::hir::*;
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// The value of a constant expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    /// A 64-bit signed integer.
+    Int(i64),
+    /// A 64-bit floating point number.
+    Float(f64),
+    /// A boolean.
+    Bool(bool),
+}
+
+/// The reasons a constant expression may fail to evaluate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// Integer arithmetic overflowed.
+    Overflow,
+    /// An integer was divided by zero.
+    DivisionByZero,
+    /// An integer remainder was taken with a divisor of zero.
+    RemainderByZero,
+    /// An integer was raised to a negative power.
+    NegativeExponent,
+    /// A float operation produced an infinity or NaN, in strict mode.
+    NonFinite,
+    /// An operator was applied to values of the wrong types.
+    TypeMismatch(String),
+    /// The expression isn't constant.
+    NotConstant,
+}
+
+impl StdError for EvalError {}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        use EvalError::*;
+
+        match self {
+            Overflow => write!(f, "integer overflow"),
+            DivisionByZero => write!(f, "division by zero"),
+            RemainderByZero => write!(f, "remainder by zero"),
+            NegativeExponent => write!(f, "integer raised to a negative power"),
+            NonFinite => write!(f, "float operation produced a non-finite value"),
+            TypeMismatch(op) => write!(f, "mismatched types for `{}`", op),
+            NotConstant => write!(f, "expression is not constant"),
+        }
+    }
+}
+
+/// Evaluate a constant expression.
+pub fn eval_const(expr: &Expr<'_>) -> Result<Value, EvalError> {
+    eval(expr, false)
+}
+
+/// Evaluate a constant expression, treating non-finite floats as errors.
+pub fn eval_const_strict(expr: &Expr<'_>) -> Result<Value, EvalError> {
+    eval(expr, true)
+}
+
+fn eval(expr: &Expr<'_>, strict: bool) -> Result<Value, EvalError> {
+    let value = match expr {
+        Expr::Literal(Literal::Int(Int(value, _))) => Value::Int(*value),
+        Expr::Literal(Literal::Float(Float(value, _))) => Value::Float(*value),
+        Expr::Literal(Literal::Bool(Bool(value, _))) => Value::Bool(*value),
+        Expr::FnCall(call) if call.short_circuits() => {
+            let decided = call.name.0 == "or";
+
+            match eval(&call.args[0], strict)? {
+                Value::Bool(lhs) if lhs == decided => Value::Bool(lhs),
+                Value::Bool(_) => match eval(&call.args[1], strict)? {
+                    Value::Bool(rhs) => Value::Bool(rhs),
+                    _ => return Err(EvalError::TypeMismatch(call.name.0.to_string())),
+                },
+                _ => return Err(EvalError::TypeMismatch(call.name.0.to_string())),
+            }
+        }
+        Expr::FnCall(FnCall { name, args }) => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, strict))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            match args[..] {
+                [operand] => unary(name.0, operand)?,
+                [lhs, rhs] => binary(name.0, lhs, rhs)?,
+                _ => return Err(EvalError::NotConstant),
+            }
+        }
+        _ => return Err(EvalError::NotConstant),
+    };
+
+    match value {
+        Value::Float(value) if strict && !value.is_finite() => Err(EvalError::NonFinite),
+        value => Ok(value),
+    }
+}
+
+fn unary(op: &str, operand: Value) -> Result<Value, EvalError> {
+    use Value::*;
+
+    match (op, operand) {
+        ("-", Int(value)) => value.checked_neg().map(Int).ok_or(EvalError::Overflow),
+        ("-", Float(value)) => Ok(Float(-value)),
+        ("not", Bool(value)) => Ok(Bool(!value)),
+        ("-", _) | ("not", _) => Err(EvalError::TypeMismatch(op.to_string())),
+        _ => Err(EvalError::NotConstant),
+    }
+}
+
+fn binary(op: &str, lhs: Value, rhs: Value) -> Result<Value, EvalError> {
+    use Value::*;
+
+    match (lhs, rhs) {
+        (Int(lhs), Int(rhs)) => int_binary(op, lhs, rhs),
+        (Float(lhs), Float(rhs)) => float_binary(op, lhs, rhs),
+        (Bool(lhs), Bool(rhs)) => match op {
+            "xor" => Ok(Bool(lhs ^ rhs)),
+            "==" => Ok(Bool(lhs == rhs)),
+            "!=" => Ok(Bool(lhs != rhs)),
+            _ => Err(EvalError::TypeMismatch(op.to_string())),
+        },
+        _ => Err(EvalError::TypeMismatch(op.to_string())),
+    }
+}
+
+fn int_binary(op: &str, lhs: i64, rhs: i64) -> Result<Value, EvalError> {
+    use Value::*;
+
+    let value = match op {
+        "+" => lhs.checked_add(rhs),
+        "-" => lhs.checked_sub(rhs),
+        "*" => lhs.checked_mul(rhs),
+        "/" if rhs == 0 => return Err(EvalError::DivisionByZero),
+        "/" => lhs.checked_div(rhs),
+        "%" if rhs == 0 => return Err(EvalError::RemainderByZero),
+        "%" => lhs.checked_rem(rhs),
+        "^" if rhs < 0 => return Err(EvalError::NegativeExponent),
+        "^" => u32::try_from(rhs)
+            .ok()
+            .and_then(|exponent| lhs.checked_pow(exponent)),
+        "<<" => u32::try_from(rhs).ok().and_then(|by| lhs.checked_shl(by)),
+        ">>" => u32::try_from(rhs).ok().and_then(|by| lhs.checked_shr(by)),
+        "&" => Some(lhs & rhs),
+        "|" => Some(lhs | rhs),
+        "~" => Some(lhs ^ rhs),
+        _ => return compare(op, lhs, rhs),
+    };
+
+    value.map(Int).ok_or(EvalError::Overflow)
+}
+
+fn float_binary(op: &str, lhs: f64, rhs: f64) -> Result<Value, EvalError> {
+    use Value::*;
+
+    match op {
+        "+" => Ok(Float(lhs + rhs)),
+        "-" => Ok(Float(lhs - rhs)),
+        "*" => Ok(Float(lhs * rhs)),
+        "/" => Ok(Float(lhs / rhs)),
+        "%" => Ok(Float(lhs % rhs)),
+        "^" => Ok(Float(lhs.powf(rhs))),
+        _ => compare(op, lhs, rhs),
+    }
+}
+
+fn compare<T: PartialOrd>(op: &str, lhs: T, rhs: T) -> Result<Value, EvalError> {
+    let value = match op {
+        "==" => lhs == rhs,
+        "!=" => lhs != rhs,
+        "<" => lhs < rhs,
+        ">" => lhs > rhs,
+        "<=" => lhs <= rhs,
+        ">=" => lhs >= rhs,
+        _ => return Err(EvalError::TypeMismatch(op.to_string())),
+    };
+
+    Ok(Value::Bool(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::expr;
+
+    fn eval_src(src: &str) -> Result<Value, EvalError> {
+        eval_const(&expr(src).expect("should parse").1)
+    }
+
+    #[test]
+    fn negates_zero_to_negative_zero() {
+        match eval_src("-0.0") {
+            Ok(Value::Float(value)) => assert_eq!(value.to_bits(), (-0.0f64).to_bits()),
+            other => panic!("expected a float, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn evaluates_arithmetic() {
+        assert_eq!(eval_src("1 + 2 * 3"), Ok(Value::Int(7)));
+        assert_eq!(eval_src("-(2 ^ 10) % 1000"), Ok(Value::Int(-24)));
+        assert_eq!(eval_src("1 < 2 and not false"), Ok(Value::Bool(true)));
+        assert_eq!(eval_src("1 << 4 | 3 ~ 1"), Ok(Value::Int(18)));
+    }
+
+    #[test]
+    fn exponents_group_to_the_right() {
+        assert_eq!(eval_src("2 ^ 3 ^ 2"), Ok(Value::Int(512)));
+        assert_eq!(eval_src("(2 ^ 3) ^ 2"), Ok(Value::Int(64)));
+        assert_eq!(eval_src("2.0 ^ 3.0 ^ 2.0"), Ok(Value::Float(512.0)));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(eval_src("false and 1 / 0 == 0"), Ok(Value::Bool(false)));
+        assert_eq!(eval_src("true or 1 / 0 == 0"), Ok(Value::Bool(true)));
+        assert_eq!(
+            eval_src("true and 1 / 0 == 0"),
+            Err(EvalError::DivisionByZero)
+        );
+        assert_eq!(
+            eval_src("true and 1"),
+            Err(EvalError::TypeMismatch("and".to_string()))
+        );
+        assert_eq!(eval_src("false or true"), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn integer_overflow_is_an_error() {
+        assert_eq!(eval_src("2 ^ 100"), Err(EvalError::Overflow));
+        assert_eq!(eval_src("1 << 64"), Err(EvalError::Overflow));
+        assert_eq!(
+            eval_src("9223372036854775807 + 1"),
+            Err(EvalError::Overflow)
+        );
+    }
+
+    #[test]
+    fn division_by_zero_is_an_error() {
+        assert_eq!(eval_src("1 / 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval_src("1 % 0"), Err(EvalError::RemainderByZero));
+    }
+
+    #[test]
+    fn float_infinity_is_flagged_only_when_strict() {
+        let expr = expr("1.0 / 0.0").expect("should parse").1;
+        assert_eq!(eval_const(&expr), Ok(Value::Float(f64::INFINITY)));
+        assert_eq!(eval_const_strict(&expr), Err(EvalError::NonFinite));
+    }
+
+    #[test]
+    fn mixed_types_are_an_error() {
+        assert_eq!(
+            eval_src("1 + 1.0"),
+            Err(EvalError::TypeMismatch("+".to_string()))
+        );
+        assert_eq!(eval_src("x + 1"), Err(EvalError::NotConstant));
+    }
+}
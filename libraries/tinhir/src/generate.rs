@@ -0,0 +1,736 @@
+//! Generating random programs which are valid according to the grammar.
+//!
+//! These are for seeding fuzzing corpora, so fuzzers start from inputs
+//! which reach deep into the parser instead of failing on the first byte.
+//! Every kind of literal, statement, and expression can be generated, but
+//! the programs aren't meaningful beyond parsing.
+
+use crate::parse::BINARY;
+
+/// Names which are never keywords.
+const NAMES: &[&str] = &["a", "b", "count", "some-name", "item_2", "total", "x"];
+
+/// Names of types.
+const TYS: &[&str] = &["Int", "Float", "String", "Bool", "Items"];
+
+/// Operators which can update a variable, like `+` in `x += 1`.
+const COMPOUND: &[&str] = &["+", "-", "*", "/", "%", "^"];
+
+/// How deeply blocks and expressions are nested before only leaves are
+/// generated.
+const MAX_DEPTH: usize = 4;
+
+/// Generate the source of a random valid program from a seed.
+///
+/// The same seed always produces the same program.
+pub fn generate_program(seed: u64) -> String {
+    Generator::new(seed).program()
+}
+
+/// A generator of random valid source code.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    state: u64,
+    depth: usize,
+    out: String,
+}
+
+impl Generator {
+    /// Create a generator from a seed.
+    pub fn new(seed: u64) -> Generator {
+        Generator {
+            // The state of an xorshift generator must never be zero.
+            state: (seed ^ 0x9E37_79B9_7F4A_7C15) | 1,
+            depth: 0,
+            out: String::new(),
+        }
+    }
+
+    /// Generate the source of a whole program.
+    pub fn program(&mut self) -> String {
+        self.out.clear();
+        self.fn_decl("main");
+
+        for _ in 0..self.below(6) {
+            match self.below(4) {
+                0 => {
+                    let text = self.pick(NAMES);
+                    self.text(&format!("// about {}\n", text));
+                }
+                1 => self.use_stmt(),
+                2 => {
+                    let name = self.pick(NAMES);
+                    self.fn_decl(name);
+                }
+                _ if self.below(4) == 0 => self.ty_alias(),
+                _ => self.ty_decl(),
+            }
+        }
+
+        std::mem::take(&mut self.out)
+    }
+
+    fn use_stmt(&mut self) {
+        let path = match self.below(3) {
+            0 => "std:io:println",
+            1 => "std:io:*",
+            _ => "std:{io:print, fs:read}",
+        };
+
+        self.text(&format!("use {};\n", path));
+    }
+
+    fn fn_decl(&mut self, name: &str) {
+        self.meta();
+        self.text(&format!("fn {}", name));
+        self.signature();
+        self.text(" ");
+        self.block();
+        self.text("\n");
+    }
+
+    /// A doc comment and attributes, each of which may be left off.
+    fn meta(&mut self) {
+        if self.chance() {
+            self.text("/// Does things.\n");
+        }
+
+        if self.chance() {
+            self.text("#[inline]\n");
+        }
+
+        if self.chance() {
+            self.text("#[when(unix, ");
+            self.expr();
+            self.text(")]\n");
+        }
+    }
+
+    fn signature(&mut self) {
+        self.text("(");
+
+        for i in 0..self.below(3) {
+            if i > 0 {
+                self.text(", ");
+            }
+
+            self.ty_ident(i);
+        }
+
+        self.text(")");
+
+        match self.below(4) {
+            0 => {}
+            1 => self.text(" -> ()"),
+            2 => {
+                let (first, second) = (self.pick(TYS), self.pick(TYS));
+                self.text(&format!(" -> ({}, {})", first, second));
+            }
+            _ => {
+                let ty = self.pick(TYS);
+                self.text(&format!(" -> {}", ty));
+            }
+        }
+    }
+
+    /// A name with a type, where `i` keeps names in the same list distinct.
+    fn ty_ident(&mut self, i: usize) {
+        let name = self.pick(NAMES);
+        let ty = self.pick(TYS);
+        self.text(&format!("{}{}: {}", name, i, ty));
+    }
+
+    fn ty_decl(&mut self) {
+        self.meta();
+        let name = self.pick(TYS);
+        self.text(&format!("type {}", name));
+
+        if self.chance() {
+            self.text("<A, B>");
+        }
+
+        match self.below(3) {
+            0 => {
+                self.text(" {");
+                self.named_fields();
+                self.text("}\n");
+            }
+            1 => {
+                self.anonymous_fields();
+                self.text("\n");
+            }
+            _ => {
+                let empty = self.pick(&["Empty()", "Empty"]);
+                self.text(&format!(" {{ {}, Wrap", empty));
+                self.anonymous_fields();
+                self.text(", Named {");
+                self.named_fields();
+                self.text("} }\n");
+            }
+        }
+    }
+
+    /// An alias, which never refers to itself, since `Str` isn't one of the
+    /// names it may be given.
+    fn ty_alias(&mut self) {
+        self.meta();
+        let name = self.pick(TYS);
+        let ty = self.pick(&["Array<Str>", "Map<Str>", "(Str, Str)"]);
+        self.text(&format!("type {} = {};\n", name, ty));
+    }
+
+    fn named_fields(&mut self) {
+        for i in 0..=self.below(3) {
+            self.text(if i > 0 { ", " } else { " " });
+            self.ty_ident(i);
+        }
+
+        self.text(" ");
+    }
+
+    fn anonymous_fields(&mut self) {
+        self.text("(");
+
+        for i in 0..=self.below(3) {
+            if i > 0 {
+                self.text(", ");
+            }
+
+            let ty = self.pick(TYS);
+            self.text(ty);
+        }
+
+        self.text(")");
+    }
+
+    fn block(&mut self) {
+        self.text("{\n");
+        self.depth += 1;
+
+        if self.depth <= MAX_DEPTH {
+            for _ in 0..self.below(4) {
+                self.stmt();
+            }
+        }
+
+        self.depth -= 1;
+        self.text("}");
+    }
+
+    fn stmt(&mut self) {
+        match self.below(10) {
+            0 => self.text("// note\n"),
+            1 => {
+                self.pattern(true);
+                self.text(", ");
+                self.pattern(true);
+                self.text(" = ");
+                self.expr();
+                self.text(", ");
+                self.expr();
+                self.text(";\n");
+            }
+            2..=3 => {
+                self.pattern(true);
+
+                if self.chance() {
+                    let ty = self.pick(TYS);
+                    self.text(&format!(": {}", ty));
+                }
+
+                self.text(" = ");
+                self.expr();
+                self.text(";\n");
+            }
+            4 => {
+                if self.below(4) == 0 {
+                    self.text(":done ");
+                    self.block();
+                } else {
+                    self.block_expr();
+                }
+
+                self.text("\n");
+            }
+            5 => {
+                match self.below(3) {
+                    0 => self.text("continue"),
+                    1 => self.jump("break"),
+                    _ => self.jump("return"),
+                }
+
+                self.text(";\n");
+            }
+            6 => {
+                let name = self.pick(NAMES);
+                self.text(name);
+
+                match self.below(4) {
+                    0 => self.text(".x"),
+                    1 => self.text("[i]"),
+                    _ => {}
+                }
+
+                let op = self.pick(COMPOUND);
+                self.text(&format!(" {}= ", op));
+                self.expr();
+                self.text(";\n");
+            }
+            _ => {
+                self.call();
+                self.text(";\n");
+            }
+        }
+    }
+
+    /// The left side of an assignment, which can only destructure at the
+    /// top or nested in another destructuring.
+    fn pattern(&mut self, top: bool) {
+        let name = self.pick(NAMES);
+
+        if (!top && self.depth > MAX_DEPTH) || self.below(3) > 0 {
+            self.text(name);
+
+            match self.below(8) {
+                0 => self.text(".x"),
+                1 => self.text("[0]"),
+                _ => {}
+            }
+
+            return;
+        }
+
+        let (open, close) = if self.chance() {
+            ("#(", ")")
+        } else {
+            ("#[", "]")
+        };
+
+        self.text(open);
+        self.depth += 1;
+        self.pattern(false);
+        self.text(", ");
+        self.pattern(false);
+
+        if self.chance() {
+            self.text(", ..rest");
+        }
+
+        self.depth -= 1;
+        self.text(close);
+    }
+
+    fn jump(&mut self, kw: &str) {
+        self.text(kw);
+
+        if self.chance() {
+            // A symbol right after `break` would be read as a label, so
+            // values are put in parentheses.
+            let wrap = kw == "break";
+            self.text(if wrap { " (" } else { " " });
+            self.expr();
+            self.text(if wrap { ")" } else { "" });
+        }
+    }
+
+    /// An expression which ends in a block, and so can stand alone as a
+    /// statement without a `;`.
+    fn block_expr(&mut self) {
+        match self.below(7) {
+            kw @ 0..=1 => {
+                self.text(if kw == 0 { "if " } else { "unless " });
+                self.operand();
+                self.text(" ");
+                self.block();
+
+                match self.below(3) {
+                    0 => {}
+                    1 => {
+                        self.text(" else ");
+                        self.block();
+                    }
+                    _ => {
+                        self.text(" else if ");
+                        self.operand();
+                        self.text(" ");
+                        self.block();
+                    }
+                }
+            }
+            kw @ 2..=3 => {
+                self.text(if kw == 2 { "while " } else { "until " });
+                self.operand();
+                self.text(" ");
+                self.block();
+                self.loop_else();
+            }
+            4 => {
+                let name = self.pick(NAMES);
+                self.text(&format!("for {} in ", name));
+                self.operand();
+                self.text(" ");
+                self.block();
+                self.loop_else();
+            }
+            5 => {
+                self.text("match ");
+                self.operand();
+                self.text(" {");
+
+                for _ in 0..=self.below(3) {
+                    self.text(" ");
+                    self.arm_pattern();
+                    self.text(" => ");
+
+                    if self.chance() {
+                        self.block();
+                    } else {
+                        self.expr();
+                        self.text(",");
+                    }
+                }
+
+                self.text(" }");
+            }
+            _ => {
+                self.text("loop ");
+                self.block();
+            }
+        }
+    }
+
+    /// The pattern of a `match` arm.
+    fn arm_pattern(&mut self) {
+        match self.below(3) {
+            0 => {
+                let literal = self.pick(&["0", "2.5", "'c'", ":done", "\"s\"", "true"]);
+                self.text(literal);
+            }
+            1 => {
+                let name = self.pick(NAMES);
+                self.text(name);
+            }
+            _ => {
+                let variant = self.pick(&["None", "Some", "Pair"]);
+                self.text(variant);
+
+                if variant != "None" {
+                    self.text("(");
+                    self.arm_pattern();
+                    self.text(")");
+                }
+            }
+        }
+    }
+
+    fn loop_else(&mut self) {
+        if self.chance() {
+            self.text(" else ");
+            self.block();
+        }
+    }
+
+    /// Any expression.
+    fn expr(&mut self) {
+        if self.depth > MAX_DEPTH {
+            return self.leaf();
+        }
+
+        self.depth += 1;
+
+        match self.below(6) {
+            0 => self.block_expr(),
+            1 => {
+                self.text("fn");
+                self.signature();
+                self.text(" ");
+                self.block();
+            }
+            _ => self.operand(),
+        }
+
+        self.depth -= 1;
+    }
+
+    /// An expression which doesn't end in a block, so it can be used where
+    /// a block follows, like in a condition.
+    fn operand(&mut self) {
+        if self.depth > MAX_DEPTH {
+            return self.leaf();
+        }
+
+        self.depth += 1;
+
+        match self.below(10) {
+            0 => {
+                // Binary operators are all written between spaces.
+                let (op, _) = BINARY[self.below(BINARY.len())];
+                self.text("(");
+                self.operand();
+                self.text(&format!(" {} ", op));
+                self.operand();
+                self.text(")");
+            }
+            1 => {
+                let op = self.pick(&["-", "not "]);
+                self.text(&format!("({}", op));
+                self.operand();
+                self.text(")");
+            }
+            2 => {
+                self.text("(@");
+                self.place();
+                self.text(")");
+            }
+            3 => self.place(),
+            4 => {
+                self.call();
+
+                if self.below(4) == 0 {
+                    self.text("?");
+                }
+            }
+            _ => self.leaf(),
+        }
+
+        self.depth -= 1;
+    }
+
+    /// A name, field, or element.
+    fn place(&mut self) {
+        let name = self.pick(NAMES);
+        self.text(name);
+
+        match self.below(4) {
+            0 => {
+                let field = self.pick(NAMES);
+                self.text(&format!(".{}", field));
+            }
+            1 => {
+                self.text("[");
+                self.operand();
+                self.text("]");
+            }
+            _ => {}
+        }
+    }
+
+    /// A function or method call.
+    fn call(&mut self) {
+        let name = self.pick(NAMES);
+        self.text(name);
+
+        if self.chance() {
+            let method = self.pick(NAMES);
+            self.text(&format!(".{}", method));
+        }
+
+        self.text("(");
+
+        for i in 0..self.below(3) {
+            if i > 0 {
+                self.text(", ");
+            }
+
+            self.expr();
+        }
+
+        self.text(")");
+    }
+
+    /// A name or literal.
+    fn leaf(&mut self) {
+        if self.below(4) == 0 {
+            let name = self.pick(NAMES);
+            self.text(name);
+        } else {
+            self.literal();
+        }
+    }
+
+    fn literal(&mut self) {
+        let nested = self.depth <= MAX_DEPTH;
+        let choices = if nested { 13 } else { 10 };
+
+        match self.below(choices) {
+            0 => {
+                let value = self.pick(&["true", "false"]);
+                self.text(value);
+            }
+            1 => {
+                let value = self.below(1000);
+                self.text(&value.to_string());
+            }
+            2 => {
+                let value = self.below(0x10000);
+                self.text(&format!("0x{:X}", value));
+            }
+            3 => {
+                let value = self.below(256);
+                self.text(&format!("0b{:b}", value));
+            }
+            4 => {
+                let float = self.pick(&["1.5", "0.25e3", "7e-2", "100.0"]);
+                self.text(float);
+            }
+            5 => {
+                let ustr = self.pick(&[
+                    r#""hello""#,
+                    r#""a\tb\n\"q\"""#,
+                    r#""\u{1F600}\0\\""#,
+                    r#"r"C:\path""#,
+                    r##"r#"say "hi""#"##,
+                ]);
+                self.text(ustr);
+            }
+            6 => {
+                let bstr = self.pick(&[r#"b"bytes""#, r#"b"a\tb""#]);
+                self.text(bstr);
+            }
+            7 => {
+                let c = self.pick(&["'c'", r"'\n'", r"'\''", r"'\u{e9}'"]);
+                self.text(c);
+            }
+            8 | 9 => {
+                let name = self.pick(NAMES);
+                self.text(&format!(":{}", name));
+            }
+            10 => self.literals("#[", "]"),
+            11 => self.literals("#(", ")"),
+            _ => {
+                self.depth += 1;
+                self.text("#{");
+
+                for i in 0..self.below(3) {
+                    let key = self.pick(NAMES);
+                    self.text(&format!("{}{}{}: ", if i > 0 { ", " } else { " " }, key, i));
+                    self.expr();
+                }
+
+                self.text(" }");
+                self.depth -= 1;
+            }
+        }
+    }
+
+    /// The elements of an array or tuple literal.
+    fn literals(&mut self, open: &str, close: &str) {
+        self.depth += 1;
+        self.text(open);
+        let count = self.below(4);
+
+        for i in 0..count {
+            if i > 0 {
+                self.text(", ");
+            }
+
+            self.leaf();
+        }
+
+        // A tuple of one needs a trailing comma.
+        if count == 1 && open == "#(" {
+            self.text(",");
+        }
+
+        self.text(close);
+        self.depth -= 1;
+    }
+
+    fn text(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    /// A random number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % n as u64) as usize
+    }
+
+    fn chance(&mut self) -> bool {
+        self.below(2) == 0
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::visit::Node;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn generated_programs_parse() {
+        for seed in 0..500 {
+            let src = generate_program(seed);
+
+            if let Err(error) = parse(&src) {
+                panic!("seed {} failed to parse: {:#}\n{}", seed, error, src);
+            }
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        assert_eq!(generate_program(7), generate_program(7));
+        assert_ne!(generate_program(7), generate_program(8));
+    }
+
+    #[test]
+    fn generates_every_kind_of_node() {
+        let mut literals = BTreeSet::new();
+        let mut exprs = BTreeSet::new();
+        let mut stmts = BTreeSet::new();
+
+        for seed in 0..500 {
+            let src = generate_program(seed);
+            let program = parse(&src).expect("should parse");
+
+            for (_, node) in program.traverse() {
+                match node {
+                    Node::Literal(literal) => {
+                        literals.insert(literal.kind_name());
+                    }
+                    Node::Expr(expr) => {
+                        let name = format!("{:?}", expr);
+                        exprs.insert(name[..name.find('(').unwrap_or(name.len())].to_string());
+                    }
+                    Node::Comment(_) => {
+                        stmts.insert("comment");
+                    }
+                    Node::PathGlob(_) => {
+                        stmts.insert("use");
+                    }
+                    Node::FnDecl(_) => {
+                        stmts.insert("fn");
+                    }
+                    Node::TyDecl(_) => {
+                        stmts.insert("type");
+                    }
+                    Node::TyAlias(_) => {
+                        stmts.insert("alias");
+                    }
+                    Node::VarAssign(_) => {
+                        stmts.insert("assign");
+                    }
+                    Node::MultiAssign(_) => {
+                        stmts.insert("multiple assign");
+                    }
+                    Node::CompoundAssign(_) => {
+                        stmts.insert("compound assign");
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        assert_eq!(literals.len(), 10, "missing literals: {:?}", literals);
+        assert_eq!(exprs.len(), 18, "missing expressions: {:?}", exprs);
+        assert_eq!(stmts.len(), 8, "missing statements: {:?}", stmts);
+    }
+}
@@ -14,24 +14,163 @@
 
 #![allow(dead_code)]
 
+use crate::charset::is_ty_continue;
+use crate::error::Error;
+use crate::parse::Keyword;
 use std::borrow::Cow;
-use std::collections::HashMap;
 use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::iter::FromIterator;
 
 /// A complete parsed program.
 ///
 /// A complete program is a collection of top-level statements
 /// with the one requirement being the presence of a `main` function
 /// declaration.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Program<'prgrm>(pub Vec<TopStmt<'prgrm>>);
 
+impl<'prgrm> Program<'prgrm> {
+    /// Whether the program has no top-level statements at all.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the top-level comments, in source order.
+    pub fn comments(&self) -> impl Iterator<Item = &Comment<'prgrm>> {
+        self.0.iter().filter_map(|stmt| match stmt {
+            TopStmt::Comment(comment) => Some(comment),
+            _ => None,
+        })
+    }
+
+    /// Iterate over the use statements, in source order.
+    pub fn uses(&self) -> impl Iterator<Item = &PathGlob<'prgrm>> {
+        self.0.iter().filter_map(|stmt| match stmt {
+            TopStmt::Use(path) => Some(path),
+            _ => None,
+        })
+    }
+
+    /// Iterate over the function declarations, in source order.
+    pub fn functions(&self) -> impl Iterator<Item = &FnDecl<'prgrm>> {
+        self.0.iter().filter_map(|stmt| match stmt {
+            TopStmt::FnDecl(decl) => Some(decl),
+            _ => None,
+        })
+    }
+
+    /// The `main` function, where the program starts, wherever it's
+    /// declared.
+    pub fn entry_point(&self) -> Option<&FnDecl<'prgrm>> {
+        self.functions().find(|decl| decl.name.0 == "main")
+    }
+
+    /// Iterate mutably over the bodies of the function declarations, in
+    /// source order, for passes which only rewrite bodies.
+    pub fn function_bodies_mut(&mut self) -> impl Iterator<Item = &mut Block<'prgrm>> {
+        self.0.iter_mut().filter_map(|stmt| match stmt {
+            TopStmt::FnDecl(decl) => Some(&mut decl.body),
+            _ => None,
+        })
+    }
+
+    /// Reorder the top-level statements by kind: uses first, then types,
+    /// then functions.
+    ///
+    /// Statements of the same kind keep their order. Comments move with the
+    /// statement after them, and comments at the end stay at the end.
+    pub fn sort_by_kind(&mut self) {
+        let mut groups: Vec<(TopStmtKind, Vec<TopStmt<'prgrm>>)> = Vec::new();
+        let mut pending = Vec::new();
+
+        for stmt in self.0.drain(..) {
+            let kind = stmt.kind();
+            pending.push(stmt);
+
+            if kind != TopStmtKind::Comment {
+                groups.push((kind, std::mem::take(&mut pending)));
+            }
+        }
+
+        if !pending.is_empty() {
+            groups.push((TopStmtKind::Comment, pending));
+        }
+
+        groups.sort_by_key(|(kind, _)| *kind);
+        self.0 = groups.into_iter().flat_map(|(_, stmts)| stmts).collect();
+    }
+
+    /// Iterate over the type declarations, in source order.
+    pub fn types(&self) -> impl Iterator<Item = &TyDecl<'prgrm>> {
+        self.0.iter().filter_map(|stmt| match stmt {
+            TopStmt::TyDecl(decl) => Some(decl),
+            _ => None,
+        })
+    }
+
+    /// Count the top-level statements of each kind.
+    pub fn statement_count_by_kind(&self) -> StmtCounts {
+        let mut counts = StmtCounts::default();
+
+        for stmt in &self.0 {
+            let count = match stmt.kind() {
+                TopStmtKind::Use => &mut counts.uses,
+                TopStmtKind::TyDecl => &mut counts.types,
+                TopStmtKind::TyAlias => &mut counts.aliases,
+                TopStmtKind::FnDecl => &mut counts.functions,
+                TopStmtKind::Comment => &mut counts.comments,
+            };
+            *count += 1;
+        }
+
+        counts
+    }
+
+    /// Iterate over the type aliases, in source order.
+    pub fn aliases(&self) -> impl Iterator<Item = &TyAlias<'prgrm>> {
+        self.0.iter().filter_map(|stmt| match stmt {
+            TopStmt::TyAlias(alias) => Some(alias),
+            _ => None,
+        })
+    }
+
+    /// Insert a top-level statement before the one at `index`, or at the
+    /// end if `index` is the number of statements.
+    pub fn insert_statement(&mut self, index: usize, stmt: TopStmt<'prgrm>) -> Result<(), Error> {
+        if index > self.0.len() {
+            return Err(self.no_statement(index));
+        }
+
+        self.0.insert(index, stmt);
+        Ok(())
+    }
+
+    /// Replace the top-level statement at `index`, returning the old one.
+    pub fn replace_statement(
+        &mut self,
+        index: usize,
+        stmt: TopStmt<'prgrm>,
+    ) -> Result<TopStmt<'prgrm>, Error> {
+        match self.0.get_mut(index) {
+            Some(old) => Ok(std::mem::replace(old, stmt)),
+            None => Err(self.no_statement(index)),
+        }
+    }
+
+    fn no_statement(&self, index: usize) -> Error {
+        Error::NoStatement {
+            index,
+            len: self.0.len(),
+        }
+    }
+}
+
 /// A top-level statement.
 ///
 /// A few types of statements are allowed at the top of the
 /// program, including comments, use-statements, and function
 /// declarations.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum TopStmt<'prgrm> {
     /// A comment block.
     Comment(Comment<'prgrm>),
@@ -41,32 +180,127 @@ pub enum TopStmt<'prgrm> {
     FnDecl(FnDecl<'prgrm>),
     /// A type declaration.
     TyDecl(TyDecl<'prgrm>),
+    /// A type alias.
+    TyAlias(TyAlias<'prgrm>),
+}
+
+impl<'prgrm> TopStmt<'prgrm> {
+    /// The kind of statement this is.
+    pub fn kind(&self) -> TopStmtKind {
+        match self {
+            TopStmt::Comment(_) => TopStmtKind::Comment,
+            TopStmt::Use(_) => TopStmtKind::Use,
+            TopStmt::FnDecl(_) => TopStmtKind::FnDecl,
+            TopStmt::TyDecl(_) => TopStmtKind::TyDecl,
+            TopStmt::TyAlias(_) => TopStmtKind::TyAlias,
+        }
+    }
+}
+
+/// The kinds of top-level statement, ordered as `Program::sort_by_kind`
+/// puts them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TopStmtKind {
+    /// A use statement.
+    Use,
+    /// A type declaration.
+    TyDecl,
+    /// A type alias.
+    TyAlias,
+    /// A function declaration.
+    FnDecl,
+    /// A comment block.
+    Comment,
+}
+
+/// How many top-level statements of each kind a program has, from
+/// `Program::statement_count_by_kind`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StmtCounts {
+    /// The number of use statements.
+    pub uses: usize,
+    /// The number of type declarations.
+    pub types: usize,
+    /// The number of type aliases.
+    pub aliases: usize,
+    /// The number of function declarations.
+    pub functions: usize,
+    /// The number of comment blocks.
+    pub comments: usize,
 }
 
 /// A statement.
 ///
 /// Statements have no R-value. Generally expect them to be on their
 /// own line.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Stmt<'prgrm> {
     /// A comment is a statement.
     Comment(Comment<'prgrm>),
     /// Assigning, may be declaring a new variable.
     VarAssign(VarAssign<'prgrm>),
+    /// Assigning several values at once.
+    MultiAssign(MultiAssign<'prgrm>),
+    /// Updating a variable, field, or element with an operator.
+    CompoundAssign(CompoundAssign<'prgrm>),
     /// An expression.
     ///
     /// Expressions may be treated as statements, implicitly throwing
     /// away the R-value.
     Expr(BExpr<'prgrm>),
+    /// A statement with a doc comment right before it, with the lines of
+    /// the comment joined by newlines.
+    ///
+    /// The statement is never itself a comment or documented.
+    Documented(String, Box<Stmt<'prgrm>>),
+    /// A statement with a comment after it on the same line, holding the
+    /// text after the `//`.
+    ///
+    /// The statement is never itself a comment, documented, or trailed.
+    Trailed(Box<Stmt<'prgrm>>, &'prgrm str),
+}
+
+impl<'prgrm> Stmt<'prgrm> {
+    /// The doc comment on the statement, if it has one.
+    pub fn doc(&self) -> Option<&str> {
+        match self {
+            Stmt::Documented(doc, _) => Some(doc),
+            _ => None,
+        }
+    }
+
+    /// The statement without its doc comment.
+    pub fn undocumented(&self) -> &Stmt<'prgrm> {
+        match self {
+            Stmt::Documented(_, stmt) => stmt,
+            stmt => stmt,
+        }
+    }
+
+    /// The comment after the statement on the same line, if it has one.
+    pub fn trailing_comment(&self) -> Option<&'prgrm str> {
+        match self.undocumented() {
+            Stmt::Trailed(_, comment) => Some(comment),
+            _ => None,
+        }
+    }
+
+    /// The statement without its doc comment or trailing comment.
+    pub fn without_comments(&self) -> &Stmt<'prgrm> {
+        match self.undocumented() {
+            Stmt::Trailed(stmt, _) => stmt,
+            stmt => stmt,
+        }
+    }
 }
 
 /// A convenience type wrapping `Expr` in a `Box`.
-type BExpr<'prgrm> = Box<Expr<'prgrm>>;
+pub type BExpr<'prgrm> = Box<Expr<'prgrm>>;
 
 /// An expression.
 ///
 /// Unlike statements, expressions _have_ R-values.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Expr<'prgrm> {
     /// An `if` (optionally with an `else`)
     If(BExpr<'prgrm>, Block<'prgrm>, Option<Block<'prgrm>>),
@@ -74,31 +308,60 @@ pub enum Expr<'prgrm> {
     Unless(BExpr<'prgrm>, Block<'prgrm>, Option<Block<'prgrm>>),
     /// An unconditional loop
     Loop(Block<'prgrm>),
-    /// A `while` loop
-    While(BExpr<'prgrm>, Block<'prgrm>),
-    /// An `until` loop
-    Until(BExpr<'prgrm>, Block<'prgrm>),
-    /// A `for x in y` loop.
-    For(BExpr<'prgrm>, BExpr<'prgrm>, Block<'prgrm>),
+    /// A `while` loop (optionally with an `else`)
+    ///
+    /// The `else` block runs only if the body executed zero times.
+    While(BExpr<'prgrm>, Block<'prgrm>, Option<Block<'prgrm>>),
+    /// An `until` loop (optionally with an `else`)
+    ///
+    /// The `else` block runs only if the body executed zero times.
+    Until(BExpr<'prgrm>, Block<'prgrm>, Option<Block<'prgrm>>),
+    /// A `for x in y` loop (optionally with an `else`)
+    ///
+    /// The `else` block runs only if the body executed zero times.
+    For(
+        BExpr<'prgrm>,
+        BExpr<'prgrm>,
+        Block<'prgrm>,
+        Option<Block<'prgrm>>,
+    ),
     /// A continue, jumping to the next loop iteration.
     Continue(&'prgrm str),
     /// A break, ending loop iteration, optionally with a value.
-    Break(Option<BExpr<'prgrm>>, &'prgrm str),
+    ///
+    /// A break with a label leaves the labeled block instead.
+    Break(Option<Label<'prgrm>>, Option<BExpr<'prgrm>>, &'prgrm str),
+    /// A return, ending the function, optionally with a value.
+    Return(Option<BExpr<'prgrm>>, &'prgrm str),
+    /// A labeled block, which a `break` naming the label leaves early,
+    /// making the block's value that of the `break`.
+    Labeled(Label<'prgrm>, Block<'prgrm>),
+    /// A `match`, running the first arm whose pattern matches the value.
+    Match(Match<'prgrm>),
     /// A function call.
     ///
     /// Function calls are also how operators are represented.
     FnCall(FnCall<'prgrm>),
     /// An identifier.
     Ident(Ident<'prgrm>),
+    /// A literal value.
+    Literal(Literal<'prgrm>),
     /// A field access.
     Dot(BExpr<'prgrm>, BExpr<'prgrm>),
+    /// An index into an array or map (e.g. `items[0]` or `config[:name]`),
+    /// holding the base and then the index.
+    Index(BExpr<'prgrm>, BExpr<'prgrm>),
+    /// Propagating an error out of the function, like `read(path)?`.
+    ///
+    /// What counts as an error isn't worked out yet.
+    Try(BExpr<'prgrm>),
+    /// An anonymous function.
+    Lambda(Lambda<'prgrm>),
 }
 
 /// A literal value.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Literal<'prgrm> {
-    /// An identifier (e.g. `some-name`)
-    Ident(Ident<'prgrm>),
     /// A boolean value (e.g. `true` or `false`)
     Bool(Bool<'prgrm>),
     /// A 64-bit integer (e.g. `645`)
@@ -126,30 +389,161 @@ pub enum Literal<'prgrm> {
 ///
 /// If the variable identifier hasn't been declared in scope before,
 /// this is treated as declaring a new variable.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct VarAssign<'prgrm> {
-    /// The name of the variable being declared.
-    pub name: Ident<'prgrm>,
+    /// The variable being declared, or a pattern destructuring the value
+    /// into several variables.
+    pub pattern: Pattern<'prgrm>,
     /// Optionally, a typing annotation.
     pub ty: Option<Ty<'prgrm>>,
     /// The RHS of the assignment, used to produce the value.
     pub rhs: BExpr<'prgrm>,
 }
 
+/// An assignment of several values at once, like `a, b = b, a`.
+///
+/// Every value is evaluated before any are assigned, so this can swap
+/// variables. There are always as many values as patterns.
+#[derive(Debug, PartialEq)]
+pub struct MultiAssign<'prgrm> {
+    /// The variables being assigned, in order.
+    pub patterns: Vec<Pattern<'prgrm>>,
+    /// The values, one for each pattern.
+    pub values: Vec<BExpr<'prgrm>>,
+}
+
+/// An assignment which updates a variable, field, or element with an
+/// operator, like `a[i] += 1`.
+///
+/// The target is evaluated once, before the value. `desugar::compound_assign`
+/// lowers these into plain assignments which keep that order.
+#[derive(Debug, PartialEq)]
+pub struct CompoundAssign<'prgrm> {
+    /// The variable, field, or element being updated, which is never a
+    /// tuple or array pattern.
+    pub target: Pattern<'prgrm>,
+    /// The operator, like `+` for `+=`, which is the name of the call it
+    /// lowers to.
+    pub op: &'prgrm str,
+    /// The value combined with the target's.
+    pub rhs: BExpr<'prgrm>,
+}
+
+/// The left-hand side of an assignment.
+#[derive(Debug, PartialEq)]
+pub enum Pattern<'prgrm> {
+    /// A single variable (e.g. `x`)
+    Ident(Ident<'prgrm>),
+    /// A tuple destructure (e.g. `#(a, b)`)
+    Tuple(Vec<Pattern<'prgrm>>, Option<Rest<'prgrm>>),
+    /// An array destructure (e.g. `#[first, ..rest]`)
+    Array(Vec<Pattern<'prgrm>>, Option<Rest<'prgrm>>),
+    /// A field or element of an existing value (e.g. `point.x` or
+    /// `items[0]`), which is always an `Expr::Dot` or `Expr::Index`.
+    ///
+    /// Assigning to a place changes part of a value, so it declares nothing.
+    Place(BExpr<'prgrm>),
+}
+
+/// The `..` at the end of a pattern, matching any remaining elements.
+///
+/// The remaining elements may be bound to a name, as in `..rest`, or
+/// ignored, as in `..`.
+#[derive(Debug, PartialEq)]
+pub struct Rest<'prgrm>(pub Option<Ident<'prgrm>>);
+
+/// The label of a block, like `init` in `:init { ... }`, without its `:`.
+#[derive(Debug, PartialEq)]
+pub struct Label<'prgrm>(pub &'prgrm str);
+
+/// A `match` expression, like `match n { 0 => :none, n => :some }`.
+///
+/// Arms are tried in order. Whether they cover every value isn't checked.
+#[derive(Debug, PartialEq)]
+pub struct Match<'prgrm> {
+    /// The value being matched.
+    pub scrutinee: BExpr<'prgrm>,
+    /// The arms, in source order.
+    pub arms: Vec<Arm<'prgrm>>,
+}
+
+/// A single arm of a `match`.
+///
+/// An arm written with an expression instead of a block, as in `0 => :none`,
+/// has a block holding only that expression.
+#[derive(Debug, PartialEq)]
+pub struct Arm<'prgrm> {
+    /// The pattern the value must match for the arm to run.
+    pub pattern: ArmPattern<'prgrm>,
+    /// The body of the arm.
+    pub body: Block<'prgrm>,
+}
+
+/// The pattern of a `match` arm.
+#[derive(Debug, PartialEq)]
+pub enum ArmPattern<'prgrm> {
+    /// A literal, matching values equal to it (e.g. `0` or `:done`)
+    Literal(Literal<'prgrm>),
+    /// A name, matching any value and binding it (e.g. `n`)
+    Bind(Ident<'prgrm>),
+    /// A variant of a type, with patterns for its fields (e.g. `Some(n)` or
+    /// `None`)
+    ///
+    /// Variant names start with an uppercase letter, like type names, which
+    /// is what tells them apart from bindings.
+    Variant(Ident<'prgrm>, Vec<ArmPattern<'prgrm>>),
+}
+
 /// A type declaration.
 ///
 /// Sum and product types are declared uniformly in Tin.
 /// A type can have any number of variants, and each variant
 /// can have a set of either named or unnamed fields.
-#[derive(Debug)]
-pub struct TyDecl<'prgrm>(pub Vec<TyVariant<'prgrm>>);
+#[derive(Debug, PartialEq)]
+pub struct TyDecl<'prgrm> {
+    /// The documentation and attributes on the type.
+    pub meta: ItemMeta<'prgrm>,
+    /// The name of the type.
+    pub name: Ident<'prgrm>,
+    /// The generic type parameters, like `A` and `B` in `Pair<A, B>`.
+    pub params: Vec<Ident<'prgrm>>,
+    /// The variants of the type.
+    pub variants: Vec<TyVariant<'prgrm>>,
+}
+
+impl<'prgrm> TyDecl<'prgrm> {
+    /// Get the documentation and attributes on the type.
+    pub fn meta(&self) -> &ItemMeta<'prgrm> {
+        &self.meta
+    }
+
+    /// Whether a type used in the declaration refers to one of its generic
+    /// parameters.
+    pub fn is_param(&self, ty: &Ty<'_>) -> bool {
+        self.params.iter().any(|param| param.0 == ty.0)
+    }
+}
+
+/// A type alias, like `type Names = Array<Str>;`.
+///
+/// Unlike a `TyDecl`, this doesn't declare a new type. The alias can be
+/// used anywhere the aliased type could.
+#[derive(Debug, PartialEq)]
+pub struct TyAlias<'prgrm> {
+    /// The documentation and attributes on the alias.
+    pub meta: ItemMeta<'prgrm>,
+    /// The name of the alias.
+    pub name: Ident<'prgrm>,
+    /// The type it stands for.
+    pub ty: Ty<'prgrm>,
+}
 
 /// A single variant of a type.
 ///
 /// A type variant may have a name. If only variant is present,
 /// then the name is optional. If there are multiple variants,
 /// they all must have names.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct TyVariant<'prgrm> {
     /// The name of the type.
     pub name: Option<Ident<'prgrm>>,
@@ -161,8 +555,8 @@ pub struct TyVariant<'prgrm> {
 ///
 /// Can either be named, in which case they include both the name
 /// and the type, or anonymous, in which case they include only the
-/// type.
-#[derive(Debug)]
+/// type. A variant without fields has no anonymous fields.
+#[derive(Debug, PartialEq)]
 pub enum Fields<'prgrm> {
     /// All fields are named.
     Named(Vec<TyIdent<'prgrm>>),
@@ -170,11 +564,21 @@ pub enum Fields<'prgrm> {
     Anonymous(Vec<Ty<'prgrm>>),
 }
 
+impl<'prgrm> Fields<'prgrm> {
+    /// Whether there are no fields.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Fields::Named(fields) => fields.is_empty(),
+            Fields::Anonymous(fields) => fields.is_empty(),
+        }
+    }
+}
+
 /// A combination of an identifier with a type.
 ///
 /// This is generally used to express the assign of type to a variable
 /// or field.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct TyIdent<'prgrm> {
     /// The identifier having a type assigned.
     pub ident: Ident<'prgrm>,
@@ -183,7 +587,7 @@ pub struct TyIdent<'prgrm> {
 }
 
 /// A function call.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct FnCall<'prgrm> {
     /// The name of the function being called.
     pub name: Ident<'prgrm>,
@@ -191,31 +595,158 @@ pub struct FnCall<'prgrm> {
     pub args: Vec<BExpr<'prgrm>>,
 }
 
+impl<'prgrm> FnCall<'prgrm> {
+    /// Whether this is a logical `and` or `or`, which only evaluates its
+    /// right operand if the left one doesn't already decide the result.
+    ///
+    /// The bitwise `&` and `|` are ordinary calls, and always evaluate both
+    /// operands.
+    pub fn short_circuits(&self) -> bool {
+        self.name.is_operator() && matches!(self.name.0, "and" | "or") && self.args.len() == 2
+    }
+}
+
 /// A function declaration.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct FnDecl<'prgrm> {
+    /// The documentation and attributes on the function.
+    pub meta: ItemMeta<'prgrm>,
     /// The name of the new function.
     pub name: Ident<'prgrm>,
     /// The arguments to the function.
     pub args: Vec<TyIdent<'prgrm>>,
-    /// (Optionally) the function return type.
-    pub ret_ty: Option<Ty<'prgrm>>,
+    /// The function return type.
+    pub ret_ty: ReturnType<'prgrm>,
+    /// The body of the function.
+    pub body: Block<'prgrm>,
+}
+
+impl<'prgrm> FnDecl<'prgrm> {
+    /// Get the documentation and attributes on the function.
+    pub fn meta(&self) -> &ItemMeta<'prgrm> {
+        &self.meta
+    }
+}
+
+/// The metadata written before an item.
+///
+/// Doc comments start with `///`, and come before any attributes. They're
+/// kept apart from ordinary comments so tools like doc generators can find
+/// them.
+#[derive(Debug, Default, PartialEq)]
+pub struct ItemMeta<'prgrm> {
+    /// The text of the doc comment, with its lines joined by newlines.
+    pub doc: Option<String>,
+    /// The attributes on the item.
+    pub attrs: Vec<Attr<'prgrm>>,
+}
+
+/// An anonymous function, like `fn(x: Int) -> Int { x + 1 }`.
+///
+/// Lambdas are written like function declarations without a name. What
+/// they capture from the surrounding scope isn't worked out yet.
+#[derive(Debug, PartialEq)]
+pub struct Lambda<'prgrm> {
+    /// The arguments to the function.
+    pub args: Vec<TyIdent<'prgrm>>,
+    /// The function return type.
+    pub ret_ty: ReturnType<'prgrm>,
     /// The body of the function.
     pub body: Block<'prgrm>,
 }
 
-/// A type.
-#[derive(Debug)]
+/// An attribute on an item, like `#[when(unix)]`.
+#[derive(Debug, PartialEq)]
+pub struct Attr<'prgrm> {
+    /// The name of the attribute.
+    pub name: Ident<'prgrm>,
+    /// The arguments to the attribute, which may be left off.
+    pub args: Vec<BExpr<'prgrm>>,
+}
+
+/// The return type of a function.
+///
+/// Leaving off the return type is different from explicitly returning
+/// unit with `-> ()`, as it leaves the return type to be inferred.
+#[derive(Debug, PartialEq)]
+pub enum ReturnType<'prgrm> {
+    /// No return type was written.
+    Inferred,
+    /// The function returns unit, written `-> ()`.
+    Unit,
+    /// The function returns the given type.
+    Explicit(Ty<'prgrm>),
+    /// The function returns several values, written like `-> (Int, Bool)`,
+    /// which callers can destructure with a tuple pattern.
+    Tuple(Vec<Ty<'prgrm>>),
+}
+
+impl<'prgrm> ReturnType<'prgrm> {
+    /// Get the explicitly-written return type, if there is exactly one.
+    pub fn ty(&self) -> Option<&Ty<'prgrm>> {
+        match self {
+            ReturnType::Explicit(ty) => Some(ty),
+            ReturnType::Inferred | ReturnType::Unit | ReturnType::Tuple(_) => None,
+        }
+    }
+
+    /// Get every explicitly-written return type, which is more than one for
+    /// a function returning several values.
+    pub fn tys(&self) -> &[Ty<'prgrm>] {
+        match self {
+            ReturnType::Explicit(ty) => std::slice::from_ref(ty),
+            ReturnType::Tuple(tys) => tys,
+            ReturnType::Inferred | ReturnType::Unit => &[],
+        }
+    }
+}
+
+/// A type, as written, including any generic arguments.
+#[derive(Debug, PartialEq)]
 pub struct Ty<'prgrm>(pub &'prgrm str);
 
+impl<'prgrm> Ty<'prgrm> {
+    /// Iterate over the names in the type, like `Array` and `Str` in
+    /// `Array<Str>`, in the order they're written.
+    pub fn names(&self) -> impl Iterator<Item = &'prgrm str> {
+        self.0
+            .split(|c: char| !is_ty_continue(c))
+            .filter(|name| !name.is_empty())
+    }
+}
+
 /// A block of statements.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Block<'prgrm>(pub Vec<Stmt<'prgrm>>);
 
+impl<'prgrm> IntoIterator for Block<'prgrm> {
+    type Item = Stmt<'prgrm>;
+    type IntoIter = std::vec::IntoIter<Stmt<'prgrm>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, 'prgrm> IntoIterator for &'a Block<'prgrm> {
+    type Item = &'a Stmt<'prgrm>;
+    type IntoIter = std::slice::Iter<'a, Stmt<'prgrm>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<'prgrm> FromIterator<Stmt<'prgrm>> for Block<'prgrm> {
+    fn from_iter<I: IntoIterator<Item = Stmt<'prgrm>>>(stmts: I) -> Self {
+        Block(stmts.into_iter().collect())
+    }
+}
+
 /// A comment block.
 ///
 /// Comments may be a reference to a string in the program (if single-line)
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Comment<'prgrm> {
     /// If a single line, a comment is a slice to the line.
     SingleLine(&'prgrm str),
@@ -241,59 +772,614 @@ impl<'prgrm> Display for Comment<'prgrm> {
     }
 }
 
+impl<'prgrm> Comment<'prgrm> {
+    /// The lines of the comment, without the leading `//`.
+    pub fn lines(&self) -> &[&'prgrm str] {
+        match self {
+            Comment::SingleLine(line) => std::slice::from_ref(line),
+            Comment::MultiLine(lines) => lines,
+        }
+    }
+
+    /// The code in fenced blocks in the comment, like examples to test.
+    ///
+    /// Blocks start and end with a line of three backticks, and only those
+    /// with no language or `tin` after the opening fence are Tin code. The
+    /// fence's indentation is removed from each line of the block, and a
+    /// block left open runs to the end of the comment.
+    pub fn code_blocks(&self) -> Vec<String> {
+        let mut blocks = Vec::new();
+        let mut open: Option<(usize, bool, Vec<&str>)> = None;
+
+        for line in self.lines() {
+            let text = line.trim_start();
+            let indent = line.len() - text.len();
+
+            match (&mut open, text.strip_prefix("```")) {
+                (Some(_), Some(rest)) if rest.trim().is_empty() => {
+                    if let Some((_, true, code)) = open.take() {
+                        blocks.push(code.join("\n"));
+                    }
+                }
+                (Some((fence, _, code)), _) => code.push(&line[indent.min(*fence)..]),
+                (None, Some(lang)) => {
+                    let lang = lang.trim();
+                    open = Some((indent, lang.is_empty() || lang == "tin", Vec::new()));
+                }
+                (None, None) => {}
+            }
+        }
+
+        if let Some((_, true, code)) = open {
+            blocks.push(code.join("\n"));
+        }
+
+        blocks
+    }
+}
+
 /// A glob specifier for a path.
 ///
 /// This may resolve into multiple paths.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct PathGlob<'prgrm>(pub &'prgrm str);
 
 /// A single path to an external module.
 ///
 /// Path contains a `Cow` to avoid allocating in cases where no globs are used.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Path<'prgrm>(pub Cow<'prgrm, str>);
 
 /// A boolean value.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Bool<'prgrm>(pub bool, pub &'prgrm str);
 
 /// A 64-bit signed integer.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Int<'prgrm>(pub i64, pub &'prgrm str);
 
 /// A 64-bit floating point number.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Float<'prgrm>(pub f64, pub &'prgrm str);
 
+/// Floats are displayed in the shortest form which parses back to exactly
+/// the same value, like `0.1` or `1e300`, always with a fraction or an
+/// exponent so they aren't read back as integers.
+///
+/// Float literals can't be infinite or NaN, so those are displayed as the
+/// divisions which produce them: `(1.0 / 0.0)`, `(-1.0 / 0.0)`, and
+/// `(0.0 / 0.0)`.
+impl<'prgrm> Display for Float<'prgrm> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let value = self.0;
+
+        if value.is_nan() {
+            write!(f, "(0.0 / 0.0)")
+        } else if value.is_infinite() {
+            let sign = if value < 0.0 { "-" } else { "" };
+            write!(f, "({}1.0 / 0.0)", sign)
+        } else {
+            // `Debug` is the shortest round-trip form, and unlike `Display`
+            // keeps the `.0` on whole numbers and uses exponents for very
+            // large or small ones.
+            write!(f, "{:?}", value)
+        }
+    }
+}
+
 /// An interned string used as keys in hashmaps or other
 /// places where string identity is important.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Symbol<'prgrm>(pub &'prgrm str);
 
 /// A UTF-8 encoded string.
-#[derive(Debug)]
-pub struct UStr<'prgrm>(pub &'prgrm str);
+///
+/// The contents are as written in the source, between the quotes, so any
+/// escapes are left in place.
+#[derive(Debug, PartialEq)]
+pub struct UStr<'prgrm>(pub &'prgrm str, pub StrStyle);
+
+impl<'prgrm> UStr<'prgrm> {
+    /// Whether this is a raw string, without escapes.
+    pub fn is_raw(&self) -> bool {
+        matches!(self.1, StrStyle::Raw(_))
+    }
+
+    /// Render the string as it would be written in source code.
+    pub fn to_source(&self) -> String {
+        match self.1 {
+            StrStyle::Escaped => format!("\"{}\"", self.0),
+            StrStyle::Raw(hashes) => {
+                let hashes = "#".repeat(hashes);
+                format!("r{}\"{}\"{}", hashes, self.0, hashes)
+            }
+        }
+    }
+}
+
+/// How a string literal was written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrStyle {
+    /// A normal string, which may contain escapes (e.g. `"a\tb"`)
+    Escaped,
+    /// A raw string without escapes, with the number of `#`s around it
+    /// (e.g. `r#"a "quoted" b"#`)
+    Raw(usize),
+}
 
 /// A byte-vector built from ASCII text.
-#[derive(Debug)]
+///
+/// The bytes are as written, with escapes left in place. `BStr::value`
+/// gives the bytes they stand for.
+#[derive(Debug, PartialEq)]
 pub struct BStr<'prgrm>(pub &'prgrm [u8], pub &'prgrm str);
 
 /// An identifier.
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Ident<'prgrm>(pub &'prgrm str);
 
+impl<'prgrm> Ident<'prgrm> {
+    /// Whether this is the name of an operator, like `+` or `and`, as used
+    /// in the calls operators are represented by.
+    ///
+    /// Keyword operators are named with the keyword's own spelling, so a name
+    /// which only reads the same, like a variable `and` when that keyword is
+    /// disabled, isn't one.
+    pub fn is_operator(&self) -> bool {
+        !self.0.starts_with(|c: char| c.is_ascii_alphabetic())
+            || [Keyword::And, Keyword::Or, Keyword::Xor, Keyword::Not]
+                .iter()
+                .any(|kw| std::ptr::eq(self.0, kw.spelling()))
+    }
+}
+
 /// A UTF-8 code-point (32-bits).
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct Char<'prgrm>(pub char, pub &'prgrm str);
 
+/// Literals which keep their whole source text know its length in bytes.
+macro_rules! source_len_of_src {
+    ($($ty:ident),*) => {
+        $(
+            impl<'prgrm> $ty<'prgrm> {
+                /// The length of the literal in the source, in bytes.
+                pub fn source_len(&self) -> usize {
+                    self.1.len()
+                }
+            }
+        )*
+    };
+}
+
+source_len_of_src!(Bool, Int, Float, BStr, Char);
+
+impl<'prgrm> UStr<'prgrm> {
+    /// The length of the string in the source, in bytes, including its
+    /// quotes and any `r` and `#`s.
+    pub fn source_len(&self) -> usize {
+        match self.1 {
+            StrStyle::Escaped => self.0.len() + 2,
+            StrStyle::Raw(hashes) => self.0.len() + 3 + 2 * hashes,
+        }
+    }
+}
+
+impl<'prgrm> Symbol<'prgrm> {
+    /// The length of the symbol in the source, in bytes, including its `:`.
+    pub fn source_len(&self) -> usize {
+        self.0.len() + 1
+    }
+}
+
+impl<'prgrm> Ident<'prgrm> {
+    /// The length of the identifier in the source, in bytes.
+    pub fn source_len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<'prgrm> Literal<'prgrm> {
+    /// The length of the literal in the source, in bytes, if it's a single
+    /// token rather than a container of other values.
+    pub fn source_len(&self) -> Option<usize> {
+        use Literal::*;
+
+        match self {
+            Bool(bool) => Some(bool.source_len()),
+            Int(int) => Some(int.source_len()),
+            Float(float) => Some(float.source_len()),
+            UStr(ustr) => Some(ustr.source_len()),
+            BStr(bstr) => Some(bstr.source_len()),
+            Char(char) => Some(char.source_len()),
+            Symbol(symbol) => Some(symbol.source_len()),
+            Array(_) | Tuple(_) | Map(_) => None,
+        }
+    }
+}
+
 /// A homogeneous container of values.
-#[derive(Debug)]
-pub struct Array<'prgrm>(pub Vec<Literal<'prgrm>>);
+///
+/// Elements may be any expression, like `#[-1, x + 1]`. The element type
+/// may be given, like `#[]: Array<Int>`, which an empty array needs to have
+/// a known type. Without it, the element type is unknown.
+#[derive(Debug, PartialEq)]
+pub struct Array<'prgrm>(pub Vec<BExpr<'prgrm>>, pub Option<Ty<'prgrm>>);
 
 /// A heterogeneous container of values.
-#[derive(Debug)]
-pub struct Tuple<'prgrm>(pub Vec<Literal<'prgrm>>);
+///
+/// Elements may be any expression, like `#(-1, f(x))`.
+#[derive(Debug, PartialEq)]
+pub struct Tuple<'prgrm>(pub Vec<BExpr<'prgrm>>);
 
 /// A hashmap of symbols to expressions.
-#[derive(Debug)]
-pub struct Map<'prgrm>(HashMap<Symbol<'prgrm>, BExpr<'prgrm>>);
+///
+/// Entries are kept in source order. The value type may be given, like
+/// `#{}: Map<Int>`, as for arrays.
+#[derive(Debug, PartialEq)]
+pub struct Map<'prgrm>(
+    pub Vec<(Symbol<'prgrm>, BExpr<'prgrm>)>,
+    pub Option<Ty<'prgrm>>,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixed_program() -> Program<'static> {
+        Program(vec![
+            TopStmt::Comment(Comment::SingleLine(" First.")),
+            TopStmt::Use(PathGlob("std:io")),
+            TopStmt::FnDecl(FnDecl {
+                meta: ItemMeta::default(),
+                name: Ident("main"),
+                args: vec![],
+                ret_ty: ReturnType::Inferred,
+                body: Block(vec![]),
+            }),
+            TopStmt::TyDecl(TyDecl {
+                meta: ItemMeta::default(),
+                name: Ident("Unit"),
+                params: vec![],
+                variants: vec![],
+            }),
+            TopStmt::Use(PathGlob("std:fs:*")),
+            TopStmt::Comment(Comment::SingleLine(" Last.")),
+        ])
+    }
+
+    #[test]
+    fn extracts_a_code_block() {
+        let comment = Comment::MultiLine(vec![
+            " Adds one.",
+            "",
+            " ```tin",
+            " fn main() {",
+            "     print(inc(1));",
+            " }",
+            " ```",
+        ]);
+
+        assert_eq!(
+            comment.code_blocks(),
+            vec!["fn main() {\n    print(inc(1));\n}"]
+        );
+    }
+
+    #[test]
+    fn extracts_several_indented_code_blocks() {
+        let comment = Comment::MultiLine(vec![
+            " Either:",
+            "   ```",
+            "   x = 1;",
+            "   ```",
+            " ```text",
+            " not tin",
+            " ```",
+            " or:",
+            "     ```tin",
+            "     x = 2;",
+            "   y = 3;",
+        ]);
+
+        assert_eq!(comment.code_blocks(), vec!["x = 1;", "x = 2;\ny = 3;"]);
+        assert!(Comment::SingleLine(" ```").code_blocks()[0].is_empty());
+    }
+
+    #[test]
+    fn floats_round_trip() {
+        let tricky = [
+            0.1,
+            0.30000000000000004,
+            1.0,
+            123456789.125,
+            1e300,
+            1.7976931348623157e308,
+            2.5e-8,
+            5e-324,
+        ];
+
+        for value in tricky.iter() {
+            let text = Float(*value, "").to_string();
+            let reparsed: f64 = text.parse().expect("should be a valid float");
+            assert_eq!(
+                reparsed.to_bits(),
+                value.to_bits(),
+                "{} didn't round-trip",
+                text
+            );
+            assert!(
+                text.contains('.') || text.contains('e'),
+                "{} looks like an int",
+                text
+            );
+        }
+
+        assert_eq!(Float(0.1, "").to_string(), "0.1");
+        assert_eq!(Float(1e300, "").to_string(), "1e300");
+    }
+
+    #[test]
+    fn non_finite_floats_display_as_divisions() {
+        assert_eq!(Float(f64::INFINITY, "").to_string(), "(1.0 / 0.0)");
+        assert_eq!(Float(f64::NEG_INFINITY, "").to_string(), "(-1.0 / 0.0)");
+        assert_eq!(Float(f64::NAN, "").to_string(), "(0.0 / 0.0)");
+    }
+
+    #[test]
+    fn filters_comments() {
+        let comments: Vec<_> = mixed_program()
+            .comments()
+            .map(|comment| comment.to_string())
+            .collect();
+        assert_eq!(comments, vec![" First.", " Last."]);
+    }
+
+    #[test]
+    fn filters_uses() {
+        let program = mixed_program();
+        let uses: Vec<_> = program.uses().map(|path| path.0).collect();
+        assert_eq!(uses, vec!["std:io", "std:fs:*"]);
+    }
+
+    #[test]
+    fn filters_functions() {
+        let program = mixed_program();
+        let names: Vec<_> = program.functions().map(|decl| decl.name.0).collect();
+        assert_eq!(names, vec!["main"]);
+    }
+
+    #[test]
+    fn counts_statements_by_kind() {
+        let mut program = mixed_program();
+        program.0.push(TopStmt::TyAlias(TyAlias {
+            meta: ItemMeta::default(),
+            name: Ident("Count"),
+            ty: Ty("Int"),
+        }));
+
+        assert_eq!(
+            program.statement_count_by_kind(),
+            StmtCounts {
+                uses: 2,
+                types: 1,
+                aliases: 1,
+                functions: 1,
+                comments: 2,
+            }
+        );
+        assert_eq!(
+            Program(vec![]).statement_count_by_kind(),
+            StmtCounts::default()
+        );
+    }
+
+    #[test]
+    fn inserts_statements_anywhere() {
+        let mut program = mixed_program();
+        let len = program.0.len();
+
+        program
+            .insert_statement(0, TopStmt::Use(PathGlob("front")))
+            .expect("should insert at the front");
+        program
+            .insert_statement(3, TopStmt::Use(PathGlob("middle")))
+            .expect("should insert in the middle");
+        program
+            .insert_statement(len + 2, TopStmt::Use(PathGlob("end")))
+            .expect("should insert at the end");
+
+        let uses: Vec<_> = program.uses().map(|path| path.0).collect();
+        assert_eq!(uses, vec!["front", "std:io", "middle", "std:fs:*", "end"]);
+        assert_eq!(program.0[3], TopStmt::Use(PathGlob("middle")));
+        assert_eq!(program.0.len(), len + 3);
+
+        let error = program
+            .insert_statement(len + 4, TopStmt::Use(PathGlob("past")))
+            .expect_err("should be out of bounds");
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "no top-level statement {} in a program of {}",
+                len + 4,
+                len + 3
+            )
+        );
+    }
+
+    #[test]
+    fn replaces_statements() {
+        let mut program = mixed_program();
+
+        let old = program
+            .replace_statement(1, TopStmt::Use(PathGlob("std:net")))
+            .expect("should replace");
+        assert_eq!(old, TopStmt::Use(PathGlob("std:io")));
+        assert_eq!(program.0[1], TopStmt::Use(PathGlob("std:net")));
+
+        let len = program.0.len();
+        assert!(program
+            .replace_statement(len, TopStmt::Use(PathGlob("past")))
+            .is_err());
+        assert_eq!(program.0.len(), len);
+    }
+
+    #[test]
+    fn finds_the_entry_point() {
+        let program = mixed_program();
+        let main = program.entry_point().expect("should have main");
+        assert!(std::ptr::eq(main, program.functions().next().unwrap()));
+
+        let mut program = program;
+        program.0.retain(|stmt| !matches!(stmt, TopStmt::FnDecl(_)));
+        assert!(program.entry_point().is_none());
+    }
+
+    #[test]
+    fn blocks_collect_and_iterate() {
+        let block: Block<'_> = ["a", "b"]
+            .iter()
+            .map(|name| Stmt::Expr(Box::new(Expr::Ident(Ident(name)))))
+            .collect();
+        assert_eq!(block.0.len(), 2);
+
+        let names: Vec<_> = (&block)
+            .into_iter()
+            .map(|stmt| match stmt {
+                Stmt::Expr(expr) => format!("{:?}", expr),
+                other => panic!("expected an expression, found {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, vec!["Ident(Ident(\"a\"))", "Ident(Ident(\"b\"))"]);
+
+        let stmts: Vec<Stmt<'_>> = block.into_iter().collect();
+        assert_eq!(stmts.len(), 2);
+    }
+
+    #[test]
+    fn filters_types() {
+        assert_eq!(mixed_program().types().count(), 1);
+    }
+
+    #[test]
+    fn rewrites_function_bodies() {
+        let mut program = Program(vec![
+            TopStmt::FnDecl(FnDecl {
+                meta: ItemMeta::default(),
+                name: Ident("main"),
+                args: vec![],
+                ret_ty: ReturnType::Inferred,
+                body: Block(vec![]),
+            }),
+            TopStmt::Comment(Comment::SingleLine(" Between.")),
+            TopStmt::FnDecl(FnDecl {
+                meta: ItemMeta::default(),
+                name: Ident("helper"),
+                args: vec![],
+                ret_ty: ReturnType::Inferred,
+                body: Block(vec![Stmt::Comment(Comment::SingleLine(" Kept."))]),
+            }),
+        ]);
+
+        for body in program.function_bodies_mut() {
+            body.0
+                .push(Stmt::Expr(Box::new(Expr::Ident(Ident("done")))));
+        }
+
+        let lens: Vec<_> = program.functions().map(|decl| decl.body.0.len()).collect();
+        assert_eq!(lens, vec![1, 2]);
+
+        for decl in program.functions() {
+            assert_eq!(
+                decl.body.0.last(),
+                Some(&Stmt::Expr(Box::new(Expr::Ident(Ident("done")))))
+            );
+        }
+    }
+
+    #[test]
+    fn sorts_statements_by_kind() {
+        let src = "\
+// About main.
+fn main() {}
+use std:io;
+// About Point.
+type Point { x: Int }
+fn helper() {}
+use std:fs;
+// The end.
+";
+        let mut program = crate::parse(src).expect("should parse");
+        program.sort_by_kind();
+
+        let order: Vec<_> = program
+            .0
+            .iter()
+            .map(|stmt| match stmt {
+                TopStmt::Comment(comment) => comment.to_string(),
+                TopStmt::Use(path) => path.0.to_string(),
+                TopStmt::FnDecl(decl) => decl.name.0.to_string(),
+                TopStmt::TyDecl(decl) => decl.name.0.to_string(),
+                TopStmt::TyAlias(alias) => alias.name.0.to_string(),
+            })
+            .collect();
+
+        assert_eq!(
+            order,
+            vec![
+                "std:io",
+                "std:fs",
+                " About Point.",
+                "Point",
+                " About main.",
+                "main",
+                "helper",
+                " The end.",
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_empty_programs() {
+        assert!(Program(vec![]).is_empty());
+        assert!(!mixed_program().is_empty());
+    }
+
+    #[test]
+    fn measures_literals_in_the_source() {
+        let src =
+            r##"fn main() { x = f(0x1F, 2.50, "né", r#"a"#, b"ab", '\n', :sym, true, #[1]); }"##;
+        let program = crate::parse(src).expect("should parse");
+        let body = &program.functions().next().unwrap().body;
+
+        let args = match &body.0[..] {
+            [Stmt::VarAssign(assign)] => match &*assign.rhs {
+                Expr::FnCall(call) => &call.args,
+                other => panic!("expected a call, found {:?}", other),
+            },
+            other => panic!("expected an assignment, found {:?}", other),
+        };
+
+        let lens: Vec<_> = args
+            .iter()
+            .map(|arg| match &**arg {
+                Expr::Literal(literal) => literal.source_len(),
+                other => panic!("expected a literal, found {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(
+            lens,
+            vec![
+                Some(4),
+                Some(4),
+                Some(5),
+                Some(6),
+                Some(5),
+                Some(4),
+                Some(4),
+                Some(4),
+                None
+            ]
+        );
+    }
+}
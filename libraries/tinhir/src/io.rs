@@ -0,0 +1,124 @@
+//! Reading programs from files.
+
+use crate::diagnostic::explain;
+use crate::error::Error;
+use crate::lex::dump_tokens;
+use crate::options::ParseOptions;
+use std::ffi::OsString;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// Read a whole file into a string, with the path in any error.
+pub fn read_file(path: impl AsRef<Path>) -> Result<String, Error> {
+    let path = path.as_ref();
+
+    read_to_string(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// What a tool was asked for, once the flags every tool takes are handled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    /// The source of the file to work on.
+    Source(String),
+    /// The longer explanation of the code `--explain` asked about.
+    Explained(&'static str),
+    /// The file's tokens, one per line, which `--dump-tokens` asked for.
+    Tokens(String),
+}
+
+/// Read the file named by a tool's arguments, after handling the flags
+/// every tool takes.
+///
+/// With `--explain <code>`, this gives the explanation of that diagnostic
+/// code instead, and with `--dump-tokens`, it gives the file's tokens, which
+/// works even if the file doesn't parse. Nothing is printed; that's left to
+/// the tool.
+pub fn read_input(
+    args: impl IntoIterator<Item = OsString>,
+    options: ParseOptions,
+) -> Result<Input, Error> {
+    let mut args: Vec<_> = args.into_iter().collect();
+
+    if let Some(index) = args.iter().position(|arg| arg == "--explain") {
+        let code = args
+            .get(index + 1)
+            .map(|code| code.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        return explain(&code)
+            .map(Input::Explained)
+            .ok_or(Error::UnknownCode(code));
+    }
+
+    let dump = match args.iter().position(|arg| arg == "--dump-tokens") {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    };
+
+    let file_name = args.into_iter().next().ok_or(Error::NoFile)?;
+    let contents = read_file(file_name)?;
+
+    if dump {
+        return Ok(Input::Tokens(dump_tokens(&contents, options)));
+    }
+
+    Ok(Input::Source(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_the_missing_file() {
+        let error = read_file("no/such/dir/prog.tin").unwrap_err();
+
+        assert!(matches!(&error, Error::Io { path, .. } if path.ends_with("prog.tin")));
+        assert_eq!(error.to_string(), "failed to read no/such/dir/prog.tin");
+        assert!(format!("{:#}", error).starts_with("failed to read no/such/dir/prog.tin: "));
+    }
+
+    #[test]
+    fn reads_the_file_named_after_the_flags() {
+        let options = ParseOptions::default();
+
+        assert!(matches!(read_input(vec![], options), Err(Error::NoFile)));
+
+        let args = vec!["no/such/dir/prog.tin".into()];
+        let error = read_input(args, options).unwrap_err();
+        assert_eq!(error.to_string(), "failed to read no/such/dir/prog.tin");
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        match read_input(vec![path.clone().into()], options) {
+            Ok(Input::Source(contents)) => assert!(contents.contains("name = \"tinhir\"")),
+            other => panic!("expected the source, found {:?}", other),
+        }
+
+        let args = vec!["--dump-tokens".into(), path.into()];
+        match read_input(args, options) {
+            Ok(Input::Tokens(tokens)) => assert!(tokens.starts_with("Punct 0..1 \"[\"\n")),
+            other => panic!("expected tokens, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn explains_codes_without_reading_a_file() {
+        let options = ParseOptions::default();
+        let args = |code: &str| vec!["--explain".into(), code.into()];
+
+        assert_eq!(
+            read_input(args("E0002"), options).unwrap(),
+            Input::Explained(explain("E0002").unwrap())
+        );
+
+        let error = read_input(args("E9999"), options).unwrap_err();
+        assert!(matches!(&error, Error::UnknownCode(code) if code == "E9999"));
+        assert_eq!(error.to_string(), "there's no diagnostic code `E9999`");
+    }
+}
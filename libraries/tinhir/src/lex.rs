@@ -0,0 +1,505 @@
+//! Splitting source code into tokens.
+//!
+//! The parser works on the source text directly, so the lexer isn't
+//! needed to build the HIR. It exists for tooling, like syntax highlighters,
+//! which only care about the tokens and their locations.
+//!
+//! Lexing never fails. Anything which can't be lexed becomes an `Unknown`
+//! token, so tools keep working on invalid or incomplete code.
+
+use crate::hir::Program;
+use crate::options::{KeywordConfig, ParseOptions};
+use crate::parse::{self, with_options, Keyword, Operator};
+use crate::print::pretty_print;
+use crate::span::Span;
+use nom::bytes::complete::tag;
+use nom::character::complete::{multispace1, not_line_ending};
+use nom::combinator::recognize;
+use nom::sequence::pair;
+
+/// A single token in the source code.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'src> {
+    /// What kind of token this is.
+    pub kind: TokenKind,
+    /// Where the token is in the source.
+    pub span: Span,
+    /// The text of the token.
+    pub text: &'src str,
+}
+
+/// The kinds of tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// A keyword (e.g. `if`)
+    Keyword(Keyword),
+    /// An operator (e.g. `+=`)
+    Operator(Operator),
+    /// A piece of punctuation (e.g. `{` or `#[`)
+    Punct,
+    /// An identifier (e.g. `some-name`)
+    Ident,
+    /// A boolean literal (e.g. `true`)
+    Bool,
+    /// An integer literal (e.g. `645`)
+    Int,
+    /// A float literal (e.g. `5.36`)
+    Float,
+    /// A string literal (e.g. `"hello"`)
+    UStr,
+    /// A byte string literal (e.g. `b"hello"`)
+    BStr,
+    /// A character literal (e.g. `'c'`)
+    Char,
+    /// A symbol literal (e.g. `:hello`)
+    Symbol,
+    /// A comment line (e.g. `// hello`)
+    Comment,
+    /// Anything which couldn't be lexed.
+    Unknown,
+}
+
+/// The broad categories editors use to pick highlighting colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Keywords (e.g. `if`)
+    Keyword,
+    /// Operators (e.g. `+=`)
+    Operator,
+    /// Punctuation (e.g. `{` or `#[`)
+    Punctuation,
+    /// Identifiers (e.g. `some-name`)
+    Identifier,
+    /// Named constants, which are booleans and symbols (e.g. `true` or `:hello`)
+    Constant,
+    /// Integer and float literals (e.g. `645` or `5.36`)
+    Number,
+    /// String, byte string, and character literals (e.g. `"hello"` or `'c'`)
+    String,
+    /// Comments (e.g. `// hello`)
+    Comment,
+    /// Anything which couldn't be lexed.
+    Invalid,
+}
+
+impl TokenKind {
+    /// Get the highlighting scope for tokens of this kind.
+    pub fn scope(&self) -> Scope {
+        use TokenKind::*;
+
+        match self {
+            Keyword(_) => Scope::Keyword,
+            Operator(_) => Scope::Operator,
+            Punct => Scope::Punctuation,
+            Ident => Scope::Identifier,
+            Bool | Symbol => Scope::Constant,
+            Int | Float => Scope::Number,
+            UStr | BStr | Char => Scope::String,
+            Comment => Scope::Comment,
+            Unknown => Scope::Invalid,
+        }
+    }
+}
+
+/// Punctuation, longest spellings first.
+const PUNCT: &[&str] = &[
+    "->", "=>", "#[", "#(", "#{", "(", ")", "{", "}", "[", "]", ",", ";", ":", ".", "?",
+];
+
+/// Split the input into tokens, skipping whitespace.
+///
+/// Keywords and identifiers are lexed the way the parser would read them
+/// with the given options.
+pub fn lex(input: &str, options: ParseOptions) -> Vec<Token<'_>> {
+    with_options(options, || lex_tokens(input, &options.keywords))
+}
+
+fn lex_tokens<'a>(input: &'a str, keywords: &KeywordConfig) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        if let Ok((after, _)) = multispace1::<_, (&str, nom::error::ErrorKind)>(rest) {
+            rest = after;
+            continue;
+        }
+
+        let start = input.len() - rest.len();
+        let after_name = tokens.last().is_some_and(|token: &Token<'_>| {
+            token.kind == TokenKind::Ident && token.span.end == start
+        });
+        let (kind, len) = next_token(rest, after_name, keywords);
+
+        tokens.push(Token {
+            kind,
+            span: Span::new(start, start + len),
+            text: &rest[..len],
+        });
+
+        rest = &rest[len..];
+    }
+
+    tokens
+}
+
+/// Tokens along with the text they were lexed from, which their spans and
+/// text refer to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenStream {
+    text: String,
+    tokens: Vec<(TokenKind, Span)>,
+}
+
+impl TokenStream {
+    /// Lex the text with the given options, keeping the text.
+    pub fn new(text: String, options: ParseOptions) -> TokenStream {
+        let tokens = lex(&text, options)
+            .into_iter()
+            .map(|token| (token.kind, token.span))
+            .collect();
+
+        TokenStream { text, tokens }
+    }
+
+    /// Get the text the tokens were lexed from.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the tokens, in order.
+    pub fn tokens(&self) -> Vec<Token<'_>> {
+        self.tokens
+            .iter()
+            .map(|&(kind, span)| Token {
+                kind,
+                span,
+                text: &self.text[span.start..span.end],
+            })
+            .collect()
+    }
+}
+
+impl Program<'_> {
+    /// Flatten the program back into tokens.
+    ///
+    /// The tokens are laid out the way the pretty-printer would write them,
+    /// whatever the layout of the source was, so lexing the stream's text
+    /// again gives back the same tokens.
+    pub fn to_tokens(&self) -> TokenStream {
+        TokenStream::new(pretty_print(self), ParseOptions::default())
+    }
+}
+
+/// List every token, one per line, with its kind, span, and text.
+///
+/// This is for debugging the lexer, so it works on any input, including
+/// input which doesn't parse.
+pub fn dump_tokens(input: &str, options: ParseOptions) -> String {
+    lex(input, options)
+        .iter()
+        .map(|token| format!("{:?} {} {:?}\n", token.kind, token.span, token.text))
+        .collect()
+}
+
+/// Get the highlighting-relevant kind and location of every token.
+pub fn highlight(input: &str, options: ParseOptions) -> Vec<(Span, TokenKind)> {
+    lex(input, options)
+        .into_iter()
+        .map(|token| (token.span, token.kind))
+        .collect()
+}
+
+/// Lex a single token from the front of the input, returning its kind
+/// and length in bytes.
+fn next_token(input: &str, after_name: bool, keywords: &KeywordConfig) -> (TokenKind, usize) {
+    let consumed = |rest: &str| input.len() - rest.len();
+
+    if let Ok((rest, _)) = comment(input) {
+        return (TokenKind::Comment, consumed(rest));
+    }
+
+    if let Ok((rest, _)) = parse::bstr(input) {
+        return (TokenKind::BStr, consumed(rest));
+    }
+
+    // Raw strings start with an `r`, so they have to come before words.
+    if let Ok((rest, _)) = parse::ustr(input) {
+        return (TokenKind::UStr, consumed(rest));
+    }
+
+    if let Ok((rest, word)) = parse::word(input) {
+        let keyword = keywords.lookup(word);
+
+        if keyword == Some(Keyword::Else) {
+            if let Some((rest, keyword)) = chained_else(rest, keywords) {
+                return (TokenKind::Keyword(keyword), consumed(rest));
+            }
+        }
+
+        let kind = match word {
+            "true" | "false" => TokenKind::Bool,
+            _ => keyword.map_or(TokenKind::Ident, TokenKind::Keyword),
+        };
+
+        return (kind, consumed(rest));
+    }
+
+    if let Ok((rest, _)) = parse::float(input) {
+        return (TokenKind::Float, consumed(rest));
+    }
+
+    // A `-` is lexed as an operator, even where the parser would read it
+    // as part of a negative integer, since that depends on what follows.
+    if !input.starts_with('-') {
+        if let Ok((rest, _)) = parse::int(input) {
+            return (TokenKind::Int, consumed(rest));
+        }
+    }
+
+    if let Ok((rest, _)) = parse::char(input) {
+        return (TokenKind::Char, consumed(rest));
+    }
+
+    // A colon directly after a name is punctuation, as in `x: Int` or
+    // `std:io`, rather than the start of a symbol.
+    if !after_name && input.starts_with(':') {
+        if let Ok((rest, _)) = parse::symbol(input) {
+            return (TokenKind::Symbol, consumed(rest));
+        }
+    }
+
+    if let Some(punct) = PUNCT.iter().find(|punct| input.starts_with(**punct)) {
+        return (TokenKind::Punct, punct.len());
+    }
+
+    if let Some((spelling, op)) = Operator::ALL
+        .iter()
+        .find(|(spelling, _)| input.starts_with(spelling))
+    {
+        return (TokenKind::Operator(*op), spelling.len());
+    }
+
+    let len = input.chars().next().map_or(0, char::len_utf8);
+    (TokenKind::Unknown, len)
+}
+
+/// The rest of an `else if` or `else unless` after the `else`.
+///
+/// These are single keywords, so they're single tokens, whatever
+/// whitespace is between their words.
+fn chained_else<'a>(input: &'a str, keywords: &KeywordConfig) -> Option<(&'a str, Keyword)> {
+    let (rest, _) = multispace1::<_, (&str, nom::error::ErrorKind)>(input).ok()?;
+    let (rest, word) = parse::word(rest).ok()?;
+
+    match keywords.lookup(word)? {
+        Keyword::If => Some((rest, Keyword::ElseIf)),
+        Keyword::Unless => Some((rest, Keyword::ElseUnless)),
+        _ => None,
+    }
+}
+
+fn comment(input: &str) -> parse::ParseResult<&str, &str> {
+    recognize(pair(tag("//"), not_line_ending))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_a_line() {
+        // Spans are in bytes, so the multi-byte characters widen them.
+        let kinds = highlight("name = \"né☃\" + 42; // dé", ParseOptions::default());
+
+        assert_eq!(
+            kinds,
+            vec![
+                (Span::new(0, 4), TokenKind::Ident),
+                (Span::new(5, 6), TokenKind::Operator(Operator::Assign)),
+                (Span::new(7, 15), TokenKind::UStr),
+                (Span::new(16, 17), TokenKind::Operator(Operator::Add)),
+                (Span::new(18, 20), TokenKind::Int),
+                (Span::new(20, 21), TokenKind::Punct),
+                (Span::new(22, 28), TokenKind::Comment),
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_each_operator_and_keyword_as_one_token() {
+        let operators = Operator::ALL
+            .iter()
+            .map(|(spelling, op)| (*spelling, TokenKind::Operator(*op)));
+        let keywords = Keyword::ALL
+            .iter()
+            .map(|(spelling, keyword)| (*spelling, TokenKind::Keyword(*keyword)));
+
+        for (spelling, kind) in operators.chain(keywords) {
+            let tokens = lex(spelling, ParseOptions::default());
+            assert_eq!(tokens.len(), 1, "`{}` should be one token", spelling);
+            assert_eq!(tokens[0].kind, kind, "`{}` has the wrong kind", spelling);
+            assert_eq!(tokens[0].text, spelling);
+        }
+    }
+
+    #[test]
+    fn lexes_keywords_as_the_options_spell_them() {
+        let keywords = KeywordConfig::default()
+            .rename(Keyword::If, "when")
+            .expect("should rename")
+            .disable(Keyword::Loop);
+        let options = ParseOptions::default().keywords(keywords);
+        let kinds: Vec<_> = lex("else when if loop", options)
+            .iter()
+            .map(|token| token.kind)
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Keyword(Keyword::ElseIf),
+                TokenKind::Ident,
+                TokenKind::Ident
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_minus_signs_as_operators() {
+        let kinds: Vec<_> = lex("x -1", ParseOptions::default())
+            .iter()
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Ident,
+                TokenKind::Operator(Operator::Sub),
+                TokenKind::Int
+            ]
+        );
+    }
+
+    #[test]
+    fn lexes_chained_elses_across_whitespace() {
+        let tokens: Vec<_> = lex("} else\n  unless x", ParseOptions::default())
+            .iter()
+            .map(|token| token.text)
+            .collect();
+        assert_eq!(tokens, vec!["}", "else\n  unless", "x"]);
+
+        // Only whole words chain.
+        let kinds: Vec<_> = lex("else iffy", ParseOptions::default())
+            .iter()
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![TokenKind::Keyword(Keyword::Else), TokenKind::Ident]
+        );
+    }
+
+    #[test]
+    fn scopes_tokens() {
+        let scopes: Vec<_> = lex("if x >= 1.5 { :done }", ParseOptions::default())
+            .iter()
+            .map(|token| token.kind.scope())
+            .collect();
+
+        assert_eq!(
+            scopes,
+            vec![
+                Scope::Keyword,
+                Scope::Identifier,
+                Scope::Operator,
+                Scope::Number,
+                Scope::Punctuation,
+                Scope::Constant,
+                Scope::Punctuation,
+            ]
+        );
+    }
+
+    #[test]
+    fn colons_after_names_are_punctuation() {
+        let kinds: Vec<_> = lex("x: Int", ParseOptions::default())
+            .iter()
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![TokenKind::Ident, TokenKind::Punct, TokenKind::Ident]
+        );
+    }
+
+    #[test]
+    fn lexes_raw_strings() {
+        let kinds: Vec<_> = lex(r##"r r#"a"b"# r"#")"##, ParseOptions::default())
+            .iter()
+            .map(|token| (token.kind, token.text))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (TokenKind::Ident, "r"),
+                (TokenKind::UStr, r##"r#"a"b"#"##),
+                (TokenKind::UStr, r##"r"#""##),
+                (TokenKind::Punct, ")"),
+            ]
+        );
+    }
+
+    #[test]
+    fn to_tokens_matches_the_lexer() {
+        let src = "// Say hi.\nfn greet(name: Str) -> Str {\n    x = tag(1, -2.5, :a);\n    \"hi, \" + name;\n}\n";
+        let program = crate::parse(src).unwrap();
+        let stream = program.to_tokens();
+
+        assert_eq!(stream.text(), src);
+        assert_eq!(stream.tokens(), lex(src, ParseOptions::default()));
+    }
+
+    #[test]
+    fn to_tokens_lays_the_tokens_out_again() {
+        let src =
+            "// Say hi.\nfn   greet( name:Str )->Str{x=tag( 1,-2.5 , :a );\n\n  \"hi, \"+name;}";
+        let program = crate::parse(src).unwrap();
+        let stream = program.to_tokens();
+        let text = |tokens: Vec<Token<'_>>| {
+            tokens
+                .into_iter()
+                .map(|token| (token.kind, token.text.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_ne!(stream.text(), src);
+        assert_eq!(
+            text(stream.tokens()),
+            text(lex(src, ParseOptions::default()))
+        );
+        assert_eq!(stream.tokens(), lex(stream.text(), ParseOptions::default()));
+    }
+
+    #[test]
+    fn dumps_tokens_one_per_line() {
+        assert_eq!(
+            dump_tokens("x = #[1, ☃", ParseOptions::default()),
+            "\
+Ident 0..1 \"x\"
+Operator(Assign) 2..3 \"=\"
+Punct 4..6 \"#[\"
+Int 6..7 \"1\"
+Punct 7..8 \",\"
+Unknown 9..12 \"☃\"
+"
+        );
+    }
+
+    #[test]
+    fn unknown_characters_keep_their_bytes() {
+        let tokens = lex("x ☃ y", ParseOptions::default());
+        assert_eq!(tokens[1].kind, TokenKind::Unknown);
+        assert_eq!(tokens[1].span, Span::new(2, 5));
+        assert_eq!(tokens[1].text, "☃");
+    }
+}
@@ -1,7 +1,51 @@
+mod cache;
+pub mod cfg;
+mod charset;
+pub mod combinators;
+mod convert;
+pub mod desugar;
+mod diagnostic;
 mod error;
+mod eval;
+#[cfg(any(test, feature = "test-support"))]
+mod generate;
 mod hir;
+mod io;
+mod lex;
+mod lint;
+mod module;
+mod options;
 mod parse;
+mod parsed;
+mod print;
+mod rename;
+mod rewrite;
+mod scope;
+mod source_map;
+mod span;
+#[cfg(any(test, feature = "test-support"))]
+mod test_support;
+mod trivia;
+mod visit;
 
+pub use crate::cache::*;
+pub use crate::convert::*;
+pub use crate::diagnostic::*;
 pub use crate::error::*;
+pub use crate::eval::*;
+#[cfg(any(test, feature = "test-support"))]
+pub use crate::generate::*;
 pub use crate::hir::*;
+pub use crate::io::*;
+pub use crate::lex::*;
+pub use crate::lint::*;
+pub use crate::module::*;
+pub use crate::options::*;
 pub use crate::parse::*;
+pub use crate::parsed::*;
+pub use crate::print::*;
+pub use crate::rename::*;
+pub use crate::source_map::*;
+pub use crate::span::*;
+pub use crate::trivia::*;
+pub use crate::visit::*;
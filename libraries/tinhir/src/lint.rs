@@ -0,0 +1,873 @@
+//! Diagnostics about code which parses, but may not do what was meant.
+
+use crate::charset::is_digit_in_radix;
+use crate::diagnostic::{codes, Diagnostic};
+use crate::hir::*;
+use crate::span::Span;
+use crate::visit::Node;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// Options for `lint_with_options`.
+///
+/// The defaults match `lint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LintOptions {
+    pub(crate) nested_shadowing: bool,
+    pub(crate) mutual_recursion: bool,
+}
+
+impl Default for LintOptions {
+    fn default() -> LintOptions {
+        LintOptions {
+            nested_shadowing: true,
+            mutual_recursion: true,
+        }
+    }
+}
+
+impl LintOptions {
+    /// Whether to warn about parameters shadowed in blocks nested inside
+    /// the function body, like the body of an `if`, and not only at the top
+    /// of the body.
+    ///
+    /// This is on by default.
+    pub fn nested_shadowing(mut self, warn: bool) -> LintOptions {
+        self.nested_shadowing = warn;
+        self
+    }
+
+    /// Whether to note functions which call themselves through other
+    /// functions, and not only those which call themselves directly.
+    ///
+    /// This is on by default.
+    pub fn mutual_recursion(mut self, note: bool) -> LintOptions {
+        self.mutual_recursion = note;
+        self
+    }
+}
+
+/// The signature of a program's entry point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntrySignature {
+    /// How many parameters the entry point takes.
+    pub params: usize,
+    /// The types it may return, besides nothing at all.
+    pub return_tys: &'static [&'static str],
+}
+
+/// The signature `main` must have: no parameters, returning nothing or an
+/// `Int` exit code.
+pub const MAIN_SIGNATURE: EntrySignature = EntrySignature {
+    params: 0,
+    return_tys: &["Int"],
+};
+
+impl<'prgrm> Program<'prgrm> {
+    /// Check that `main`, if there is one, has the `MAIN_SIGNATURE`, given
+    /// the source the program was parsed from.
+    pub fn validate_main_signature(&self, source: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let main = match self.entry_point() {
+            Some(main) => main,
+            None => return diagnostics,
+        };
+        let expected = MAIN_SIGNATURE;
+
+        if main.args.len() != expected.params {
+            let extra = main.args.get(expected.params).map(|arg| arg.ident.0);
+
+            if let Some(span) = Span::of(extra.unwrap_or(main.name.0), source) {
+                diagnostics.push(
+                    Diagnostic::error(
+                        span,
+                        format!(
+                            "`main` takes {}, not {}",
+                            count_params(expected.params),
+                            main.args.len()
+                        ),
+                    )
+                    .with_code(codes::MAIN_PARAMETERS),
+                );
+            }
+        }
+
+        let found = match &main.ret_ty {
+            ReturnType::Inferred | ReturnType::Unit => None,
+            ReturnType::Explicit(ty) if expected.return_tys.contains(&ty.0) => None,
+            ReturnType::Explicit(ty) => Some((ty.0, ty.0.to_string())),
+            ReturnType::Tuple(tys) => {
+                let names: Vec<_> = tys.iter().map(|ty| ty.0).collect();
+                Some((tys[0].0, format!("({})", names.join(", "))))
+            }
+        };
+
+        if let Some((at, found)) = found {
+            if let Some(span) = Span::of(at, source) {
+                let allowed: Vec<_> = std::iter::once("()")
+                    .chain(expected.return_tys.iter().copied())
+                    .map(|ty| format!("`{}`", ty))
+                    .collect();
+                diagnostics.push(
+                    Diagnostic::error(
+                        span,
+                        format!(
+                            "`main` must return {}, not `{}`",
+                            allowed.join(" or "),
+                            found
+                        ),
+                    )
+                    .with_code(codes::MAIN_RETURN_TYPE),
+                );
+            }
+        }
+
+        diagnostics
+    }
+}
+
+fn count_params(count: usize) -> String {
+    match count {
+        0 => "no parameters".to_string(),
+        1 => "1 parameter".to_string(),
+        n => format!("{} parameters", n),
+    }
+}
+
+/// Check a program for suspicious code, given the source it was parsed from.
+pub fn lint(program: &Program<'_>, source: &str) -> Vec<Diagnostic> {
+    lint_with_options(program, source, LintOptions::default())
+}
+
+/// Check a program for suspicious code with the given options.
+pub fn lint_with_options(
+    program: &Program<'_>,
+    source: &str,
+    options: LintOptions,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut spellings = HashMap::new();
+
+    for (_, node) in program.traverse() {
+        match node {
+            Node::Literal(literal) => leading_zeros(literal, source, &mut diagnostics),
+            Node::VarAssign(assign) => missing_else(assign, source, &mut diagnostics),
+            Node::Expr(Expr::Loop(body))
+            | Node::Expr(Expr::While(_, body, _))
+            | Node::Expr(Expr::Until(_, body, _))
+            | Node::Expr(Expr::For(_, _, body, _)) => break_values(body, source, &mut diagnostics),
+            Node::Ident(ident) => {
+                operator_chars(ident, source, &mut diagnostics);
+                confusable(ident, source, &mut spellings, &mut diagnostics);
+            }
+            _ => {}
+        }
+    }
+
+    for decl in program.functions() {
+        shadowed_params(decl, source, options, &mut diagnostics);
+    }
+
+    duplicates(program, source, &mut diagnostics);
+    diagnostics.extend(program.validate_no_duplicate_use(source));
+    diagnostics.extend(program.validate_main_signature(source));
+    recursion(program, source, options, &mut diagnostics);
+
+    indentation(source, &mut diagnostics);
+    diagnostics
+}
+
+/// Normalize an identifier, so identifiers which are easily confused for
+/// each other normalize the same.
+///
+/// Currently this treats `_` and `-` as the same, so `some_name` and
+/// `some-name` collide.
+pub fn normalize_ident(name: &str) -> Cow<'_, str> {
+    if name.contains('_') {
+        Cow::Owned(name.replace('_', "-"))
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
+/// Identifiers may contain `+`, `*`, `<`, and `>`, so `a+b` is a single
+/// identifier, which was probably meant as an operation.
+///
+/// `-` is left alone, since it's the usual way to separate words.
+fn operator_chars(ident: &Ident<'_>, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if ident.is_operator() || !ident.0.contains(|c| "+*<>".contains(c)) {
+        return;
+    }
+
+    if let Some(span) = Span::of(ident.0, source) {
+        diagnostics.push(
+            Diagnostic::warning(
+                span,
+                format!(
+                    "`{}` is a single identifier; put spaces around operators to use them",
+                    ident.0
+                ),
+            )
+            .with_code(codes::OPERATOR_IN_NAME),
+        );
+    }
+}
+
+/// Identifiers which are spelled differently but normalize the same are
+/// easily confused.
+fn confusable<'prgrm>(
+    ident: &Ident<'prgrm>,
+    source: &str,
+    spellings: &mut HashMap<Cow<'prgrm, str>, &'prgrm str>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if ident.is_operator() {
+        return;
+    }
+
+    let first = *spellings.entry(normalize_ident(ident.0)).or_insert(ident.0);
+
+    if first == ident.0 {
+        return;
+    }
+
+    if let Some(span) = Span::of(ident.0, source) {
+        let mut diagnostic = Diagnostic::warning(
+            span,
+            format!("`{}` is easily confused with `{}`", ident.0, first),
+        )
+        .with_code(codes::CONFUSABLE_NAMES);
+
+        if let Some(first_span) = Span::of(first, source) {
+            diagnostic =
+                diagnostic.with_related(first_span, format!("`{}` is first used here", first));
+        }
+
+        diagnostics.push(diagnostic);
+    }
+}
+
+/// Functions, types, and aliases share one namespace, so each name can
+/// only be declared once. The first declaration in the source is the one
+/// the others conflict with.
+fn duplicates(program: &Program<'_>, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let names = program.0.iter().filter_map(|stmt| match stmt {
+        TopStmt::FnDecl(decl) => Some(decl.name.0),
+        TopStmt::TyDecl(decl) => Some(decl.name.0),
+        TopStmt::TyAlias(alias) => Some(alias.name.0),
+        TopStmt::Comment(_) | TopStmt::Use(_) => None,
+    });
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+
+    for name in names {
+        let first = *seen.entry(name).or_insert(name);
+
+        if first.as_ptr() == name.as_ptr() {
+            continue;
+        }
+
+        if let Some(span) = Span::of(name, source) {
+            let mut diagnostic = Diagnostic::error(span, format!("`{}` is already defined", name))
+                .with_code(codes::DUPLICATE_NAME);
+
+            if let Some(first_span) = Span::of(first, source) {
+                diagnostic = diagnostic.with_related(first_span, "first defined here");
+            }
+
+            diagnostics.push(diagnostic);
+        }
+    }
+}
+
+/// Assigning to a name which is also a parameter shadows the parameter, so
+/// its value can't be used after, which usually isn't what was meant.
+fn shadowed_params(
+    decl: &FnDecl<'_>,
+    source: &str,
+    options: LintOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let blocks: Vec<&Block<'_>> = if options.nested_shadowing {
+        Node::Block(&decl.body)
+            .traverse()
+            .filter_map(|(_, node)| match node {
+                Node::Block(block) => Some(block),
+                _ => None,
+            })
+            .collect()
+    } else {
+        vec![&decl.body]
+    };
+
+    for name in blocks.into_iter().flat_map(Block::declared_names) {
+        if !decl.args.iter().any(|arg| arg.ident.0 == name.0) {
+            continue;
+        }
+
+        if let Some(span) = Span::of(name.0, source) {
+            diagnostics.push(
+                Diagnostic::warning(
+                    span,
+                    format!("`{}` shadows the parameter of the same name", name.0),
+                )
+                .with_code(codes::SHADOWED_PARAMETER),
+            );
+        }
+    }
+}
+
+/// Recursive functions loop forever unless something stops the recursion,
+/// so they're worth a second look.
+///
+/// Calls are matched to functions by name, and don't include methods.
+fn recursion(
+    program: &Program<'_>,
+    source: &str,
+    options: LintOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let graph: HashMap<&str, Vec<&str>> = program
+        .functions()
+        .map(|decl| {
+            let mut called = Vec::new();
+            calls(Node::Block(&decl.body), &mut called);
+            (decl.name.0, called)
+        })
+        .collect();
+
+    for decl in program.functions() {
+        let name = decl.name.0;
+        let called = &graph[name];
+
+        let message = if called.contains(&name) {
+            format!("`{}` calls itself; make sure the recursion ends", name)
+        } else if !options.mutual_recursion {
+            continue;
+        } else {
+            match called
+                .iter()
+                .find(|callee| reaches(&graph, callee, name, &mut HashSet::new()))
+            {
+                Some(callee) => format!(
+                    "`{}` calls itself through `{}`; make sure the recursion ends",
+                    name, callee
+                ),
+                None => continue,
+            }
+        };
+
+        if let Some(span) = Span::of(name, source) {
+            diagnostics.push(Diagnostic::note(span, message).with_code(codes::RECURSION));
+        }
+    }
+}
+
+/// Collect the names of the functions called under a node, in source
+/// order, without duplicates.
+fn calls<'a>(node: Node<'a, '_>, called: &mut Vec<&'a str>) {
+    match node {
+        Node::Expr(Expr::FnCall(call))
+            if !call.name.is_operator() && !called.contains(&call.name.0) =>
+        {
+            called.push(call.name.0);
+        }
+        // The right side of a `.` is a method, but its arguments may still
+        // call functions.
+        Node::Expr(Expr::Dot(lhs, rhs)) => {
+            calls(Node::Expr(lhs), called);
+
+            if let Expr::FnCall(method) = &**rhs {
+                for arg in &method.args {
+                    calls(Node::Expr(arg), called);
+                }
+            }
+
+            return;
+        }
+        _ => {}
+    }
+
+    for child in node.children() {
+        calls(child, called);
+    }
+}
+
+/// Whether `target` can be called, directly or not, from `from`.
+fn reaches<'a>(
+    graph: &HashMap<&'a str, Vec<&'a str>>,
+    from: &'a str,
+    target: &str,
+    seen: &mut HashSet<&'a str>,
+) -> bool {
+    if from == target {
+        return true;
+    }
+
+    if !seen.insert(from) {
+        return false;
+    }
+
+    graph.get(from).is_some_and(|called| {
+        called
+            .iter()
+            .any(|callee| reaches(graph, callee, target, seen))
+    })
+}
+
+/// A loop's value comes from its `break`s, so they should either all give
+/// a value or none should, and literal values should all be the same kind.
+///
+/// Only literals are compared, since other values have no known type yet.
+fn break_values(body: &Block<'_>, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut breaks = Vec::new();
+    loop_breaks(Node::Block(body), &mut breaks);
+
+    let first_value = breaks.iter().find(|(value, _)| value.is_some());
+    let (first_value, first_kw) = match first_value {
+        Some((Some(value), kw)) => (value, kw),
+        _ => return,
+    };
+
+    for (value, kw) in &breaks {
+        let message = match (value, first_value) {
+            (None, _) => {
+                "`break` needs a value, since other `break`s in the loop give one".to_string()
+            }
+            (Some(Expr::Literal(literal)), Expr::Literal(first))
+                if literal.kind_name() != first.kind_name() =>
+            {
+                format!(
+                    "`break` gives {}, but the loop's first value is {}",
+                    literal.kind_name(),
+                    first.kind_name()
+                )
+            }
+            _ => continue,
+        };
+
+        if let Some(span) = Span::of(kw, source) {
+            let mut diagnostic = Diagnostic::error(span, message).with_code(codes::BREAK_VALUES);
+
+            if let Some(first_span) = Span::of(first_kw, source) {
+                diagnostic = diagnostic.with_related(first_span, "first value given here");
+            }
+
+            diagnostics.push(diagnostic);
+        }
+    }
+}
+
+/// Find the unlabeled `break`s which leave the loop the node is in, with
+/// their values and keywords.
+fn loop_breaks<'a, 'prgrm>(
+    node: Node<'a, 'prgrm>,
+    breaks: &mut Vec<(Option<&'a Expr<'prgrm>>, &'prgrm str)>,
+) {
+    match node {
+        Node::Expr(Expr::Break(None, value, kw)) => breaks.push((value.as_deref(), kw)),
+        // Breaks in these leave something else.
+        Node::Expr(Expr::Loop(_))
+        | Node::Expr(Expr::While(..))
+        | Node::Expr(Expr::Until(..))
+        | Node::Expr(Expr::For(..))
+        | Node::Expr(Expr::Lambda(_)) => return,
+        _ => {}
+    }
+
+    for child in node.children() {
+        loop_breaks(child, breaks);
+    }
+}
+
+/// An `if` without an `else` has no value when its condition is false, so
+/// one which is assigned needs an `else`, including at the end of a chain.
+///
+/// `if` used as a statement doesn't need one.
+fn missing_else(assign: &VarAssign<'_>, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut expr = &*assign.rhs;
+
+    let kw = loop {
+        let (kw, els) = match expr {
+            Expr::If(_, _, els) => ("if", els),
+            Expr::Unless(_, _, els) => ("unless", els),
+            _ => return,
+        };
+
+        match els.as_ref().map(|els| &els.0[..]) {
+            None => break kw,
+            Some([Stmt::Expr(chained)]) => expr = chained,
+            Some(_) => return,
+        }
+    };
+
+    let name = match assign.pattern.names().first() {
+        Some(name) => name.0,
+        None => return,
+    };
+
+    if let Some(span) = Span::of(name, source) {
+        diagnostics.push(Diagnostic::error(
+            span,
+            format!(
+                "`{}` is assigned an `{}` without an `else`, so it has no value when the condition fails",
+                name, kw
+            ),
+        ).with_code(codes::MISSING_ELSE));
+    }
+}
+
+/// Integers with leading zeros look like they might be octal, but aren't.
+fn leading_zeros(literal: &Literal<'_>, source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if let Literal::Int(Int(_, src)) = literal {
+        let after_zero = src.strip_prefix('0').unwrap_or("");
+
+        if after_zero.starts_with(|c| is_digit_in_radix(c, 10)) {
+            if let Some(span) = Span::of(src, source) {
+                diagnostics.push(
+                    Diagnostic::warning(
+                        span,
+                        format!("leading zeros in `{}` are ignored; it isn't octal", src),
+                    )
+                    .with_code(codes::LEADING_ZEROS),
+                );
+            }
+        }
+    }
+}
+
+/// Indentation which mixes tabs and spaces looks different depending on
+/// the tab width, so each item should stick to one or the other.
+///
+/// The first indented line of an item sets which one it uses. Columns in
+/// diagnostics count a tab as one column, whatever its width, so they
+/// don't depend on the editor either.
+fn indentation(source: &str, diagnostics: &mut Vec<Diagnostic>) {
+    let mut style = None;
+    let mut offset = 0;
+
+    for line in source.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let indent = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if indent.is_empty() {
+            style = None;
+            continue;
+        }
+
+        let span = Span::new(start, start + indent.len());
+        let uses_tabs = indent.contains('\t');
+
+        if uses_tabs && indent.contains(' ') {
+            diagnostics.push(
+                Diagnostic::warning(span, "indentation mixes tabs and spaces".to_string())
+                    .with_code(codes::MIXED_INDENTATION),
+            );
+            continue;
+        }
+
+        match style {
+            None => style = Some(uses_tabs),
+            Some(tabs) if tabs != uses_tabs => {
+                let (used, expected) = if uses_tabs {
+                    ("tabs", "spaces")
+                } else {
+                    ("spaces", "tabs")
+                };
+
+                diagnostics.push(
+                    Diagnostic::warning(
+                        span,
+                        format!("indented with {}, but earlier lines use {}", used, expected),
+                    )
+                    .with_code(codes::INCONSISTENT_INDENTATION),
+                );
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::span::line_column;
+
+    fn lint_src(src: &str) -> Vec<String> {
+        let program = parse(src).expect("should parse");
+        lint(&program, src)
+            .into_iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn flags_operators_in_identifiers() {
+        assert_eq!(
+            lint_src("fn main() { x = a+b; }"),
+            vec!["warning[W0001]: `a+b` is a single identifier; put spaces around operators to use them"]
+        );
+    }
+
+    #[test]
+    fn accepts_the_main_signature() {
+        assert!(lint_src("fn main() { }").is_empty());
+        assert!(lint_src("fn main() -> () { }").is_empty());
+        assert!(lint_src("fn main() -> Int { 0 }").is_empty());
+        assert!(lint_src("fn helper(a: Int) -> Str { }").is_empty());
+    }
+
+    #[test]
+    fn flags_main_with_parameters() {
+        let src = "fn main(args: Array<Str>, env: Env) { }";
+        let program = parse(src).expect("should parse");
+        let diagnostics = program.validate_main_signature(src);
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.to_string())
+                .collect::<Vec<_>>(),
+            vec!["error[E0003]: `main` takes no parameters, not 2"]
+        );
+        assert_eq!(line_column(src, diagnostics[0].span.start), (1, 9));
+    }
+
+    #[test]
+    fn flags_main_with_the_wrong_return_type() {
+        assert_eq!(
+            lint_src("fn main() -> Str { }"),
+            vec!["error[E0004]: `main` must return `()` or `Int`, not `Str`"]
+        );
+        assert_eq!(
+            lint_src("fn main() -> (Int, Bool) { }"),
+            vec!["error[E0004]: `main` must return `()` or `Int`, not `(Int, Bool)`"]
+        );
+    }
+
+    #[test]
+    fn accepts_consistent_break_values() {
+        let src = "fn main() { x = loop { if a { break 1; } loop { break; } break 2; }; }";
+        assert!(lint_src(src).is_empty());
+        assert!(lint_src("fn main() { loop { break; } }").is_empty());
+    }
+
+    #[test]
+    fn flags_breaks_missing_a_value() {
+        let src = "fn main() {\n    x = loop { if a { break; } break f(); };\n}";
+        let program = parse(src).expect("should parse");
+        let diagnostics = lint(&program, src);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error[E0006]: `break` needs a value, since other `break`s in the loop give one"
+        );
+        assert_eq!(diagnostics[0].span, Span::new(34, 39));
+        assert_eq!(diagnostics[0].related[0].0, Span::new(43, 48));
+    }
+
+    #[test]
+    fn flags_breaks_with_inconsistent_values() {
+        assert_eq!(
+            lint_src("fn main() { for x in xs { if x { break 1; } break \"one\"; } }"),
+            vec!["error[E0006]: `break` gives a string, but the loop's first value is an integer"]
+        );
+    }
+
+    #[test]
+    fn accepts_hyphenated_identifiers() {
+        assert!(lint_src("fn main() { a-really-long-name = a + b - c; }").is_empty());
+    }
+
+    #[test]
+    fn normalizes_identifiers() {
+        assert_eq!(normalize_ident("some_name"), "some-name");
+        assert!(matches!(normalize_ident("some-name"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn flags_confusable_identifiers() {
+        assert_eq!(
+            lint_src("fn main() { some-name = 1; print(some_name); print(some-name); }"),
+            vec!["warning[W0002]: `some_name` is easily confused with `some-name`"]
+        );
+    }
+
+    #[test]
+    fn labels_the_confusable_identifier() {
+        let src = "fn main() { some-name = 1; print(some_name); }";
+        let program = parse(src).expect("should parse");
+        let diagnostics = lint(&program, src);
+
+        assert_eq!(
+            diagnostics[0].related,
+            vec![(
+                Span::new(12, 21),
+                "`some-name` is first used here".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn flags_duplicate_declarations() {
+        let src = "fn f() {}\nfn f() {}\ntype f { x: Int }\n";
+        assert_eq!(
+            lint_src(src),
+            vec![
+                "error[E0002]: `f` is already defined",
+                "error[E0002]: `f` is already defined"
+            ]
+        );
+
+        let program = parse(src).expect("should parse");
+        let diagnostics = lint(&program, src);
+
+        assert_eq!(diagnostics[0].span, Span::new(13, 14));
+        assert_eq!(
+            diagnostics[0].related,
+            vec![(Span::new(3, 4), "first defined here".to_string())]
+        );
+        assert_eq!(diagnostics[1].span, Span::new(25, 26));
+        assert_eq!(diagnostics[1].related[0].0, Span::new(3, 4));
+    }
+
+    #[test]
+    fn flags_duplicates_after_the_first_declaration_in_the_source() {
+        let src = "type f { a: Int }\nfn f() { }\n";
+        let program = parse(src).expect("should parse");
+        let diagnostics = lint(&program, src);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].span, Span::new(21, 22));
+        assert_eq!(
+            diagnostics[0].related,
+            vec![(Span::new(5, 6), "first defined here".to_string())]
+        );
+    }
+
+    #[test]
+    fn flags_leading_zeros() {
+        assert_eq!(
+            lint_src("fn main() { x = 010 + 0; }"),
+            vec!["warning[W0004]: leading zeros in `010` are ignored; it isn't octal"]
+        );
+        assert!(lint_src("fn main() { x = 0x10 + 0b01; }").is_empty());
+    }
+
+    #[test]
+    fn flags_inconsistent_indentation() {
+        let src = "fn main() {\n\tx = 1;\n    y = 2;\n \tz = 3;\n}\n";
+        let program = parse(src).expect("should parse");
+        let found: Vec<_> = lint(&program, src)
+            .into_iter()
+            .map(|diagnostic| {
+                let start = line_column(src, diagnostic.span.start);
+                let end = line_column(src, diagnostic.span.end);
+                (start, end, diagnostic.message)
+            })
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (
+                    (3, 1),
+                    (3, 5),
+                    "indented with spaces, but earlier lines use tabs".to_string()
+                ),
+                (
+                    (4, 1),
+                    (4, 3),
+                    "indentation mixes tabs and spaces".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_shadowed_parameters() {
+        let src = "fn f(x: Int, y: Int) {\n    x = x + 1;\n    if y { #(y, z) = pair; }\n}\n";
+        assert_eq!(
+            lint_src(src),
+            vec![
+                "warning[W0003]: `x` shadows the parameter of the same name",
+                "warning[W0003]: `y` shadows the parameter of the same name",
+            ]
+        );
+
+        let program = parse(src).expect("should parse");
+        let options = LintOptions::default().nested_shadowing(false);
+        let found: Vec<_> = lint_with_options(&program, src, options)
+            .into_iter()
+            .map(|diagnostic| line_column(src, diagnostic.span.start))
+            .collect();
+        assert_eq!(found, vec![(2, 5)]);
+    }
+
+    #[test]
+    fn accepts_new_names_in_bodies() {
+        assert!(lint_src("fn f(x: Int) {\n    total = x + 1;\n}\n").is_empty());
+    }
+
+    #[test]
+    fn notes_direct_recursion() {
+        let src = "fn count(n: Int) { if n > 0 { count(n - 1); } }";
+        assert_eq!(
+            lint_src(src),
+            vec!["note[N0001]: `count` calls itself; make sure the recursion ends"]
+        );
+
+        let program = parse(src).expect("should parse");
+        assert_eq!(lint(&program, src)[0].span, Span::new(3, 8));
+    }
+
+    #[test]
+    fn notes_mutual_recursion() {
+        let src = "fn is-even(n: Int) { is-odd(n - 1); }\nfn is-odd(n: Int) { is-even(n - 1); }";
+        assert_eq!(
+            lint_src(src),
+            vec![
+                "note[N0001]: `is-even` calls itself through `is-odd`; make sure the recursion ends",
+                "note[N0001]: `is-odd` calls itself through `is-even`; make sure the recursion ends",
+            ]
+        );
+
+        let program = parse(src).expect("should parse");
+        let options = LintOptions::default().mutual_recursion(false);
+        assert!(lint_with_options(&program, src, options).is_empty());
+    }
+
+    #[test]
+    fn accepts_functions_without_recursion() {
+        let src = "fn main() { x = helper(1).helper(); print(x); }\nfn helper(n: Int) { n + 1; }";
+        assert!(lint_src(src).is_empty());
+    }
+
+    #[test]
+    fn requires_else_on_assigned_ifs() {
+        assert_eq!(
+            lint_src("fn main() { x = if a { 1; }; y = unless a { 1; } else if b { 2; }; }"),
+            vec![
+                "error[E0005]: `x` is assigned an `if` without an `else`, so it has no value when the condition fails",
+                "error[E0005]: `y` is assigned an `if` without an `else`, so it has no value when the condition fails",
+            ]
+        );
+        assert!(lint_src("fn main() { x = if a { 1; } else if b { 2; } else { 3; }; }").is_empty());
+    }
+
+    #[test]
+    fn accepts_ifs_without_else_as_statements() {
+        assert!(lint_src("fn main() { if a { print(1); } unless b { print(2); } }").is_empty());
+    }
+
+    #[test]
+    fn each_item_picks_its_own_indentation() {
+        assert!(lint_src("fn f() {\n\tx = 1;\n}\nfn g() {\n    y = 2;\n}\n").is_empty());
+    }
+}
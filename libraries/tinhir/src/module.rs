@@ -0,0 +1,337 @@
+//! The names brought into scope by `use` statements.
+
+use crate::diagnostic::{codes, Diagnostic};
+use crate::hir::*;
+use crate::span::Span;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+impl<'prgrm> PathGlob<'prgrm> {
+    /// Expand the glob's braces into the individual paths it names.
+    ///
+    /// A `*` can't be expanded without knowing what the module contains, so
+    /// paths ending in `*` are kept as they are.
+    pub fn resolve(&self) -> Vec<Path<'prgrm>> {
+        if !self.0.contains('{') {
+            return vec![Path(Cow::Borrowed(self.0))];
+        }
+
+        let mut paths = Vec::new();
+        expand("", self.0, &mut paths);
+        paths
+            .into_iter()
+            .map(|path| Path(Cow::Owned(path)))
+            .collect()
+    }
+}
+
+impl<'prgrm> Path<'prgrm> {
+    /// The module the path is in, which is everything before the last `:`.
+    pub fn module(&self) -> &str {
+        self.0.rsplit_once(':').map_or("", |(module, _)| module)
+    }
+
+    /// The name the path refers to, which is everything after the last `:`.
+    pub fn name(&self) -> &str {
+        self.0.rsplit_once(':').map_or(&self.0, |(_, name)| name)
+    }
+
+    /// Whether the path imports everything in a module.
+    pub fn is_glob(&self) -> bool {
+        self.name() == "*"
+    }
+}
+
+impl<'prgrm> Program<'prgrm> {
+    /// Warn about `use` statements which import something already imported,
+    /// given the source the program was parsed from.
+    ///
+    /// Importing the same path twice is a duplicate. Importing a module with
+    /// `*` overlaps importing anything else from that module, either way
+    /// around. Only the paths are compared, since what's in each module
+    /// isn't known.
+    pub fn validate_no_duplicate_use(&self, source: &str) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut seen: Vec<(Path<'prgrm>, &PathGlob<'prgrm>)> = Vec::new();
+
+        for glob in self.uses() {
+            for path in glob.resolve() {
+                let earlier = seen.iter().find(|(earlier, _)| {
+                    earlier.0 == path.0
+                        || (earlier.module() == path.module()
+                            && (earlier.is_glob() || path.is_glob()))
+                });
+
+                if let (Some((earlier, first)), Some(span)) = (earlier, Span::of(glob.0, source)) {
+                    let message = if earlier.0 == path.0 {
+                        format!("`{}` is already imported", path.0)
+                    } else {
+                        format!("`{}` overlaps the import of `{}`", path.0, earlier.0)
+                    };
+                    let mut diagnostic =
+                        Diagnostic::warning(span, message).with_code(codes::DUPLICATE_USE);
+
+                    if first.0.as_ptr() != glob.0.as_ptr() {
+                        if let Some(first_span) = Span::of(first.0, source) {
+                            diagnostic = diagnostic.with_related(first_span, "first imported here");
+                        }
+                    }
+
+                    diagnostics.push(diagnostic);
+                    continue;
+                }
+
+                seen.push((path, glob));
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// Expand the braces in a glob, adding each path to `paths`.
+fn expand(prefix: &str, glob: &str, paths: &mut Vec<String>) {
+    match glob.find('{') {
+        Some(open) => {
+            let prefix = format!("{}{}", prefix, &glob[..open]);
+            let inner = &glob[open + 1..];
+            let inner = inner.strip_suffix('}').unwrap_or(inner);
+
+            for part in split_top_level(inner) {
+                expand(&prefix, part.trim(), paths);
+            }
+        }
+        None => paths.push(format!("{}{}", prefix, glob)),
+    }
+}
+
+/// Split on the commas which aren't inside nested braces.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&text[start..]);
+    parts.retain(|part| !part.trim().is_empty());
+    parts
+}
+
+/// The names imported by a program's `use` statements, and the modules they
+/// come from.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ModuleTable {
+    names: BTreeMap<String, String>,
+    globs: Vec<String>,
+}
+
+/// The same name was imported from two different modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportConflict {
+    /// The name imported twice.
+    pub name: String,
+    /// The module it was first imported from.
+    pub first: String,
+    /// The module it was imported from again.
+    pub second: String,
+}
+
+impl StdError for ImportConflict {}
+
+impl Display for ImportConflict {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(
+            f,
+            "`{}` is imported from both `{}` and `{}`",
+            self.name, self.first, self.second
+        )
+    }
+}
+
+impl ModuleTable {
+    /// Build the table from every `use` statement in the program.
+    pub fn from_program(program: &Program<'_>) -> Result<ModuleTable, ImportConflict> {
+        let mut table = ModuleTable::default();
+
+        for path in program.uses().flat_map(PathGlob::resolve) {
+            table.insert(&path)?;
+        }
+
+        Ok(table)
+    }
+
+    /// Add a single imported path to the table.
+    ///
+    /// Importing the same name from the same module again is allowed.
+    pub fn insert(&mut self, path: &Path<'_>) -> Result<(), ImportConflict> {
+        let module = path.module();
+
+        if path.is_glob() {
+            if !self.globs.iter().any(|glob| glob == module) {
+                self.globs.push(module.to_string());
+            }
+
+            return Ok(());
+        }
+
+        match self.names.get(path.name()) {
+            Some(first) if first != module => Err(ImportConflict {
+                name: path.name().to_string(),
+                first: first.clone(),
+                second: module.to_string(),
+            }),
+            Some(_) => Ok(()),
+            None => {
+                self.names
+                    .insert(path.name().to_string(), module.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    /// Find the module a name was imported from.
+    pub fn module_of(&self, name: &str) -> Option<&str> {
+        self.names.get(name).map(String::as_str)
+    }
+
+    /// Iterate over the imported names and their modules, sorted by name.
+    pub fn names(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.names
+            .iter()
+            .map(|(name, module)| (name.as_str(), module.as_str()))
+    }
+
+    /// The modules everything was imported from with `*`, in source order.
+    pub fn globs(&self) -> &[String] {
+        &self.globs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    #[test]
+    fn resolves_braced_globs() {
+        let paths: Vec<_> = PathGlob("std:{io:{println, print}, fs:{read}}")
+            .resolve()
+            .into_iter()
+            .map(|path| path.0.into_owned())
+            .collect();
+        assert_eq!(paths, vec!["std:io:println", "std:io:print", "std:fs:read"]);
+    }
+
+    #[test]
+    fn resolves_plain_paths_without_allocating() {
+        let paths = PathGlob("std:io:println").resolve();
+        assert!(matches!(
+            &paths[..],
+            [Path(Cow::Borrowed("std:io:println"))]
+        ));
+    }
+
+    #[test]
+    fn builds_a_table_from_uses() {
+        let src = "\
+use std:io:{println, print};
+use std:fs:read;
+use std:io:println;
+use std:math:*;
+fn main() { }
+";
+        let program = parse(src).expect("should parse");
+        let table = ModuleTable::from_program(&program).expect("no conflicts");
+
+        assert_eq!(
+            table.names().collect::<Vec<_>>(),
+            vec![
+                ("print", "std:io"),
+                ("println", "std:io"),
+                ("read", "std:fs")
+            ]
+        );
+        assert_eq!(table.module_of("read"), Some("std:fs"));
+        assert_eq!(table.module_of("write"), None);
+        assert_eq!(table.globs(), &["std:math".to_string()]);
+    }
+
+    #[test]
+    fn warns_about_duplicate_uses() {
+        let src = "use std:io:println;\nuse std:{fs:read, io:println};\nfn main() { }\n";
+        let program = parse(src).expect("should parse");
+        let diagnostics = program.validate_no_duplicate_use(src);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "`std:io:println` is already imported"
+        );
+        assert_eq!(diagnostics[0].span, Span::new(24, 49));
+        assert_eq!(
+            diagnostics[0].related,
+            vec![(Span::new(4, 18), "first imported here".to_string())]
+        );
+    }
+
+    #[test]
+    fn warns_about_overlapping_globs() {
+        let src = "\
+use std:io:print;
+use std:io:*;
+use std:net:*;
+use std:net:{connect, read};
+fn main() { }
+";
+        let program = parse(src).expect("should parse");
+        let messages: Vec<_> = program
+            .validate_no_duplicate_use(src)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                "`std:io:*` overlaps the import of `std:io:print`",
+                "`std:net:connect` overlaps the import of `std:net:*`",
+                "`std:net:read` overlaps the import of `std:net:*`",
+            ]
+        );
+    }
+
+    #[test]
+    fn allows_separate_imports() {
+        let src = "use std:io:*;\nuse std:io:fs:read;\nuse std:net:{read, write};\nfn main() { }\n";
+        let program = parse(src).expect("should parse");
+        assert!(program.validate_no_duplicate_use(src).is_empty());
+    }
+
+    #[test]
+    fn detects_conflicting_imports() {
+        let src = "use std:io:read;\nuse std:net:{connect, read};\nfn main() { }\n";
+        let program = parse(src).expect("should parse");
+
+        assert_eq!(
+            ModuleTable::from_program(&program),
+            Err(ImportConflict {
+                name: "read".to_string(),
+                first: "std:io".to_string(),
+                second: "std:net".to_string(),
+            })
+        );
+    }
+}
@@ -0,0 +1,200 @@
+//! Options controlling how programs are parsed.
+
+use crate::charset::{is_ident_continue, is_ident_start, is_operator_char};
+use crate::error::Error;
+use crate::parse::Keyword;
+
+/// Options for `parse_with_options`.
+///
+/// The defaults match `parse`, so only the options which differ need to be
+/// set, as in `ParseOptions::default().keep_comments(false)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) keep_comments: bool,
+    pub(crate) unicode_idents: bool,
+    pub(crate) keywords: KeywordConfig,
+    pub(crate) script_mode: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            max_depth: None,
+            keep_comments: true,
+            unicode_idents: false,
+            keywords: KeywordConfig::default(),
+            script_mode: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Limit how deeply blocks and expressions may nest.
+    ///
+    /// Each block and each expression inside another counts as one level.
+    /// There's no limit by default.
+    pub fn max_depth(mut self, depth: usize) -> ParseOptions {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Whether comments are kept as statements in the program.
+    ///
+    /// They're kept by default. Doc comments are always kept on the items
+    /// they document.
+    pub fn keep_comments(mut self, keep: bool) -> ParseOptions {
+        self.keep_comments = keep;
+        self
+    }
+
+    /// Whether identifiers may contain letters and digits outside of ASCII.
+    ///
+    /// Identifiers are ASCII-only by default.
+    pub fn unicode_idents(mut self, allow: bool) -> ParseOptions {
+        self.unicode_idents = allow;
+        self
+    }
+
+    /// How keywords are spelled, for parsing dialects of the language.
+    ///
+    /// Every keyword has its usual spelling by default.
+    pub fn keywords(mut self, keywords: KeywordConfig) -> ParseOptions {
+        self.keywords = keywords;
+        self
+    }
+
+    /// Whether statements may be written at the top level, as in a script.
+    ///
+    /// They're gathered, in order, into the body of a `main` function, so a
+    /// script can't declare `main` itself. They're rejected by default.
+    pub fn script_mode(mut self, enabled: bool) -> ParseOptions {
+        self.script_mode = enabled;
+        self
+    }
+}
+
+/// How each keyword is spelled, if it's a keyword at all.
+///
+/// A disabled keyword is parsed as a plain identifier, and a renamed one is
+/// only recognized under its new spelling, which is reserved in its place.
+/// Operators like `and` are calls named with their usual spelling, so the
+/// printer always uses the usual spellings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeywordConfig {
+    /// The spelling of each keyword, in the order of `Keyword::ALL`.
+    spellings: [Option<&'static str>; Keyword::ALL.len()],
+}
+
+impl Default for KeywordConfig {
+    fn default() -> KeywordConfig {
+        let mut spellings = [None; Keyword::ALL.len()];
+
+        for (spelling, (usual, _)) in spellings.iter_mut().zip(Keyword::ALL) {
+            *spelling = Some(*usual);
+        }
+
+        KeywordConfig { spellings }
+    }
+}
+
+impl KeywordConfig {
+    /// Spell a keyword differently.
+    ///
+    /// The new spelling has to be shaped like an identifier, since that's
+    /// how keywords are read, and can't already spell another keyword or a
+    /// boolean.
+    pub fn rename(
+        mut self,
+        keyword: Keyword,
+        spelling: &'static str,
+    ) -> Result<KeywordConfig, Error> {
+        let mut chars = spelling.chars();
+        let is_word = chars.next().is_some_and(|c| is_ident_start(c, true))
+            && chars.all(|c| is_ident_continue(c, true))
+            && !spelling.ends_with(is_operator_char);
+
+        if !is_word {
+            return Err(Error::NotAWord(spelling));
+        }
+
+        let taken = self.lookup(spelling).is_some_and(|other| other != keyword);
+
+        if taken || spelling == "true" || spelling == "false" {
+            return Err(Error::KeywordTaken(spelling));
+        }
+
+        self.spellings[index(keyword)] = Some(spelling);
+        Ok(self)
+    }
+
+    /// Stop treating a keyword as a keyword.
+    pub fn disable(mut self, keyword: Keyword) -> KeywordConfig {
+        self.spellings[index(keyword)] = None;
+        self
+    }
+
+    /// How a keyword is spelled, or `None` if it's disabled.
+    pub fn spelling(&self, keyword: Keyword) -> Option<&'static str> {
+        self.spellings[index(keyword)]
+    }
+
+    /// Find the keyword with the given spelling, if there is one.
+    pub fn lookup(&self, text: &str) -> Option<Keyword> {
+        self.spellings
+            .iter()
+            .zip(Keyword::ALL)
+            .find(|(spelling, _)| **spelling == Some(text))
+            .map(|(_, (_, keyword))| *keyword)
+    }
+}
+
+fn index(keyword: Keyword) -> usize {
+    Keyword::ALL
+        .iter()
+        .position(|(_, kw)| *kw == keyword)
+        .expect("every keyword is in `Keyword::ALL`")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_must_be_words_which_are_free() {
+        let keywords = KeywordConfig::default();
+
+        let renamed = keywords
+            .rename(Keyword::And, "also")
+            .expect("should rename");
+        assert_eq!(renamed.lookup("also"), Some(Keyword::And));
+        assert_eq!(renamed.lookup("and"), None);
+
+        assert!(keywords.rename(Keyword::And, "and").is_ok());
+        assert!(keywords
+            .disable(Keyword::Or)
+            .rename(Keyword::And, "or")
+            .is_ok());
+
+        let error = |spelling| {
+            keywords
+                .rename(Keyword::And, spelling)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(error("or"), "`or` already spells a keyword or literal");
+        assert_eq!(error("true"), "`true` already spells a keyword or literal");
+        assert_eq!(
+            error("&&"),
+            "`&&` isn't shaped like an identifier, so it can't spell a keyword"
+        );
+        assert_eq!(
+            error("also-"),
+            "`also-` isn't shaped like an identifier, so it can't spell a keyword"
+        );
+        assert_eq!(
+            error(""),
+            "`` isn't shaped like an identifier, so it can't spell a keyword"
+        );
+    }
+}
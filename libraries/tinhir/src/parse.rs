@@ -1,171 +1,4286 @@
 #![allow(dead_code)]
 
-use crate::error::Error;
+use crate::charset::{
+    is_digit_in_radix, is_ident_continue, is_ident_start, is_operator_char, is_ty_continue,
+};
+use crate::diagnostic::{codes, Diagnostic};
+use crate::error::{Error, ParseError as ParseFailure};
 use crate::hir::*;
+use crate::lint::lint;
+use crate::options::ParseOptions;
+use crate::span::{line_column, Span};
+use crate::trivia::Trivia;
+use crate::visit::Node;
 use anyhow::{anyhow, Error as AnyError, Result};
 use nom::{
-    character::complete::{line_ending, not_line_ending},
-    combinator::complete,
-    error::{convert_error, VerboseError},
-    sequence::terminated,
-    Err, IResult,
+    branch::alt,
+    bytes::complete::{tag, take_while, take_while1, take_while_m_n},
+    character::complete::{
+        char as chr, multispace0, multispace1, none_of, not_line_ending, one_of, space0,
+    },
+    combinator::{complete, cut, map as fmap, not, opt, peek, recognize, verify},
+    error::{context, ErrorKind, ParseError, VerboseError, VerboseErrorKind},
+    multi::{many0, many1, separated_list, separated_nonempty_list},
+    sequence::{delimited, pair, preceded, terminated},
+    Err,
 };
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+pub(crate) use crate::combinators::ParseResult;
+
+thread_local! {
+    /// The options for the parse running on this thread.
+    static OPTIONS: Cell<ParseOptions> = Cell::new(ParseOptions::default());
+    /// How deeply nested the parser currently is.
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
 
-type ParseResult<I, O> = IResult<I, O, VerboseError<I>>;
+/// Parse the input into a complete program, or report why it didn't parse.
+pub fn parse(input: &str) -> Result<Program<'_>> {
+    parse_with_options(input, ParseOptions::default())
+}
+
+/// Parse the input into a complete program with the given options, or
+/// report why it didn't parse.
+///
+/// Unlike `parse_with_output`, this doesn't write the error report
+/// anywhere.
+pub fn parse_with_options(input: &str, options: ParseOptions) -> Result<Program<'_>> {
+    parse_with_output(input, options, &mut io::sink())
+}
 
-/// Parse the input into a complete program, or print errors and report that
-/// compilation failed.
-pub fn parse(input: &str) -> Result<Program> {
-    parse_with_errors(input)
+/// Parse the input into a complete program with the given options, or
+/// write errors to `out` and report that compilation failed.
+pub fn parse_with_output<'a>(
+    input: &'a str,
+    options: ParseOptions,
+    out: &mut dyn Write,
+) -> Result<Program<'a>> {
+    with_options(options, || parse_with_errors(input))
         .map(|(_, output)| output)
-        .map_err(|error| handle_error(input, error))
+        .map_err(|error| handle_error(input, error, out))
+}
+
+/// Parse raw bytes into a complete program with the given options, or
+/// report why they didn't parse.
+///
+/// The bytes must be UTF-8, optionally starting with a byte-order mark.
+pub fn parse_bytes(input: &[u8], options: ParseOptions) -> Result<Program<'_>> {
+    const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+    let (skipped, bytes) = match input.strip_prefix(BOM) {
+        Some(rest) => (BOM.len(), rest),
+        None => (0, input),
+    };
+
+    let input = std::str::from_utf8(bytes)
+        .map_err(|error| anyhow!(Error::InvalidUtf8(skipped + error.valid_up_to())))?;
+    parse_with_options(input, options)
+}
+
+/// Run a parser with the given options, restoring the previous ones after.
+///
+/// The parsers read their options from here, so every public way in to
+/// them has to go through this.
+pub(crate) fn with_options<T>(options: ParseOptions, parse: impl FnOnce() -> T) -> T {
+    struct Restore(ParseOptions, usize);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            OPTIONS.with(|options| options.set(self.0));
+            DEPTH.with(|depth| depth.set(self.1));
+        }
+    }
+
+    let _restore = Restore(
+        OPTIONS.with(|current| current.replace(options)),
+        DEPTH.with(|depth| depth.replace(0)),
+    );
+    parse()
 }
 
-fn parse_with_errors(input: &str) -> ParseResult<&str, Program> {
-    complete(line)(input).map(|(i, _)| (i, Program(Vec::new())))
+/// The options for the current parse.
+pub(crate) fn options() -> ParseOptions {
+    OPTIONS.with(Cell::get)
 }
 
-fn line(input: &str) -> ParseResult<&str, &str> {
-    terminated(not_line_ending, line_ending)(input)
+/// Run a parser one level deeper, failing if that's past the maximum depth.
+fn nested<'a, O>(
+    input: &'a str,
+    parser: impl FnOnce(&'a str) -> ParseResult<&'a str, O>,
+) -> ParseResult<&'a str, O> {
+    let depth = DEPTH.with(Cell::get) + 1;
+
+    if options().max_depth.is_some_and(|max| depth > max) {
+        return failure(input, "blocks and expressions are nested too deeply");
+    }
+
+    DEPTH.with(|current| current.set(depth));
+    let result = parser(input);
+    DEPTH.with(|current| current.set(depth - 1));
+    result
+}
+
+/// Parse the input into a complete program with the given options, also
+/// collecting all of its comments and blank lines.
+pub fn parse_all(input: &str, options: ParseOptions) -> Result<(Program<'_>, Trivia<'_>)> {
+    let program = parse_with_options(input, options)?;
+    Ok((program, Trivia::collect(input)))
+}
+
+/// Parse the input one top-level statement at a time, with the given
+/// options.
+///
+/// Errors are yielded in place of the statement which failed to parse.
+/// Parsing then picks up at the next line which starts a new top-level
+/// statement, so later statements are still yielded.
+pub fn parse_iter(input: &str, options: ParseOptions) -> impl Iterator<Item = Result<TopStmt<'_>>> {
+    ParseIter {
+        input,
+        rest: input,
+        options,
+    }
 }
 
-fn ident(_input: &str) -> ParseResult<&str, Ident<'_>> {
-    // idents can start with [a-zA-Z], but can then include [0-9] or -, _, +, >, <, *
-    todo!()
+struct ParseIter<'a> {
+    input: &'a str,
+    rest: &'a str,
+    options: ParseOptions,
 }
 
-fn bool(_input: &str) -> ParseResult<&str, Bool<'_>> {
-    todo!()
+impl<'a> Iterator for ParseIter<'a> {
+    type Item = Result<TopStmt<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rest = self.rest.trim_start();
+
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        match with_options(self.options, || complete(cut(top_stmt))(self.rest)) {
+            Ok((rest, stmt)) => {
+                self.rest = rest;
+                Some(Ok(stmt))
+            }
+            Err(Err::Error(error)) | Err(Err::Failure(error)) => {
+                let message = error_message(self.input, &error);
+                self.rest = recover(self.rest);
+                let failure = parse_failure(self.input, &error);
+                Some(Err(anyhow!(Error::ParseFailed(failure)).context(message)))
+            }
+            Err(Err::Incomplete(_)) => unreachable!(),
+        }
+    }
 }
 
-fn ty(_input: &str) -> ParseResult<&str, Ty<'_>> {
-    todo!()
+/// Skip to the next line which starts, without indentation, with
+/// something that looks like a top-level statement.
+fn recover(input: &str) -> &str {
+    let starts_stmt = |line: &str| {
+        ["fn", "type", "use", "//", "#["]
+            .iter()
+            .any(|start| line.starts_with(start))
+    };
+
+    let mut rest = input;
+
+    while let Some(newline) = rest.find('\n') {
+        rest = &rest[newline + 1..];
+
+        if starts_stmt(rest) {
+            return rest;
+        }
+    }
+
+    ""
 }
 
-fn int(_input: &str) -> ParseResult<&str, Int<'_>> {
-    todo!()
+/// Parse the input with the given options, returning every diagnostic found
+/// along the way.
+///
+/// If parsing succeeds, the program is returned along with anything `lint`
+/// finds in it. If it fails, there's no program, and the diagnostics
+/// include the error.
+pub fn parse_with_diagnostics(
+    input: &str,
+    options: ParseOptions,
+) -> (Option<Program<'_>>, Vec<Diagnostic>) {
+    match with_options(options, || parse_with_errors(input)) {
+        Ok((_, program)) => {
+            let diagnostics = lint(&program, input);
+            (Some(program), diagnostics)
+        }
+        Err(Err::Error(error)) | Err(Err::Failure(error)) => {
+            let offset = error_offset(input, &error);
+            let span = Span::new(offset, offset);
+            let diagnostic =
+                Diagnostic::error(span, error_context(&error)).with_code(codes::SYNTAX);
+            (None, vec![diagnostic])
+        }
+        Err(Err::Incomplete(_)) => unreachable!(),
+    }
 }
 
-fn float(_input: &str) -> ParseResult<&str, Float<'_>> {
-    todo!()
+fn parse_with_errors(input: &str) -> ParseResult<&str, Program<'_>> {
+    complete(program)(input)
 }
 
-fn ustr(_input: &str) -> ParseResult<&str, UStr<'_>> {
-    todo!()
+/// Parse top-level statements until the input runs out.
+///
+/// In script mode, statements at the top level make up the body of an
+/// implicit `main`, which goes where the first of them was written.
+fn program(input: &str) -> ParseResult<&str, Program<'_>> {
+    let mut stmts = Vec::new();
+    let mut script: Option<(usize, &str, Vec<Stmt<'_>>)> = None;
+    let (mut input, _) = multispace0(input)?;
+
+    while !input.is_empty() {
+        let (rest, item) = if options().script_mode {
+            cut(script_item)(input)?
+        } else {
+            fmap(cut(top_stmt), ScriptItem::Top)(input)?
+        };
+        let (rest, _) = multispace0(rest)?;
+
+        match item {
+            ScriptItem::Top(stmt) => {
+                if options().keep_comments || !matches!(stmt, TopStmt::Comment(_)) {
+                    stmts.push(stmt);
+                }
+            }
+            ScriptItem::Main(stmt) => {
+                check_labels(input, Node::from(&stmt))?;
+                let (_, _, body) = script.get_or_insert_with(|| (stmts.len(), input, Vec::new()));
+                body.push(stmt);
+            }
+        }
+
+        input = rest;
+    }
+
+    let mut program = Program(stmts);
+
+    if let Some((at, start, body)) = script {
+        if program.entry_point().is_some() {
+            return failure(
+                start,
+                "a script with top-level statements can't also declare `main`",
+            );
+        }
+
+        let main = FnDecl {
+            meta: ItemMeta::default(),
+            name: Ident("main"),
+            args: Vec::new(),
+            ret_ty: ReturnType::Inferred,
+            body: Block(body),
+        };
+        program.0.insert(at, TopStmt::FnDecl(main));
+    }
+
+    Ok((input, program))
 }
 
-fn bstr(_input: &str) -> ParseResult<&str, BStr<'_>> {
-    todo!()
+/// Something written at the top level of a script.
+enum ScriptItem<'a> {
+    /// A function, type, use statement, or comment, as in any program.
+    Top(TopStmt<'a>),
+    /// A statement belonging to the implicit `main`.
+    Main(Stmt<'a>),
 }
 
-fn char(_input: &str) -> ParseResult<&str, Char<'_>> {
-    todo!()
+fn script_item(input: &str) -> ParseResult<&str, ScriptItem<'_>> {
+    context(
+        "expected a function, type, use statement, comment, or statement",
+        alt((
+            fmap(top_stmt, ScriptItem::Top),
+            fmap(bare_stmt, ScriptItem::Main),
+        )),
+    )(input)
 }
 
-fn symbol(_input: &str) -> ParseResult<&str, Symbol<'_>> {
-    todo!()
+fn top_stmt(input: &str) -> ParseResult<&str, TopStmt<'_>> {
+    context(
+        "expected a function, type, use statement, or comment",
+        alt((
+            item,
+            fmap(comment, TopStmt::Comment),
+            fmap(use_stmt, TopStmt::Use),
+        )),
+    )(input)
 }
 
-fn array(_input: &str) -> ParseResult<&str, Array<'_>> {
-    todo!()
+fn use_stmt(input: &str) -> ParseResult<&str, PathGlob<'_>> {
+    let (input, _) = keyword(Keyword::Use)(input)?;
+    let (input, path) = expect("expected a path after `use`", ws(path_glob))(input)?;
+    let (input, _) = expect("expected `;` after the use statement", sym(";"))(input)?;
+    Ok((input, path))
 }
 
-fn tuple(_input: &str) -> ParseResult<&str, Tuple<'_>> {
-    todo!()
+/// A path glob, like `std:io:println`, `std:io:*`, or `std:io:{println, print}`.
+fn path_glob(input: &str) -> ParseResult<&str, PathGlob<'_>> {
+    fmap(recognize(path_tree), PathGlob)(input)
 }
 
-fn map(_input: &str) -> ParseResult<&str, Map<'_>> {
-    todo!()
+fn path_tree(input: &str) -> ParseResult<&str, ()> {
+    let (input, _) = ty_name(input)?;
+    let (input, _) = many0(preceded(
+        chr(':'),
+        cut(alt((
+            fmap(chr('*'), |_| ()),
+            fmap(
+                delimited(chr('{'), separated_list(sym(","), ws(path_tree)), sym("}")),
+                |_| (),
+            ),
+            path_tree,
+        ))),
+    ))(input)?;
+    Ok((input, ()))
 }
 
-fn handle_error(input: &str, error: Err<VerboseError<&str>>) -> AnyError {
-    match error {
-        // we call `complete` on the parser in `parse_with_errors` so this should never happen.
-        Err::Incomplete(_) => unreachable!(),
-        Err::Error(error) | Err::Failure(error) => println!("{}", convert_error(input, error)),
+/// A function or type, along with any doc comment and attributes before it.
+fn item(input: &str) -> ParseResult<&str, TopStmt<'_>> {
+    let (input, doc) = opt(doc_comment)(input)?;
+    let (input, attrs) = many0(ws(attr))(input)?;
+    let decl = alt((fmap(fn_decl, TopStmt::FnDecl), ty_decl));
+
+    let (input, mut stmt) = if doc.is_some() {
+        expect("expected a function or type after its doc comment", decl)(input)?
+    } else if !attrs.is_empty() {
+        expect("expected a function or type after its attributes", decl)(input)?
+    } else {
+        decl(input)?
+    };
+
+    let meta = ItemMeta { doc, attrs };
+
+    match &mut stmt {
+        TopStmt::FnDecl(decl) => decl.meta = meta,
+        TopStmt::TyDecl(decl) => decl.meta = meta,
+        TopStmt::TyAlias(alias) => alias.meta = meta,
+        TopStmt::Comment(_) | TopStmt::Use(_) => unreachable!(),
     }
 
-    anyhow!(Error::ParseFailed)
+    Ok((input, stmt))
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum Keyword {
-    // use
-    Use,
-    // fn
-    Fn,
-    // return
-    Return,
-    // and
-    And,
-    // or
-    Or,
-    // xor
-    Xor,
-    // not
-    Not,
-    // if
-    If,
-    // else
-    Else,
-    // else if
-    ElseIf,
-    // unless
-    Unless,
-    // else unless
-    ElseUnless,
-    // loop
-    Loop,
-    // while
-    While,
-    // until
-    Until,
-    // for
-    For,
-    // in
-    In,
+/// A doc comment, made of consecutive `///` lines.
+fn doc_comment(input: &str) -> ParseResult<&str, String> {
+    let (mut input, first) = doc_line(input)?;
+    let mut doc = first.to_string();
+
+    while let Ok((rest, line)) = preceded(line_gap, doc_line)(input) {
+        doc.push('\n');
+        doc.push_str(line);
+        input = rest;
+    }
+
+    Ok((input, doc))
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
-pub enum Operator {
-    // =
-    Assign,
-    // +
-    Add,
-    // -
-    Sub,
-    // *
-    Mul,
-    // /
-    Div,
-    // %
-    Rem,
-    // ^ (e.g. x ^ 2 == x * x)
-    Exp,
-    // +=
-    AddAssign,
-    // -=
-    SubAssign,
-    // *=
-    MulAssign,
-    // /=
-    DivAssign,
-    // %=
-    RemAssign,
-    // ^=
-    ExpAssign,
-    // == (equality of value)
-    Eq,
-    // @ (get identity)
-    Id,
-    // >
-    Greater,
-    // <
-    Less,
-    // >=
-    GreaterEq,
-    // <=
-    LessEq,
-    // !=
-    NotEq,
+/// A single `///` line, returning the text after the slashes and the space
+/// following them.
+fn doc_line(input: &str) -> ParseResult<&str, &str> {
+    let (input, line) = ws(preceded(
+        terminated(tag("///"), not(chr('/'))),
+        not_line_ending,
+    ))(input)?;
+    Ok((input, line.strip_prefix(' ').unwrap_or(line)))
+}
+
+/// An attribute, like `#[when(unix)]` or `#[inline]`.
+fn attr(input: &str) -> ParseResult<&str, Attr<'_>> {
+    let (input, _) = tag("#[")(input)?;
+    let (input, name) = expect("expected an attribute name", ws(ident))(input)?;
+    let (input, args) = opt(preceded(
+        chr('('),
+        terminated(
+            separated_list(sym(","), fmap(expr, Box::new)),
+            pair(
+                opt(sym(",")),
+                expect("expected `)` to close the attribute arguments", sym(")")),
+            ),
+        ),
+    ))(input)?;
+    let (input, _) = expect("expected `]` to close the attribute", sym("]"))(input)?;
+    Ok((
+        input,
+        Attr {
+            name,
+            args: args.unwrap_or_default(),
+        },
+    ))
+}
+
+fn fn_decl(input: &str) -> ParseResult<&str, FnDecl<'_>> {
+    let (input, _) = keyword(Keyword::Fn)(input)?;
+    cut(fn_decl_rest)(input)
+}
+
+fn fn_decl_rest(input: &str) -> ParseResult<&str, FnDecl<'_>> {
+    let (input, name) = expect("expected a function name", ws(ident))(input)?;
+    let (input, args) = params(input)?;
+    let (input, ret_ty) = return_type(input)?;
+    let (rest, body) = expect("expected `{` to start the function body", block)(input)?;
+    check_labels(input, Node::Block(&body))?;
+
+    Ok((
+        rest,
+        FnDecl {
+            meta: ItemMeta::default(),
+            name,
+            args,
+            ret_ty,
+            body,
+        },
+    ))
+}
+
+/// A type declaration, like `type Pair<A, B> { first: A, second: B }`.
+///
+/// A type with a single variant lists its fields directly, either named in
+/// braces or anonymous in parentheses. A type with several variants names
+/// each of them in braces, as in `type Either<L, R> { Left(L), Right(R) }`.
+///
+/// A type followed by `=` is an alias instead.
+fn ty_decl(input: &str) -> ParseResult<&str, TopStmt<'_>> {
+    let (input, _) = keyword(Keyword::Type)(input)?;
+    cut(alt((
+        fmap(ty_alias_rest, TopStmt::TyAlias),
+        fmap(ty_decl_rest, TopStmt::TyDecl),
+    )))(input)
+}
+
+/// A type alias, like `type Names = Array<Str>;`.
+///
+/// An alias which refers to itself could never be expanded, so it's
+/// rejected here rather than left for name resolution to loop on.
+fn ty_alias_rest(input: &str) -> ParseResult<&str, TyAlias<'_>> {
+    let (input, name) = ws(fmap(ty_name, Ident))(input)?;
+    let (input, _) = sym("=")(input)?;
+    let (rest, ty) = expect("expected a type after `=`", ws(ty))(input)?;
+    let (rest, _) = expect("expected `;` after the type alias", sym(";"))(rest)?;
+
+    if ty.names().any(|part| part == name.0) {
+        return failure(input.trim_start(), "a type alias can't refer to itself");
+    }
+
+    Ok((
+        rest,
+        TyAlias {
+            meta: ItemMeta::default(),
+            name,
+            ty,
+        },
+    ))
+}
+
+fn ty_decl_rest(input: &str) -> ParseResult<&str, TyDecl<'_>> {
+    let (input, name) = expect("expected a type name", ws(fmap(ty_name, Ident)))(input)?;
+    let (input, params) = ty_params(input)?;
+
+    let (input, variants) = expect(
+        "expected the fields or variants of the type",
+        alt((
+            fmap(
+                delimited(
+                    sym("{"),
+                    named_fields,
+                    expect("expected `}` to close the type", sym("}")),
+                ),
+                |fields| vec![TyVariant { name: None, fields }],
+            ),
+            fmap(anonymous_fields, |fields| {
+                vec![TyVariant { name: None, fields }]
+            }),
+            delimited(
+                sym("{"),
+                terminated(separated_list(sym(","), ty_variant), opt(sym(","))),
+                expect("expected `}` to close the type", sym("}")),
+            ),
+        )),
+    )(input)?;
+
+    Ok((
+        input,
+        TyDecl {
+            meta: ItemMeta::default(),
+            name,
+            params,
+            variants,
+        },
+    ))
+}
+
+/// The generic parameters of a type declaration, which may be left off.
+fn ty_params(input: &str) -> ParseResult<&str, Vec<Ident<'_>>> {
+    let (rest, params) = opt(preceded(
+        sym("<"),
+        cut(terminated(
+            separated_list(sym(","), ws(fmap(ty_name, Ident))),
+            pair(
+                opt(sym(",")),
+                expect("expected `>` to close the type parameters", sym(">")),
+            ),
+        )),
+    ))(input)?;
+    let params = params.unwrap_or_default();
+
+    for (i, param) in params.iter().enumerate() {
+        if params[..i].iter().any(|earlier| earlier.0 == param.0) {
+            let at = Span::of(param.0, input).map_or(input, |span| &input[span.start..]);
+            return failure(at, "type parameter names must be unique");
+        }
+    }
+
+    Ok((rest, params))
+}
+
+/// A named variant of a type.
+///
+/// A variant with no fields, like `None`, may leave off its `()`.
+fn ty_variant(input: &str) -> ParseResult<&str, TyVariant<'_>> {
+    let (input, name) = ws(fmap(ty_name, Ident))(input)?;
+    let (input, fields) = opt(alt((
+        delimited(
+            sym("{"),
+            named_fields,
+            expect("expected `}` to close the fields", sym("}")),
+        ),
+        anonymous_fields,
+    )))(input)?;
+    let fields = fields.unwrap_or(Fields::Anonymous(Vec::new()));
+    Ok((
+        input,
+        TyVariant {
+            name: Some(name),
+            fields,
+        },
+    ))
+}
+
+/// Named fields, like `first: A, second: B`, without their braces.
+///
+/// At least one field is needed, so they can be told apart from a list of
+/// variants.
+fn named_fields(input: &str) -> ParseResult<&str, Fields<'_>> {
+    let (input, _) = peek(pair(ws(ident), sym(":")))(input)?;
+    let (input, first) = ty_ident(input)?;
+    let (input, rest) = many0(preceded(sym(","), ty_ident))(input)?;
+    let (input, _) = opt(sym(","))(input)?;
+
+    let mut fields = vec![first];
+    fields.extend(rest);
+    Ok((input, Fields::Named(fields)))
+}
+
+/// Anonymous fields, like `(A, B)`.
+fn anonymous_fields(input: &str) -> ParseResult<&str, Fields<'_>> {
+    fmap(
+        delimited(
+            sym("("),
+            terminated(separated_list(sym(","), ws(ty)), opt(sym(","))),
+            expect("expected `)` to close the fields", sym(")")),
+        ),
+        Fields::Anonymous,
+    )(input)
+}
+
+/// A function's parenthesized parameter list.
+fn params(input: &str) -> ParseResult<&str, Vec<TyIdent<'_>>> {
+    delimited(
+        expect("expected `(` to start the parameter list", sym("(")),
+        terminated(separated_list(sym(","), ty_ident), opt(sym(","))),
+        expect("expected `)` to close the parameter list", sym(")")),
+    )(input)
+}
+
+/// An optional return type, where `-> ()` is distinct from leaving it off.
+fn return_type(input: &str) -> ParseResult<&str, ReturnType<'_>> {
+    let (input, arrow) = opt(sym("->"))(input)?;
+
+    if arrow.is_none() {
+        return Ok((input, ReturnType::Inferred));
+    }
+
+    expect(
+        "expected a return type after `->`",
+        alt((
+            fmap(pair(sym("("), sym(")")), |_| ReturnType::Unit),
+            fmap(ws(tuple_ty), ReturnType::Tuple),
+            fmap(ws(ty), ReturnType::Explicit),
+        )),
+    )(input)
+}
+
+fn ty_ident(input: &str) -> ParseResult<&str, TyIdent<'_>> {
+    let (input, ident) = ws(ident)(input)?;
+    let (input, _) = expect("expected `:` after the name", sym(":"))(input)?;
+    let (input, ty) = expect("expected a type", ws(ty))(input)?;
+    Ok((input, TyIdent { ident, ty }))
+}
+
+fn block(input: &str) -> ParseResult<&str, Block<'_>> {
+    let (input, _) = sym("{")(input)?;
+    nested(input, block_rest)
+}
+
+/// The statements of a block and its closing `}`.
+fn block_rest(mut input: &str) -> ParseResult<&str, Block<'_>> {
+    let mut stmts = Vec::new();
+
+    loop {
+        let (rest, _) = multispace0(input)?;
+
+        if let Some(rest) = rest.strip_prefix('}') {
+            return Ok((rest, Block(stmts)));
+        }
+
+        if rest.is_empty() {
+            return failure(rest, "expected `}` to close the block");
+        }
+
+        let (rest, stmt) = expect("expected a statement", stmt)(rest)?;
+
+        if options().keep_comments || !matches!(stmt, Stmt::Comment(_)) {
+            stmts.push(stmt);
+        }
+
+        input = rest;
+    }
+}
+
+fn stmt(input: &str) -> ParseResult<&str, Stmt<'_>> {
+    alt((documented_stmt, fmap(comment, Stmt::Comment), bare_stmt))(input)
+}
+
+/// A statement other than a comment, along with any comment after it on
+/// the same line.
+///
+/// A trailing comment belongs to the statement before it, so it's never
+/// joined with comments on the lines after.
+fn bare_stmt(input: &str) -> ParseResult<&str, Stmt<'_>> {
+    let (input, stmt) = alt((
+        fmap(var_assign, Stmt::VarAssign),
+        fmap(multi_assign, Stmt::MultiAssign),
+        fmap(compound_assign, Stmt::CompoundAssign),
+        fmap(expr_stmt, Stmt::Expr),
+    ))(input)?;
+    let (input, comment) = opt(trailing_comment)(input)?;
+
+    match comment {
+        Some(comment) if options().keep_comments => {
+            Ok((input, Stmt::Trailed(Box::new(stmt), comment)))
+        }
+        _ => Ok((input, stmt)),
+    }
+}
+
+/// A `//` comment on the rest of the current line, returning the text
+/// after the slashes.
+fn trailing_comment(input: &str) -> ParseResult<&str, &str> {
+    preceded(space0, preceded(tag("//"), not_line_ending))(input)
+}
+
+/// A statement with a doc comment on the line right before it.
+///
+/// A doc comment with a blank line or another comment after it documents
+/// nothing, and is parsed as an ordinary comment instead.
+fn documented_stmt(input: &str) -> ParseResult<&str, Stmt<'_>> {
+    let (input, doc) = doc_comment(input)?;
+    let (input, stmt) = preceded(line_gap, bare_stmt)(input)?;
+
+    if options().keep_comments {
+        Ok((input, Stmt::Documented(doc, Box::new(stmt))))
+    } else {
+        Ok((input, stmt))
+    }
+}
+
+fn var_assign(input: &str) -> ParseResult<&str, VarAssign<'_>> {
+    let (input, pattern) = pattern(input)?;
+    let (input, ty) = opt(preceded(sym(":"), ws(ty)))(input)?;
+    let (input, _) = ws(terminated(chr('='), not(chr('='))))(input)?;
+    let (input, rhs) = expect(
+        "expected an expression after `=`",
+        alt((labeled_block, expr)),
+    )(input)?;
+    let (input, _) = cut(terminator)(input)?;
+    Ok((
+        input,
+        VarAssign {
+            pattern,
+            ty,
+            rhs: Box::new(rhs),
+        },
+    ))
+}
+
+/// An assignment of several values at once, like `a, b = 1, 2`.
+fn multi_assign(input: &str) -> ParseResult<&str, MultiAssign<'_>> {
+    let (input, first) = pattern(input)?;
+    let (input, rest) = many1(preceded(sym(","), ws(pattern)))(input)?;
+    let (input, _) = ws(terminated(chr('='), not(chr('='))))(input)?;
+    let (rest_input, values) = expect(
+        "expected an expression after `=`",
+        separated_nonempty_list(sym(","), expr),
+    )(input)?;
+
+    let patterns: Vec<_> = std::iter::once(first).chain(rest).collect();
+
+    if values.len() != patterns.len() {
+        return failure(
+            input.trim_start(),
+            "expected as many values as names being assigned",
+        );
+    }
+
+    let (input, _) = cut(terminator)(rest_input)?;
+    Ok((
+        input,
+        MultiAssign {
+            patterns,
+            values: values.into_iter().map(Box::new).collect(),
+        },
+    ))
+}
+
+/// An assignment which updates a variable, field, or element with an
+/// operator, like `x += 1` or `grid[y][x] *= 2`.
+fn compound_assign(input: &str) -> ParseResult<&str, CompoundAssign<'_>> {
+    let (input, target) = alt((
+        fmap(place, |place| Pattern::Place(Box::new(place))),
+        fmap(ident, Pattern::Ident),
+    ))(input)?;
+    let (input, op) = ws(terminated(recognize(one_of("+-*/%^")), chr('=')))(input)?;
+    let (input, rhs) = expect("expected an expression after the operator", expr)(input)?;
+    let (input, _) = cut(terminator)(input)?;
+    Ok((
+        input,
+        CompoundAssign {
+            target,
+            op,
+            rhs: Box::new(rhs),
+        },
+    ))
+}
+
+/// The left-hand side of an assignment, which may destructure a tuple or
+/// array into several variables.
+fn pattern(input: &str) -> ParseResult<&str, Pattern<'_>> {
+    alt((
+        tuple_pattern,
+        fmap(preceded(tag("#["), patterns("]")), |(items, rest, _)| {
+            Pattern::Array(items, rest)
+        }),
+        fmap(place, |place| Pattern::Place(Box::new(place))),
+        fmap(ident, Pattern::Ident),
+    ))(input)
+}
+
+/// A field or element which can be assigned to, like `point.x` or
+/// `grid[y][x]`.
+///
+/// This is a name followed by at least one field access or index. Calls
+/// aren't allowed anywhere in it, since their results can't be assigned.
+fn place(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (mut input, name) = ident(input)?;
+    let mut place = Expr::Ident(name);
+
+    loop {
+        if let Ok((rest, field)) = preceded(sym("."), ws(ident))(input) {
+            if !rest.starts_with('(') {
+                place = Expr::Dot(Box::new(place), Box::new(Expr::Ident(field)));
+                input = rest;
+                continue;
+            }
+        }
+
+        match index(input) {
+            Ok((rest, index)) => {
+                place = Expr::Index(Box::new(place), Box::new(index));
+                input = rest;
+            }
+            Err(Err::Error(_)) if matches!(place, Expr::Ident(_)) => {
+                return Err(Err::Error(VerboseError::from_error_kind(
+                    input,
+                    ErrorKind::Verify,
+                )));
+            }
+            Err(Err::Error(_)) => return Ok((input, place)),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// A tuple pattern, which like a tuple literal needs a trailing comma if it
+/// has only one element.
+fn tuple_pattern(input: &str) -> ParseResult<&str, Pattern<'_>> {
+    let (input, _) = tag("#(")(input)?;
+    let (after, (items, rest, trailing_comma)) = patterns(")")(input)?;
+
+    if items.len() == 1 && rest.is_none() && !trailing_comma {
+        return failure(
+            input,
+            "a tuple with one element needs a trailing comma, as in `#(x,)`",
+        );
+    }
+
+    Ok((after, Pattern::Tuple(items, rest)))
+}
+
+/// The comma-separated elements of a pattern, up to the closing bracket,
+/// possibly ending with a `..` rest, and whether they end with a comma.
+///
+/// Until a `..` is seen, this might still be an expression statement
+/// instead, like `#(a, b);`, so only errors after that are unrecoverable.
+#[allow(clippy::type_complexity)]
+fn patterns<'a>(
+    close: &'static str,
+) -> impl Fn(&'a str) -> ParseResult<&'a str, (Vec<Pattern<'a>>, Option<Rest<'a>>, bool)> {
+    move |mut input: &'a str| {
+        let mut items = Vec::new();
+        let mut rest = None;
+        let mut trailing_comma = false;
+
+        loop {
+            let (after, _) = multispace0(input)?;
+
+            if let Some(after) = after.strip_prefix(close) {
+                return Ok((after, (items, rest, trailing_comma)));
+            }
+
+            if let Ok((after, _)) = sym("..")(after) {
+                if rest.is_some() {
+                    return failure(after, "a pattern may only have one `..`");
+                }
+
+                let (after, name) = opt(ident)(after)?;
+                rest = Some(Rest(name));
+                input = after;
+            } else if rest.is_some() {
+                return failure(after, "`..` must be the last element of a pattern");
+            } else {
+                let (after, item) = ws(pattern)(after)?;
+                items.push(item);
+                input = after;
+            }
+
+            match sym(",")(input) {
+                Ok((after, _)) => {
+                    input = after;
+                    trailing_comma = true;
+                }
+                Err(Err::Error(_)) => {
+                    let (after, _) = sym(close)(input)?;
+                    return Ok((after, (items, rest, false)));
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+/// An expression used as a statement.
+///
+/// Expressions ending in a block don't need a terminating `;`.
+fn expr_stmt(input: &str) -> ParseResult<&str, BExpr<'_>> {
+    let start = input;
+    let (input, expr) = alt((labeled_block, expr))(input)?;
+
+    // Anything assignable was already parsed as an assignment.
+    if ws(terminated(chr('='), not(chr('='))))(input).is_ok() {
+        let message = match &expr {
+            Expr::Literal(_) => "can't assign to a literal",
+            Expr::FnCall(call) if !call.name.is_operator() => {
+                "can't assign to the result of a call"
+            }
+            Expr::Dot(_, field) if matches!(**field, Expr::FnCall(_)) => {
+                "can't assign to the result of a call"
+            }
+            _ => "can't assign to this expression",
+        };
+        return failure(start.trim_start(), message);
+    }
+
+    let (input, _) = if ends_with_block(&expr) {
+        fmap(opt(sym(";")), |_| ())(input)?
+    } else {
+        cut(terminator)(input)?
+    };
+
+    Ok((input, Box::new(expr)))
+}
+
+/// Statements end with a `;`, which may be left off the final statement
+/// in a block.
+fn terminator(input: &str) -> ParseResult<&str, ()> {
+    let (rest, _) = multispace0(input)?;
+
+    if rest.starts_with('}') {
+        return Ok((rest, ()));
+    }
+
+    context("expected `;` after the statement", fmap(chr(';'), |_| ()))(rest)
+}
+
+fn ends_with_block(expr: &Expr<'_>) -> bool {
+    use Expr::*;
+
+    matches!(
+        expr,
+        If(..) | Unless(..) | Loop(..) | While(..) | Until(..) | For(..) | Match(..) | Labeled(..)
+    )
+}
+
+/// Parse an expression.
+///
+/// From loosest to tightest binding, the precedence levels are:
+///
+/// 1. `or`
+/// 2. `xor`
+/// 3. `and`
+/// 4. `not` (prefix)
+/// 5. `==`, `!=`, `<`, `>`, `<=`, `>=` (non-associative)
+/// 6. `|` (bitwise or)
+/// 7. `~` (bitwise xor)
+/// 8. `&` (bitwise and)
+/// 9. `<<`, `>>`
+/// 10. `+`, `-`
+/// 11. `*`, `/`, `%`
+/// 12. `-`, `@` (prefix)
+/// 13. `^` (right-associative)
+/// 14. `.` (field access), `[]` (indexing)
+///
+/// Operators are represented as calls to a function whose name is the
+/// operator itself. Their levels are in `BINARY` and `PREFIX`, which the
+/// parser reads the binary operators from.
+pub(crate) fn expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    nested(input, or_expr)
+}
+
+/// Operators written between their two operands, with their precedence
+/// levels, as listed on `expr`. Keywords have their usual spellings.
+pub(crate) const BINARY: &[(&str, u8)] = &[
+    ("or", 1),
+    ("xor", 2),
+    ("and", 3),
+    ("==", COMPARISON),
+    ("!=", COMPARISON),
+    ("<", COMPARISON),
+    (">", COMPARISON),
+    ("<=", COMPARISON),
+    (">=", COMPARISON),
+    ("|", 6),
+    ("~", 7),
+    ("&", 8),
+    ("<<", 9),
+    (">>", 9),
+    ("+", 10),
+    ("-", 10),
+    ("*", 11),
+    ("/", 11),
+    ("%", 11),
+    ("^", POWER),
+];
+
+/// Operators written before their one operand, with their precedence
+/// levels, as listed on `expr`.
+pub(crate) const PREFIX: &[(&str, u8)] = &[("not", 4), ("-", NEGATION), ("@", NEGATION)];
+
+/// The precedence of comparisons, which don't chain.
+pub(crate) const COMPARISON: u8 = 5;
+
+/// The precedence of negation, which an exponent may start with.
+pub(crate) const NEGATION: u8 = 12;
+
+/// The precedence of exponentiation, which binds to the right.
+pub(crate) const POWER: u8 = 13;
+
+/// The precedence of field access, and of anything which is never wrapped.
+pub(crate) const POSTFIX: u8 = 14;
+
+/// Any operator in `BINARY` with the given precedence level.
+fn binary_op<'a>(level: u8) -> impl Fn(&'a str) -> ParseResult<&'a str, &'a str> {
+    move |input| {
+        let mut last = VerboseError::from_error_kind(input, ErrorKind::Tag);
+
+        for (text, _) in BINARY.iter().filter(|(_, op_level)| *op_level == level) {
+            let result = match Keyword::ALL.iter().find(|(usual, _)| usual == text) {
+                Some((_, kw)) => keyword_op(*kw)(input),
+                None => op(text)(input),
+            };
+
+            match result {
+                Err(Err::Error(error)) => last = error,
+                result => return result,
+            }
+        }
+
+        Err(Err::Error(VerboseError::append(
+            input,
+            ErrorKind::Alt,
+            last,
+        )))
+    }
+}
+
+fn or_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, xor_expr, binary_op(1))
+}
+
+fn xor_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, and_expr, binary_op(2))
+}
+
+fn and_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, not_expr, binary_op(3))
+}
+
+fn not_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    alt((
+        fmap(
+            pair(keyword_op(Keyword::Not), operand(not_expr)),
+            |(op, operand)| op_call(op, vec![operand]),
+        ),
+        comparison,
+    ))(input)
+}
+
+fn comparison(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, lhs) = bit_or(input)?;
+    match binary_op(COMPARISON)(input) {
+        Ok((input, op)) => {
+            let (input, rhs) = operand(bit_or)(input)?;
+            Ok((input, op_call(op, vec![lhs, rhs])))
+        }
+        Err(Err::Error(_)) => Ok((input, lhs)),
+        Err(error) => Err(error),
+    }
+}
+
+fn bit_or(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, bit_xor, binary_op(6))
+}
+
+fn bit_xor(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, bit_and, binary_op(7))
+}
+
+fn bit_and(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, shift, binary_op(8))
+}
+
+fn shift(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, sum, binary_op(9))
+}
+
+fn sum(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, product, binary_op(10))
+}
+
+fn product(input: &str) -> ParseResult<&str, Expr<'_>> {
+    left_assoc(input, negation, binary_op(11))
+}
+
+fn negation(input: &str) -> ParseResult<&str, Expr<'_>> {
+    alt((
+        negative_int,
+        prefix_increment,
+        fmap(pair(op("-"), operand(negation)), |(op, operand)| {
+            op_call(op, vec![operand])
+        }),
+        identity,
+        power,
+    ))(input)
+}
+
+/// A C-style `++x`, which Tin doesn't have, so it gets a better message
+/// than a missing operand would.
+///
+/// `--x` is a double negation, so only `++` is caught here.
+fn prefix_increment<'a>(input: &'a str) -> ParseResult<&'a str, Expr<'a>> {
+    let (_, _) = sym("++")(input)?;
+    failure(input.trim_start(), INCREMENT)
+}
+
+/// A C-style `x++` or `x--` ending an expression, which Tin doesn't have.
+fn postfix_increment(input: &str) -> ParseResult<&str, &str> {
+    let (rest, op) = alt((sym("++"), sym("--")))(input)?;
+
+    match rest.trim_start().chars().next() {
+        None | Some(';') | Some(',') | Some(')') | Some(']') | Some('}') => Ok((rest, op)),
+        Some(_) => Err(Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Verify,
+        ))),
+    }
+}
+
+const INCREMENT: &str = "there's no `++` operator; use `+= 1` instead";
+const DECREMENT: &str = "there's no `--` operator; use `-= 1` instead";
+
+/// The identity of a place, like `@x` or `@point.x`.
+///
+/// Identity is reference identity: `@a == @b` when `a` and `b` name the same
+/// value in memory, not merely equal ones. Values like literals are never
+/// stored anywhere by name, so they have no identity to take.
+fn identity(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (rest, op) = op("@")(input)?;
+    let (rest, place) = operand(postfix)(rest)?;
+
+    match place {
+        Expr::Ident(_) | Expr::Dot(..) | Expr::Index(..) => Ok((rest, op_call(op, vec![place]))),
+        _ => failure(
+            input.trim_start(),
+            "`@` takes the identity of a name, field, or element",
+        ),
+    }
+}
+
+fn power(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, base) = postfix(input)?;
+
+    match binary_op(POWER)(input) {
+        // The exponent may itself be negated, and binds to the right.
+        Ok((input, op)) => {
+            let (input, exponent) = operand(negation)(input)?;
+            Ok((input, op_call(op, vec![base, exponent])))
+        }
+        Err(Err::Error(_)) => Ok((input, base)),
+        Err(error) => Err(error),
+    }
+}
+
+/// An expression followed by any number of field accesses, method calls,
+/// indexes, and `?`s, like `a.b()?[0]`.
+///
+/// These all bind equally tightly, so they apply left to right.
+fn postfix(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (mut input, mut expr) = primary(input)?;
+
+    loop {
+        if let Ok((_, op)) = postfix_increment(input) {
+            let message = if op == "++" { INCREMENT } else { DECREMENT };
+            return failure(input.trim_start(), message);
+        }
+
+        match suffix(input) {
+            Ok((rest, suffix)) => {
+                expr = suffix.apply(expr);
+                input = rest;
+            }
+            Err(Err::Error(_)) => return Ok((input, expr)),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// A single postfix operation, to apply to the expression before it.
+enum Suffix<'a> {
+    /// A `?`.
+    Try,
+    /// A `.` followed by a field name or method call.
+    Dot(Expr<'a>),
+    /// An index in brackets.
+    Index(Expr<'a>),
+}
+
+impl<'a> Suffix<'a> {
+    fn apply(self, expr: Expr<'a>) -> Expr<'a> {
+        match self {
+            Suffix::Try => Expr::Try(Box::new(expr)),
+            Suffix::Dot(field) => Expr::Dot(Box::new(expr), Box::new(field)),
+            Suffix::Index(index) => Expr::Index(Box::new(expr), Box::new(index)),
+        }
+    }
+}
+
+fn suffix(input: &str) -> ParseResult<&str, Suffix<'_>> {
+    alt((
+        fmap(sym("?"), |_| Suffix::Try),
+        fmap(
+            preceded(
+                sym("."),
+                expect(
+                    "expected a field or method name after `.`",
+                    alt((fmap(fn_call, Expr::FnCall), fmap(ident, Expr::Ident))),
+                ),
+            ),
+            Suffix::Dot,
+        ),
+        fmap(index, Suffix::Index),
+    ))(input)
+}
+
+/// An index in brackets, like the `[0]` in `items[0]`.
+fn index(input: &str) -> ParseResult<&str, Expr<'_>> {
+    delimited(
+        sym("["),
+        expect("expected an index after `[`", ws(expr)),
+        expect("expected `]` to close the index", sym("]")),
+    )(input)
+}
+
+fn primary(input: &str) -> ParseResult<&str, Expr<'_>> {
+    ws(alt((
+        delimited(
+            chr('('),
+            expect("expected an expression", ws(expr)),
+            expect("expected `)` to close the parentheses", sym(")")),
+        ),
+        if_expr,
+        unless_expr,
+        loop_expr,
+        while_expr,
+        until_expr,
+        for_expr,
+        fmap(match_expr, Expr::Match),
+        break_expr,
+        continue_expr,
+        return_expr,
+        fmap(lambda, Expr::Lambda),
+        fmap(value_literal, Expr::Literal),
+        fmap(fn_call, Expr::FnCall),
+        fmap(ident, Expr::Ident),
+    )))(input)
+}
+
+/// An anonymous function, which is a function declaration without a name.
+fn lambda(input: &str) -> ParseResult<&str, Lambda<'_>> {
+    let (input, _) = keyword(Keyword::Fn)(input)?;
+    let (input, args) = params(input)?;
+    let (input, ret_ty) = return_type(input)?;
+    let (input, body) = expect("expected `{` to start the function body", block)(input)?;
+    Ok((input, Lambda { args, ret_ty, body }))
+}
+
+fn if_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, _) = keyword(Keyword::If)(input)?;
+    let (input, (cond, then)) = cut(cond_block)(input)?;
+    let (input, els) = else_block(input)?;
+    Ok((input, Expr::If(cond, then, els)))
+}
+
+fn unless_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, _) = keyword(Keyword::Unless)(input)?;
+    let (input, (cond, then)) = cut(cond_block)(input)?;
+    let (input, els) = else_block(input)?;
+    Ok((input, Expr::Unless(cond, then, els)))
+}
+
+/// A condition followed by a block, as used by `if` and the loops.
+fn cond_block(input: &str) -> ParseResult<&str, (BExpr<'_>, Block<'_>)> {
+    let (input, cond) = expect("expected a condition", expr)(input)?;
+    let (input, body) = expect("expected `{` to start the block", block)(input)?;
+    Ok((input, (Box::new(cond), body)))
+}
+
+/// An optional `else`, which may chain into another `if` or `unless`.
+///
+/// Chained conditionals are represented as an `else` block containing
+/// only the chained expression.
+fn else_block(input: &str) -> ParseResult<&str, Option<Block<'_>>> {
+    let (input, els) = opt(keyword(Keyword::Else))(input)?;
+
+    if els.is_none() {
+        return Ok((input, None));
+    }
+
+    let chained = fmap(alt((if_expr, unless_expr)), |expr| {
+        Block(vec![Stmt::Expr(Box::new(expr))])
+    });
+
+    let (input, block) = expect(
+        "expected a block, `if`, or `unless` after `else`",
+        alt((chained, block)),
+    )(input)?;
+    Ok((input, Some(block)))
+}
+
+/// An optional `else` on a loop, which runs if the body never executed.
+fn loop_else(input: &str) -> ParseResult<&str, Option<Block<'_>>> {
+    opt(preceded(
+        keyword(Keyword::Else),
+        expect("expected a block after `else`", block),
+    ))(input)
+}
+
+fn loop_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, _) = keyword(Keyword::Loop)(input)?;
+    let (input, body) = expect("expected `{` to start the block", block)(input)?;
+    Ok((input, Expr::Loop(body)))
+}
+
+fn while_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, _) = keyword(Keyword::While)(input)?;
+    let (input, (cond, body)) = cut(cond_block)(input)?;
+    let (input, els) = loop_else(input)?;
+    Ok((input, Expr::While(cond, body, els)))
+}
+
+fn until_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, _) = keyword(Keyword::Until)(input)?;
+    let (input, (cond, body)) = cut(cond_block)(input)?;
+    let (input, els) = loop_else(input)?;
+    Ok((input, Expr::Until(cond, body, els)))
+}
+
+fn for_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, _) = keyword(Keyword::For)(input)?;
+    let (input, item) = expect("expected a loop variable", fmap(ws(ident), Expr::Ident))(input)?;
+    let (input, _) = expect(
+        "expected `in` after the loop variable",
+        keyword(Keyword::In),
+    )(input)?;
+    let (input, iter) = expect("expected an expression to loop over", expr)(input)?;
+    let (input, body) = expect("expected `{` to start the block", block)(input)?;
+    let (input, els) = loop_else(input)?;
+    Ok((input, Expr::For(Box::new(item), Box::new(iter), body, els)))
+}
+
+/// A `match`, like `match n { 0 => :none, n => { log(n); :some } }`.
+///
+/// Arms are separated by commas, which may be left off after a block.
+fn match_expr(input: &str) -> ParseResult<&str, Match<'_>> {
+    let (input, _) = keyword(Keyword::Match)(input)?;
+    let (input, scrutinee) = expect("expected a value to match on", expr)(input)?;
+    let (mut input, _) = expect("expected `{` to start the arms", sym("{"))(input)?;
+    let mut arms = Vec::new();
+
+    loop {
+        if let Ok((rest, _)) = sym("}")(input) {
+            let scrutinee = Box::new(scrutinee);
+            return Ok((rest, Match { scrutinee, arms }));
+        }
+
+        let (rest, (arm, braced)) = arm(input)?;
+        arms.push(arm);
+
+        input = match sym(",")(rest) {
+            Ok((rest, _)) => rest,
+            Err(Err::Error(_)) if braced => rest,
+            Err(Err::Error(_)) => {
+                let (rest, _) = expect("expected `,` or `}` after the arm", sym("}"))(rest)?;
+                let scrutinee = Box::new(scrutinee);
+                return Ok((rest, Match { scrutinee, arms }));
+            }
+            Err(error) => return Err(error),
+        };
+    }
+}
+
+/// A single arm, and whether its body was a block.
+fn arm(input: &str) -> ParseResult<&str, (Arm<'_>, bool)> {
+    let (input, pattern) = expect("expected a pattern", ws(arm_pattern))(input)?;
+    let (input, _) = expect("expected `=>` after the pattern", sym("=>"))(input)?;
+    let (input, (body, braced)) = expect(
+        "expected a block or expression after `=>`",
+        alt((
+            fmap(block, |body| (body, true)),
+            fmap(expr, |expr| {
+                (Block(vec![Stmt::Expr(Box::new(expr))]), false)
+            }),
+        )),
+    )(input)?;
+    Ok((input, (Arm { pattern, body }, braced)))
+}
+
+/// The pattern of a `match` arm.
+fn arm_pattern(input: &str) -> ParseResult<&str, ArmPattern<'_>> {
+    alt((
+        fmap(
+            alt((
+                fmap(bool, Literal::Bool),
+                fmap(float, Literal::Float),
+                fmap(int, Literal::Int),
+                fmap(ustr, Literal::UStr),
+                fmap(bstr, Literal::BStr),
+                fmap(char, Literal::Char),
+                fmap(symbol, Literal::Symbol),
+            )),
+            ArmPattern::Literal,
+        ),
+        variant_pattern,
+        fmap(ident, ArmPattern::Bind),
+    ))(input)
+}
+
+/// A variant pattern, like `None` or `Some(n)`.
+fn variant_pattern(input: &str) -> ParseResult<&str, ArmPattern<'_>> {
+    let (input, name) = verify(ident, |name: &Ident<'_>| {
+        name.0.starts_with(|c: char| c.is_uppercase())
+    })(input)?;
+    let (input, fields) = opt(preceded(
+        chr('('),
+        terminated(
+            separated_list(sym(","), ws(arm_pattern)),
+            pair(
+                opt(sym(",")),
+                expect("expected `)` to close the fields", sym(")")),
+            ),
+        ),
+    ))(input)?;
+    Ok((input, ArmPattern::Variant(name, fields.unwrap_or_default())))
+}
+
+/// A `break`, which may name the labeled block it leaves.
+///
+/// Labels are written like symbols, so a `:name` right after `break` is
+/// always a label, and a symbol value has to be put in parentheses, as in
+/// `break (:done)`. Whether the label belongs to an enclosing block is left
+/// to `check_labels`, once the whole function is parsed.
+fn break_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, kw) = keyword(Keyword::Break)(input)?;
+    let (input, label) = opt(ws(fmap(symbol, |Symbol(name)| Label(name))))(input)?;
+    let (input, value) = opt(expr)(input)?;
+    Ok((input, Expr::Break(label, value.map(Box::new), kw)))
+}
+
+/// A labeled block, like `:init { ... }`, which `break :init value` leaves
+/// early.
+///
+/// These would be mistaken for a symbol before a block in conditions, like
+/// `if x == :a { ... }`, so they're only allowed as a whole statement or the
+/// whole right side of an assignment.
+fn labeled_block(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, Symbol(name)) = ws(symbol)(input)?;
+    let (input, _) = peek(sym("{"))(input)?;
+    let (input, body) = block(input)?;
+    Ok((input, Expr::Labeled(Label(name), body)))
+}
+
+/// Check that every labeled `break` in a parsed node is inside a block with
+/// that label, failing at the first which isn't.
+///
+/// `input` is where the node's source starts. A lambda's body is a function
+/// of its own, so the labels around the lambda aren't visible in it.
+fn check_labels<'a>(input: &'a str, node: Node<'_, 'a>) -> ParseResult<&'a str, ()> {
+    match unknown_label(node, &mut Vec::new()) {
+        Some(label) => {
+            // Point at the `:` before the label's name.
+            let offset = label.as_ptr() as usize - input.as_ptr() as usize - 1;
+            failure(
+                &input[offset..],
+                "no enclosing block has this label; put a symbol in parentheses to break with it, as in `break (:done)`",
+            )
+        }
+        None => Ok((input, ())),
+    }
+}
+
+/// Find the first `break` whose label isn't one of `labels`, or the labels
+/// of the blocks around it inside the node.
+fn unknown_label<'a>(node: Node<'_, 'a>, labels: &mut Vec<&'a str>) -> Option<&'a str> {
+    match node {
+        Node::Expr(Expr::Labeled(Label(name), body)) => {
+            labels.push(name);
+            let unknown = unknown_label(Node::Block(body), labels);
+            labels.pop();
+            unknown
+        }
+        Node::Expr(Expr::Break(Some(Label(name)), ..)) if !labels.contains(name) => Some(name),
+        Node::Expr(Expr::Lambda(lambda)) => {
+            unknown_label(Node::Block(&lambda.body), &mut Vec::new())
+        }
+        node => node
+            .children()
+            .into_iter()
+            .find_map(|child| unknown_label(child, labels)),
+    }
+}
+
+fn continue_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, kw) = keyword(Keyword::Continue)(input)?;
+    Ok((input, Expr::Continue(kw)))
+}
+
+fn return_expr(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (input, kw) = keyword(Keyword::Return)(input)?;
+    let (input, value) = opt(expr)(input)?;
+    Ok((input, Expr::Return(value.map(Box::new), kw)))
+}
+
+/// A call, which must have its argument list directly after the name.
+fn fn_call(input: &str) -> ParseResult<&str, FnCall<'_>> {
+    let (input, name) = ident(input)?;
+    let (input, _) = chr('(')(input)?;
+    let (input, args) = terminated(
+        separated_list(sym(","), fmap(expr, Box::new)),
+        pair(
+            opt(sym(",")),
+            expect("expected `)` to close the argument list", sym(")")),
+        ),
+    )(input)?;
+    Ok((input, FnCall { name, args }))
+}
+
+/// Build a call to an operator.
+fn op_call<'prgrm>(op: &'prgrm str, args: Vec<Expr<'prgrm>>) -> Expr<'prgrm> {
+    Expr::FnCall(FnCall {
+        name: Ident(op),
+        args: args.into_iter().map(Box::new).collect(),
+    })
+}
+
+/// Parse a chain of left-associative binary operators.
+fn left_assoc<'a, F, G>(input: &'a str, operand: F, op: G) -> ParseResult<&'a str, Expr<'a>>
+where
+    F: Fn(&'a str) -> ParseResult<&'a str, Expr<'a>>,
+    G: Fn(&'a str) -> ParseResult<&'a str, &'a str>,
+{
+    let (mut input, mut lhs) = operand(input)?;
+
+    loop {
+        match op(input) {
+            Ok((rest, op)) => {
+                let (rest, rhs) = self::operand(&operand)(rest)?;
+                lhs = op_call(op, vec![lhs, rhs]);
+                input = rest;
+            }
+            Err(Err::Error(_)) => return Ok((input, lhs)),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Run the parser, failing unrecoverably with the given message if it
+/// doesn't match.
+///
+/// These messages are what users see, so they say what the grammar wanted
+/// rather than which combinator gave up.
+fn expect<'a, O, F>(message: &'static str, parser: F) -> impl Fn(&'a str) -> ParseResult<&'a str, O>
+where
+    F: Fn(&'a str) -> ParseResult<&'a str, O>,
+{
+    context(message, cut(parser))
+}
+
+/// The operand of an operator, which must be present.
+fn operand<'a, F>(parser: F) -> impl Fn(&'a str) -> ParseResult<&'a str, Expr<'a>>
+where
+    F: Fn(&'a str) -> ParseResult<&'a str, Expr<'a>>,
+{
+    expect("expected an expression after the operator", parser)
+}
+
+/// Skip any leading whitespace before running the parser.
+fn ws<'a, O, F>(parser: F) -> impl Fn(&'a str) -> ParseResult<&'a str, O>
+where
+    F: Fn(&'a str) -> ParseResult<&'a str, O>,
+{
+    preceded(multispace0, parser)
+}
+
+/// A piece of punctuation, possibly preceded by whitespace.
+fn sym<'a>(text: &'static str) -> impl Fn(&'a str) -> ParseResult<&'a str, &'a str> {
+    ws(tag(text))
+}
+
+/// An operator, which must not run into a longer operator or a comment.
+fn op<'a>(text: &'static str) -> impl Fn(&'a str) -> ParseResult<&'a str, &'a str> {
+    ws(terminated(tag(text), not(one_of("=<>/"))))
+}
+
+/// A keyword, which must not run into a longer word.
+///
+/// The keyword is spelled however the options say.
+fn keyword<'a>(kw: Keyword) -> impl Fn(&'a str) -> ParseResult<&'a str, &'a str> {
+    ws(verify(word, move |word: &str| {
+        options().keywords.lookup(word) == Some(kw)
+    }))
+}
+
+/// A keyword operator, like `and`, producing its usual spelling.
+///
+/// Operators are represented as calls named with their usual spelling,
+/// whatever they're spelled as in the source. The name is always the
+/// keyword's own spelling rather than a slice of the source, which is how
+/// `Ident::is_operator` tells it from a name the same, if the keyword is
+/// disabled.
+fn keyword_op<'a>(kw: Keyword) -> impl Fn(&'a str) -> ParseResult<&'a str, &'a str> {
+    move |input| {
+        let (rest, _) = keyword(kw)(input)?;
+        Ok((rest, kw.spelling()))
+    }
+}
+
+/// A word shaped like an identifier, which may turn out to be a keyword.
+///
+/// Words never end in an operator character, so `x+ 1` is `x` followed by `+`.
+pub(crate) fn word(input: &str) -> ParseResult<&str, &str> {
+    let unicode = options().unicode_idents;
+    let (_, word) = recognize(pair(
+        take_while_m_n(1, 1, |c| is_ident_start(c, unicode)),
+        take_while(|c| is_ident_continue(c, unicode)),
+    ))(input)?;
+    let word = word.trim_end_matches(is_operator_char);
+    Ok((&input[word.len()..], word))
+}
+
+/// Whether a word is reserved, and so can't be an identifier.
+pub(crate) fn is_reserved(word: &str) -> bool {
+    options().keywords.lookup(word).is_some() || word == "true" || word == "false"
+}
+
+/// An identifier.
+///
+/// Identifiers start with `[a-zA-Z]`, and may then include `[0-9]` or any of
+/// `-`, `_`, `+`, `>`, `<`, `*`, though they may not end with an operator
+/// character. This means `a+b` is a single identifier, while `a + b` is an
+/// addition.
+pub(crate) fn ident(input: &str) -> ParseResult<&str, Ident<'_>> {
+    let (rest, word) = word(input)?;
+
+    if is_reserved(word) {
+        return Err(Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Verify,
+        )));
+    }
+
+    Ok((rest, Ident(word)))
+}
+
+/// The name of a type, which is an identifier without operator characters.
+fn ty_name(input: &str) -> ParseResult<&str, &str> {
+    let unicode = options().unicode_idents;
+    let (_, name) = recognize(pair(
+        take_while_m_n(1, 1, |c| is_ident_start(c, unicode)),
+        take_while(is_ty_continue),
+    ))(input)?;
+    let name = name.trim_end_matches('-');
+    Ok((&input[name.len()..], name))
+}
+
+/// A type, which may have generic arguments, like `Map<Str, Array<Int>>`,
+/// or be a tuple of types, like `(Int, Bool)`.
+///
+/// In a type, `<` always starts generic arguments, unless it's `<=`. In an
+/// expression it never does: `a < b` is a comparison, and since `<` may be
+/// part of a name, `f<a>(b)` compares the name `f<a` with `(b)`.
+pub(crate) fn ty(input: &str) -> ParseResult<&str, Ty<'_>> {
+    fmap(
+        alt((recognize(tuple_ty), recognize(pair(ty_name, opt(ty_args))))),
+        Ty,
+    )(input)
+}
+
+/// The element types of a tuple type, like `(Int, Bool)`, which like a
+/// tuple literal needs a trailing comma if it has only one element.
+fn tuple_ty(input: &str) -> ParseResult<&str, Vec<Ty<'_>>> {
+    let (input, _) = chr('(')(input)?;
+    let (rest, (tys, trailing_comma)) = cut(pair(
+        separated_nonempty_list(sym(","), ws(ty)),
+        opt(sym(",")),
+    ))(input)?;
+    let (rest, _) = expect("expected `)` to close the tuple type", sym(")"))(rest)?;
+
+    if tys.len() == 1 && trailing_comma.is_none() {
+        return failure(
+            input,
+            "a tuple type with one element needs a trailing comma, as in `(Int,)`",
+        );
+    }
+
+    Ok((rest, tys))
+}
+
+/// The generic arguments of a type, like `<Str, Int>`.
+fn ty_args(input: &str) -> ParseResult<&str, ()> {
+    let (input, _) = terminated(tag("<"), not(chr('=')))(input)?;
+    let (input, _) = cut(terminated(
+        separated_nonempty_list(sym(","), ws(ty)),
+        opt(sym(",")),
+    ))(input)?;
+    let (input, _) = expect("expected `>` to close the type arguments", sym(">"))(input)?;
+    Ok((input, ()))
+}
+
+/// A comment block, made of consecutive `//` lines.
+fn comment(input: &str) -> ParseResult<&str, Comment<'_>> {
+    let (mut input, first) = comment_line(input)?;
+    let mut lines = vec![first];
+
+    // Following lines only join the block if there's no blank line between,
+    // and a doc comment starts a new block.
+    while let Ok((rest, line)) = preceded(line_gap, preceded(not(doc_line), comment_line))(input) {
+        lines.push(line);
+        input = rest;
+    }
+
+    let comment = if lines.len() == 1 {
+        Comment::SingleLine(first)
+    } else {
+        Comment::MultiLine(lines)
+    };
+
+    Ok((input, comment))
+}
+
+/// A single `//` comment, returning the text after the slashes.
+fn comment_line(input: &str) -> ParseResult<&str, &str> {
+    ws(preceded(tag("//"), not_line_ending))(input)
+}
+
+/// Whitespace containing exactly one line break.
+fn line_gap(input: &str) -> ParseResult<&str, &str> {
+    verify(multispace0, |gap: &str| gap.matches('\n').count() == 1)(input)
+}
+
+/// Skip whitespace and comments before running the parser.
+///
+/// Collection literals are often written one element per line, so comments
+/// may appear anywhere between their elements. They're left out of the HIR,
+/// but are still collected as trivia by `parse_all`.
+fn spaced<'a, O, F>(parser: F) -> impl Fn(&'a str) -> ParseResult<&'a str, O>
+where
+    F: Fn(&'a str) -> ParseResult<&'a str, O>,
+{
+    preceded(many0(alt((comment_line, multispace1))), parser)
+}
+
+/// A literal value.
+fn value_literal(input: &str) -> ParseResult<&str, Literal<'_>> {
+    alt((
+        fmap(bool, Literal::Bool),
+        fmap(float, Literal::Float),
+        fmap(int, Literal::Int),
+        fmap(ustr, Literal::UStr),
+        fmap(bstr, Literal::BStr),
+        fmap(char, Literal::Char),
+        fmap(symbol, Literal::Symbol),
+        fmap(array, Literal::Array),
+        fmap(tuple, Literal::Tuple),
+        fmap(map, Literal::Map),
+    ))(input)
+}
+
+pub(crate) fn bool(input: &str) -> ParseResult<&str, Bool<'_>> {
+    let (rest, word) = word(input)?;
+
+    match word {
+        "true" => Ok((rest, Bool(true, word))),
+        "false" => Ok((rest, Bool(false, word))),
+        _ => Err(Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        ))),
+    }
+}
+
+/// An integer, in decimal, or in hexadecimal or binary with a `0x` or `0b`
+/// prefix, with an optional leading `-`.
+///
+/// The source is kept with its sign and prefix, so it can be printed in the
+/// same base it was written in.
+pub(crate) fn int(input: &str) -> ParseResult<&str, Int<'_>> {
+    let (rest, (minus, digits)) = pair(opt(chr('-')), int_digits)(input)?;
+    let src = &input[..input.len() - rest.len()];
+
+    let value = magnitude(digits).and_then(|magnitude| {
+        let magnitude = i128::from(magnitude);
+        i64::try_from(if minus.is_some() {
+            -magnitude
+        } else {
+            magnitude
+        })
+        .ok()
+    });
+
+    match value {
+        Some(value) => Ok((rest, Int(value, src))),
+        None => failure(input, "integer literal out of range"),
+    }
+}
+
+/// The digits of an integer, with any prefix.
+fn int_digits(input: &str) -> ParseResult<&str, &str> {
+    alt((
+        recognize(preceded(
+            tag("0x"),
+            expect(
+                "expected hexadecimal digits after `0x`",
+                take_while1(|c| is_digit_in_radix(c, 16)),
+            ),
+        )),
+        recognize(preceded(
+            tag("0b"),
+            expect(
+                "expected binary digits after `0b`",
+                take_while1(|c| is_digit_in_radix(c, 2)),
+            ),
+        )),
+        decimal,
+    ))(input)
+}
+
+/// Decimal digits, as in an integer or a float.
+fn decimal(input: &str) -> ParseResult<&str, &str> {
+    take_while1(|c| is_digit_in_radix(c, 10))(input)
+}
+
+/// The value of an integer's digits, if it fits in a `u64`.
+fn magnitude(src: &str) -> Option<u64> {
+    match src.get(..2) {
+        Some("0x") => u64::from_str_radix(&src[2..], 16),
+        Some("0b") => u64::from_str_radix(&src[2..], 2),
+        _ => src.parse(),
+    }
+    .ok()
+}
+
+/// A negative integer, like `-42` or `-9223372036854775808`.
+///
+/// The `-` is read as part of the literal, rather than as negation, only
+/// when it's right before the digits and nothing binds tighter to them than
+/// the `-` does. So `-2 ^ 2` is still `-(2 ^ 2)`, and `-9223372036854775808
+/// ^ 2` is out of range.
+fn negative_int(input: &str) -> ParseResult<&str, Expr<'_>> {
+    let (start, _) = multispace0(input)?;
+    let (rest, _) = pair(chr('-'), int_digits)(start)?;
+
+    let continues = rest.starts_with(|c: char| c.is_alphanumeric() || c == '.')
+        || rest.trim_start().starts_with(['.', '[', '^']);
+
+    if continues {
+        return Err(Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Verify,
+        )));
+    }
+
+    fmap(int, |int| Expr::Literal(Literal::Int(int)))(start)
+}
+
+/// A float, which needs either a fractional part or an exponent.
+///
+/// The exponent may be written with `e` or `E`. A float can't run straight
+/// into more digits or letters, so `1.2.3` and `1e1e1` are errors rather
+/// than a float followed by something else.
+pub(crate) fn float(input: &str) -> ParseResult<&str, Float<'_>> {
+    let exponent = |input| preceded(one_of("eE"), pair(opt(one_of("+-")), decimal))(input);
+    let fraction = |input| preceded(chr('.'), decimal)(input);
+
+    let (rest, src) = recognize(pair(
+        decimal,
+        alt((
+            fmap(pair(fraction, opt(exponent)), |_| ()),
+            fmap(exponent, |_| ()),
+        )),
+    ))(input)?;
+
+    let mut after = rest.chars();
+    let runs_on = match after.next() {
+        Some('.') => after.next().is_some_and(|c| c.is_ascii_digit()),
+        Some(c) => c.is_alphanumeric() || c == '_',
+        None => false,
+    };
+
+    if runs_on {
+        return failure(input, "invalid float literal");
+    }
+
+    match src.parse::<f64>() {
+        Ok(value) if value.is_finite() => Ok((rest, Float(value, src))),
+        Ok(_) => failure(input, "float literal out of range"),
+        Err(_) => failure(input, "invalid float literal"),
+    }
+}
+
+pub(crate) fn ustr(input: &str) -> ParseResult<&str, UStr<'_>> {
+    alt((
+        raw_ustr,
+        fmap(quoted('"', escape), |contents| {
+            UStr(contents, StrStyle::Escaped)
+        }),
+    ))(input)
+}
+
+/// A raw string, like `r"C:\path"` or `r#"say "hi""#`.
+///
+/// Raw strings have no escapes, and end at the first quote followed by as
+/// many `#`s as came before the opening quote.
+fn raw_ustr(input: &str) -> ParseResult<&str, UStr<'_>> {
+    let (rest, hashes) = delimited(chr('r'), take_while(|c| c == '#'), chr('"'))(input)?;
+    let close = format!("\"{}", hashes);
+
+    match rest.find(&close) {
+        Some(end) => Ok((
+            &rest[end + close.len()..],
+            UStr(&rest[..end], StrStyle::Raw(hashes.len())),
+        )),
+        None => failure(rest, "expected a closing quote and matching `#`s"),
+    }
+}
+
+/// A byte string, like `b"GET\r\n"` or `b"\xFF\377"`.
+///
+/// Byte strings are written in ASCII, and other bytes are written with
+/// escapes.
+pub(crate) fn bstr(input: &str) -> ParseResult<&str, BStr<'_>> {
+    let (rest, (_, contents)) = pair(chr('b'), quoted('"', byte_escape))(input)?;
+
+    if !contents.is_ascii() {
+        return failure(input, "byte strings may only contain ASCII");
+    }
+
+    let src = &input[..input.len() - rest.len()];
+    Ok((rest, BStr(contents.as_bytes(), src)))
+}
+
+pub(crate) fn char(input: &str) -> ParseResult<&str, Char<'_>> {
+    let (rest, value) = delimited(
+        chr('\''),
+        expect("expected a character", alt((escape, none_of("\\'")))),
+        expect("expected `'` to close the character", chr('\'')),
+    )(input)?;
+    let src = &input[..input.len() - rest.len()];
+    Ok((rest, Char(value, src)))
+}
+
+/// The contents of a quoted string, with escapes checked by the given
+/// parser but left in place.
+fn quoted<'a, E>(
+    quote: char,
+    escape: fn(&'a str) -> ParseResult<&'a str, E>,
+) -> impl Fn(&'a str) -> ParseResult<&'a str, &'a str> {
+    move |input| {
+        let (input, _) = chr(quote)(input)?;
+        let body = recognize(many0(alt((
+            fmap(escape, |_| ()),
+            fmap(take_while1(move |c| c != quote && c != '\\'), |_| ()),
+        ))));
+        let (input, contents) = body(input)?;
+        let (input, _) = expect("expected a closing quote", chr(quote))(input)?;
+        Ok((input, contents))
+    }
+}
+
+/// An escape sequence, producing the escaped character.
+pub(crate) fn escape(input: &str) -> ParseResult<&str, char> {
+    let (input, _) = chr('\\')(input)?;
+
+    context(
+        "invalid escape sequence",
+        cut(alt((
+            fmap(one_of("nrt0\\'\""), |c| match c {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                '0' => '\0',
+                c => c,
+            }),
+            unicode_escape,
+        ))),
+    )(input)
+}
+
+/// An escape sequence in a byte string, producing the escaped byte.
+///
+/// Besides the escapes for characters, a byte may be given in hex, like
+/// `\xFF`, or in octal with up to three digits, like `\377`. `\0` is the
+/// octal escape for zero. Unicode escapes aren't allowed.
+pub(crate) fn byte_escape(input: &str) -> ParseResult<&str, u8> {
+    let (input, _) = chr('\\')(input)?;
+    let hex = |input| {
+        let (rest, digits) =
+            preceded(chr('x'), take_while_m_n(2, 2, |c| is_digit_in_radix(c, 16)))(input)?;
+        Ok((rest, u32::from_str_radix(digits, 16).unwrap_or(0)))
+    };
+    let octal = |input| {
+        let (rest, digits) = take_while_m_n(1, 3, |c: char| c.is_digit(8))(input)?;
+        Ok((rest, u32::from_str_radix(digits, 8).unwrap_or(0)))
+    };
+
+    let (rest, value) = context(
+        "invalid escape sequence",
+        cut(alt((
+            fmap(one_of("nrt\\'\""), |c| match c {
+                'n' => '\n' as u32,
+                'r' => '\r' as u32,
+                't' => '\t' as u32,
+                c => c as u32,
+            }),
+            hex,
+            octal,
+        ))),
+    )(input)?;
+
+    match u8::try_from(value) {
+        Ok(byte) => Ok((rest, byte)),
+        Err(_) => failure(input, "octal escape out of range"),
+    }
+}
+
+/// A `\u{...}` escape, with the leading backslash already consumed.
+///
+/// The braces hold one to six hex digits, naming any code point other
+/// than a surrogate, so `\u{41}` and `\u{10FFFF}` are both allowed.
+fn unicode_escape(input: &str) -> ParseResult<&str, char> {
+    let (rest, _) = tag("u{")(input)?;
+    let (rest, digits) = take_while(|c| is_digit_in_radix(c, 16))(rest)?;
+
+    if digits.is_empty() || digits.len() > 6 {
+        return failure(input, "expected 1 to 6 hex digits in the unicode escape");
+    }
+
+    let (rest, _) = expect("expected `}` to close the unicode escape", chr('}'))(rest)?;
+
+    match u32::from_str_radix(digits, 16).unwrap_or(u32::MAX) {
+        0xD800..=0xDFFF => failure(input, "unicode escapes can't be surrogates"),
+        value => match std::char::from_u32(value) {
+            Some(c) => Ok((rest, c)),
+            None => failure(
+                input,
+                "unicode escape is past the last code point, `10FFFF`",
+            ),
+        },
+    }
+}
+
+pub(crate) fn symbol(input: &str) -> ParseResult<&str, Symbol<'_>> {
+    fmap(preceded(chr(':'), word), Symbol)(input)
+}
+
+fn array(input: &str) -> ParseResult<&str, Array<'_>> {
+    let close = expect("expected `]` to close the array", spaced(tag("]")));
+    let (input, (items, _)) = delimited(tag("#["), cut(elements), close)(input)?;
+    let (input, ty) = opt(collection_ty("Array"))(input)?;
+    Ok((input, Array(items, ty)))
+}
+
+/// The element type given for a collection, like `: Array<Int>` after
+/// `#[]`.
+fn collection_ty<'a>(kind: &'static str) -> impl Fn(&'a str) -> ParseResult<&'a str, Ty<'a>> {
+    preceded(
+        pair(sym(":"), pair(sym(kind), sym("<"))),
+        terminated(
+            expect("expected an element type", ws(ty)),
+            expect("expected `>` after the element type", sym(">")),
+        ),
+    )
+}
+
+/// A tuple literal, like `#(1, :a)`.
+///
+/// A tuple with one element must have a trailing comma, as in `#(x,)`, so
+/// it can't be mistaken for grouping. `(x)` is always just `x`.
+fn tuple(input: &str) -> ParseResult<&str, Tuple<'_>> {
+    let (input, _) = tag("#(")(input)?;
+    let (rest, (items, trailing_comma)) = cut(elements)(input)?;
+    let (rest, _) = expect("expected `)` to close the tuple", spaced(tag(")")))(rest)?;
+
+    if items.len() == 1 && !trailing_comma {
+        return failure(
+            input,
+            "a tuple with one element needs a trailing comma, as in `#(x,)`",
+        );
+    }
+
+    Ok((rest, Tuple(items)))
+}
+
+/// Comma-separated elements, allowing a trailing comma, and whether there
+/// was one.
+///
+/// Elements are full expressions, so `#[-1, x + 1]` is read the same way
+/// as each of its elements would be on its own.
+fn elements(input: &str) -> ParseResult<&str, (Vec<BExpr<'_>>, bool)> {
+    pair(
+        separated_list(spaced(tag(",")), spaced(fmap(expr, Box::new))),
+        fmap(opt(spaced(tag(","))), |comma| comma.is_some()),
+    )(input)
+}
+
+fn map(input: &str) -> ParseResult<&str, Map<'_>> {
+    let entry = |input| {
+        let (input, key) = spaced(word)(input)?;
+        let (input, _) = expect("expected `:` after the map key", sym(":"))(input)?;
+        let (input, value) = expect("expected an expression", expr)(input)?;
+        Ok((input, (Symbol(key), Box::new(value))))
+    };
+
+    let (input, entries) = delimited(
+        tag("#{"),
+        cut(terminated(
+            separated_list(spaced(tag(",")), entry),
+            opt(spaced(tag(","))),
+        )),
+        expect("expected `}` to close the map", spaced(tag("}"))),
+    )(input)?;
+    let (input, ty) = opt(collection_ty("Map"))(input)?;
+    Ok((input, Map(entries, ty)))
+}
+
+/// Fail unrecoverably with the given context.
+fn failure<'a, O>(input: &'a str, context: &'static str) -> ParseResult<&'a str, O> {
+    Err(Err::Failure(VerboseError {
+        errors: vec![(input, VerboseErrorKind::Context(context))],
+    }))
+}
+
+fn handle_error(input: &str, error: Err<VerboseError<&str>>, out: &mut dyn Write) -> AnyError {
+    match error {
+        // we call `complete` on the parser in `parse_with_errors` so this should never happen.
+        Err::Incomplete(_) => unreachable!(),
+        Err::Error(error) | Err::Failure(error) => {
+            // The error is returned either way, so failing to report it
+            // loses nothing the caller can't still see.
+            let _ = writeln!(out, "{}", error_report(input, &error));
+            anyhow!(Error::ParseFailed(parse_failure(input, &error)))
+        }
+    }
+}
+
+/// Describe a parse failure for library users, without formatting it.
+fn parse_failure(input: &str, error: &VerboseError<&str>) -> ParseFailure {
+    let offset = error_offset(input, error);
+    let (line, _) = line_column(input, offset);
+    let mut context: Vec<_> = error
+        .errors
+        .iter()
+        .filter_map(|(_, kind)| match kind {
+            VerboseErrorKind::Context(context) => Some(*context),
+            _ => None,
+        })
+        .collect();
+
+    if context.is_empty() {
+        context.push(error_context(error));
+    }
+
+    ParseFailure {
+        message: error_context(error).to_string(),
+        span: Span::new(offset, offset),
+        context,
+        snippet: input.lines().nth(line - 1).unwrap_or("").to_string(),
+    }
+}
+
+/// Describe a parse failure as `line:column: message`.
+///
+/// The message is the innermost context the parser was in when it failed,
+/// so it's always one of ours rather than anything from `nom`.
+fn error_message(input: &str, error: &VerboseError<&str>) -> String {
+    let (line, column) = error_position(input, error);
+    format!("{}:{}: {}", line, column, error_context(error))
+}
+
+/// Describe a parse failure, along with the line it happened on.
+fn error_report(input: &str, error: &VerboseError<&str>) -> String {
+    let (line, column) = error_position(input, error);
+    let text = input.lines().nth(line - 1).unwrap_or("");
+
+    format!(
+        "error: {}\n{:>4} | {}\n     | {:>width$}",
+        error_message(input, error),
+        line,
+        text,
+        "^",
+        width = column
+    )
+}
+
+fn error_context(error: &VerboseError<&str>) -> &'static str {
+    error
+        .errors
+        .iter()
+        .find_map(|(_, kind)| match kind {
+            VerboseErrorKind::Context(context) => Some(*context),
+            _ => None,
+        })
+        .unwrap_or("unexpected input")
+}
+
+/// Find the one-based line and column where parsing failed.
+fn error_position(input: &str, error: &VerboseError<&str>) -> (usize, usize) {
+    line_column(input, error_offset(input, error))
+}
+
+/// Find the byte offset where parsing failed.
+fn error_offset(input: &str, error: &VerboseError<&str>) -> usize {
+    let rest = error
+        .errors
+        .first()
+        .map_or("", |(rest, _)| rest.trim_start());
+    input.len() - rest.len()
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Keyword {
+    // use
+    Use,
+    // fn
+    Fn,
+    // return
+    Return,
+    // and
+    And,
+    // or
+    Or,
+    // xor
+    Xor,
+    // not
+    Not,
+    // if
+    If,
+    // else
+    Else,
+    // else if
+    ElseIf,
+    // unless
+    Unless,
+    // else unless
+    ElseUnless,
+    // loop
+    Loop,
+    // while
+    While,
+    // until
+    Until,
+    // for
+    For,
+    // in
+    In,
+    // break
+    Break,
+    // continue
+    Continue,
+    // type
+    Type,
+    // match
+    Match,
+}
+
+impl Keyword {
+    /// Every keyword, paired with its spelling.
+    pub const ALL: &'static [(&'static str, Keyword)] = &[
+        ("use", Keyword::Use),
+        ("fn", Keyword::Fn),
+        ("return", Keyword::Return),
+        ("and", Keyword::And),
+        ("or", Keyword::Or),
+        ("xor", Keyword::Xor),
+        ("not", Keyword::Not),
+        ("if", Keyword::If),
+        ("else", Keyword::Else),
+        ("else if", Keyword::ElseIf),
+        ("unless", Keyword::Unless),
+        ("else unless", Keyword::ElseUnless),
+        ("loop", Keyword::Loop),
+        ("while", Keyword::While),
+        ("until", Keyword::Until),
+        ("for", Keyword::For),
+        ("in", Keyword::In),
+        ("break", Keyword::Break),
+        ("continue", Keyword::Continue),
+        ("type", Keyword::Type),
+        ("match", Keyword::Match),
+    ];
+
+    /// The usual spelling of the keyword.
+    pub fn spelling(self) -> &'static str {
+        Keyword::ALL
+            .iter()
+            .find(|(_, keyword)| *keyword == self)
+            .map_or("", |(spelling, _)| *spelling)
+    }
+
+    /// Find the keyword with the given spelling, if there is one.
+    pub fn lookup(text: &str) -> Option<Keyword> {
+        Keyword::ALL
+            .iter()
+            .find(|(spelling, _)| *spelling == text)
+            .map(|(_, keyword)| *keyword)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Operator {
+    // =
+    Assign,
+    // +
+    Add,
+    // -
+    Sub,
+    // *
+    Mul,
+    // /
+    Div,
+    // %
+    Rem,
+    // ^ (e.g. x ^ 2 == x * x)
+    Exp,
+    // +=
+    AddAssign,
+    // -=
+    SubAssign,
+    // *=
+    MulAssign,
+    // /=
+    DivAssign,
+    // %=
+    RemAssign,
+    // ^=
+    ExpAssign,
+    // == (equality of value)
+    Eq,
+    // @ (get identity, as in `@x`, which identifies where `x` is stored)
+    Id,
+    // >
+    Greater,
+    // <
+    Less,
+    // >=
+    GreaterEq,
+    // <=
+    LessEq,
+    // !=
+    NotEq,
+    // <<
+    Shl,
+    // >>
+    Shr,
+    // & (bitwise, unlike the `and` keyword)
+    BitAnd,
+    // | (bitwise, unlike the `or` keyword)
+    BitOr,
+    // ~ (bitwise, unlike the `xor` keyword, and distinct from `^`)
+    BitXor,
+}
+
+impl Operator {
+    /// Every operator, paired with its spelling, longest spellings first.
+    pub const ALL: &'static [(&'static str, Operator)] = &[
+        ("+=", Operator::AddAssign),
+        ("-=", Operator::SubAssign),
+        ("*=", Operator::MulAssign),
+        ("/=", Operator::DivAssign),
+        ("%=", Operator::RemAssign),
+        ("^=", Operator::ExpAssign),
+        ("==", Operator::Eq),
+        (">=", Operator::GreaterEq),
+        ("<=", Operator::LessEq),
+        ("!=", Operator::NotEq),
+        ("<<", Operator::Shl),
+        (">>", Operator::Shr),
+        ("=", Operator::Assign),
+        ("+", Operator::Add),
+        ("-", Operator::Sub),
+        ("*", Operator::Mul),
+        ("/", Operator::Div),
+        ("%", Operator::Rem),
+        ("^", Operator::Exp),
+        ("@", Operator::Id),
+        (">", Operator::Greater),
+        ("<", Operator::Less),
+        ("&", Operator::BitAnd),
+        ("|", Operator::BitOr),
+        ("~", Operator::BitXor),
+    ];
+
+    /// Find the operator with the given spelling, if there is one.
+    pub fn lookup(text: &str) -> Option<Operator> {
+        Operator::ALL
+            .iter()
+            .find(|(spelling, _)| *spelling == text)
+            .map(|(_, operator)| *operator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Severity;
+    use crate::options::KeywordConfig;
+    use crate::print::pretty_print;
+    use crate::rename::RenameScope;
+
+    /// Parse the body of a `main` function.
+    fn body(src: &str) -> Vec<Stmt<'_>> {
+        let program = parse_with_errors(src).expect("should parse").1;
+
+        match program.0.into_iter().next() {
+            Some(TopStmt::FnDecl(decl)) => decl.body.0,
+            other => panic!("expected a function, found {:?}", other),
+        }
+    }
+
+    /// Render an expression as an s-expression, to check its structure.
+    fn sexpr(expr: &Expr<'_>) -> String {
+        match expr {
+            Expr::FnCall(call) => {
+                let args: Vec<_> = call.args.iter().map(|arg| sexpr(arg)).collect();
+                format!("({} {})", call.name.0, args.join(" "))
+            }
+            Expr::Ident(ident) => ident.0.to_string(),
+            Expr::Literal(Literal::Int(int)) => int.1.to_string(),
+            Expr::Literal(Literal::Float(float)) => float.1.to_string(),
+            Expr::Literal(Literal::Symbol(symbol)) => format!(":{}", symbol.0),
+            Expr::Literal(Literal::Array(Array(items, _))) => format!("#[{}]", sexprs(items)),
+            Expr::Literal(Literal::Tuple(Tuple(items))) => format!("#({})", sexprs(items)),
+            Expr::Index(base, index) => format!("([] {} {})", sexpr(base), sexpr(index)),
+            Expr::Dot(lhs, rhs) => format!("(. {} {})", sexpr(lhs), sexpr(rhs)),
+            Expr::Try(expr) => format!("(? {})", sexpr(expr)),
+            other => format!("{:?}", other),
+        }
+    }
+
+    fn sexprs(exprs: &[BExpr<'_>]) -> String {
+        let exprs: Vec<_> = exprs.iter().map(|expr| sexpr(expr)).collect();
+        exprs.join(", ")
+    }
+
+    fn parse_sexpr(src: &str) -> String {
+        let (rest, expr) = expr(src).expect("should parse");
+        assert_eq!(rest, "", "should consume the whole expression");
+        sexpr(&expr)
+    }
+
+    #[test]
+    fn parses_every_operator_in_the_table() {
+        for (text, level) in BINARY {
+            let src = format!("a {} b {} c", text, text);
+            let (left, right) = (
+                format!("({} ({} a b) c)", text, text),
+                format!("({} a ({} b c))", text, text),
+            );
+
+            match *level {
+                // Comparisons don't chain.
+                COMPARISON => {
+                    let once = format!("a {} b", text);
+                    assert_eq!(parse_sexpr(&once), format!("({} a b)", text));
+                    assert!(expr(&src).map_or(true, |(rest, _)| !rest.is_empty()));
+                }
+                POWER => assert_eq!(parse_sexpr(&src), right),
+                _ => assert_eq!(parse_sexpr(&src), left),
+            }
+        }
+
+        for (text, _) in PREFIX {
+            assert_eq!(
+                parse_sexpr(&format!("{} (a)", text)),
+                format!("({} a)", text)
+            );
+        }
+    }
+
+    #[test]
+    fn the_operator_table_matches_the_lexer() {
+        let is_keyword = |text: &str| Keyword::ALL.iter().any(|(usual, _)| *usual == text);
+        let is_operator = |text: &str| Operator::ALL.iter().any(|(spelling, _)| *spelling == text);
+
+        for (text, _) in BINARY.iter().chain(PREFIX) {
+            assert!(
+                is_keyword(text) != is_operator(text),
+                "`{}` isn't lexed",
+                text
+            );
+        }
+
+        // Everything but assignments has a precedence.
+        for (spelling, operator) in Operator::ALL {
+            let is_assignment = spelling.ends_with('=')
+                && !matches!(
+                    operator,
+                    Operator::Eq | Operator::NotEq | Operator::LessEq | Operator::GreaterEq
+                );
+
+            assert!(
+                is_assignment
+                    || BINARY
+                        .iter()
+                        .chain(PREFIX)
+                        .any(|(text, _)| text == spelling),
+                "`{}` has no precedence",
+                spelling
+            );
+        }
+    }
+
+    #[test]
+    fn parses_identifiers() {
+        assert_eq!(ident("some-name rest"), Ok((" rest", Ident("some-name"))));
+        assert_eq!(ident("x"), Ok(("", Ident("x"))));
+        assert_eq!(ident("str->int(s)"), Ok(("(s)", Ident("str->int"))));
+
+        // Operator characters inside a name belong to it, but trailing ones
+        // are left for the expression after.
+        assert_eq!(ident("a+b"), Ok(("", Ident("a+b"))));
+        assert_eq!(ident("a+ b"), Ok(("+ b", Ident("a"))));
+        assert_eq!(ident("n--"), Ok(("--", Ident("n"))));
+        assert_eq!(parse_sexpr("a+b"), "a+b");
+        assert_eq!(parse_sexpr("a + b"), "(+ a b)");
+    }
+
+    #[test]
+    fn identifiers_cant_start_with_digits_or_be_keywords() {
+        assert!(matches!(ident("1st"), Err(Err::Error(_))));
+        assert!(matches!(ident("-x"), Err(Err::Error(_))));
+
+        for keyword in &["if", "loop", "for", "else", "true"] {
+            assert!(
+                matches!(ident(keyword), Err(Err::Error(_))),
+                "`{}` shouldn't be an identifier",
+                keyword
+            );
+        }
+
+        // Only the whole word is checked, so names may contain keywords.
+        assert_eq!(ident("iffy"), Ok(("", Ident("iffy"))));
+        assert_eq!(ident("for-each"), Ok(("", Ident("for-each"))));
+    }
+
+    #[test]
+    fn applies_postfix_operations_left_to_right() {
+        assert_eq!(parse_sexpr("a.b[0].c()?"), "(? (. ([] (. a b) 0) (c )))");
+        assert_eq!(parse_sexpr("f(x)[1].y?"), "(? (. ([] (f x) 1) y))");
+        assert_eq!(parse_sexpr("a?.b?[0]?"), "(? ([] (? (. (? a) b)) 0))");
+        assert_eq!(
+            parse_sexpr("grid[y][x].at(i)[j]"),
+            "([] (. ([] ([] grid y) x) (at i)) j)"
+        );
+
+        // Postfix operations bind tighter than any prefix or binary operator.
+        assert_eq!(parse_sexpr("-a.b[0]"), "(- ([] (. a b) 0))");
+        assert_eq!(parse_sexpr("a.b ^ c[0]?"), "(^ (. a b) (? ([] c 0)))");
+    }
+
+    #[test]
+    fn parses_control_flow_as_call_arguments() {
+        let args = |src| match expr(src).expect("should parse") {
+            ("", Expr::FnCall(call)) => call.args,
+            other => panic!("expected only a call, found {:?}", other),
+        };
+
+        let if_args = args("f(if c { 1 } else { 2 }, x)");
+        assert!(matches!(*if_args[0], Expr::If(_, _, Some(_))));
+        assert_eq!(sexpr(&if_args[1]), "x");
+
+        let loop_args = args("f(\n    loop { break 1; },\n    unless c { 2 } else { 3 } + 1,\n)");
+        assert!(matches!(*loop_args[0], Expr::Loop(_)));
+        assert!(matches!(&*loop_args[1], Expr::FnCall(call)
+            if call.name.0 == "+" && matches!(*call.args[0], Expr::Unless(..))));
+    }
+
+    #[test]
+    fn expression_statements_consume_every_operator() {
+        let cases = [
+            ("a + b * c - d;", "(- (+ a (* b c)) d)"),
+            ("a * b + c * d == e;", "(== (+ (* a b) (* c d)) e)"),
+            ("x << 1 | y & z;", "(| (<< x 1) (& y z))"),
+            ("-a ^ 2 % b - c;", "(- (% (- (^ a 2)) b) c)"),
+        ];
+
+        for (src, expected) in cases.iter() {
+            let (rest, parsed) = expr(src).expect("should parse");
+            assert_eq!(rest, ";", "only the terminator should remain of {:?}", src);
+            assert_eq!(sexpr(&parsed), *expected);
+
+            let (rest, stmt) = expr_stmt(src).expect("should parse");
+            assert_eq!(rest, "");
+            assert_eq!(sexpr(&stmt), *expected);
+        }
+    }
+
+    fn only_expr(mut stmts: Vec<Stmt<'_>>) -> Expr<'_> {
+        assert_eq!(stmts.len(), 1);
+
+        match stmts.remove(0) {
+            Stmt::Expr(expr) => *expr,
+            other => panic!("expected an expression, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_a_program() {
+        let src = "\
+// Says hello.
+use std:io:{println, print};
+
+fn main() {
+    greeting = \"hello\";
+    println(greeting);
+}
+";
+        let program = parse_with_errors(src).expect("should parse").1;
+        assert_eq!(program.0.len(), 3);
+        assert!(matches!(
+            program.0[0],
+            TopStmt::Comment(Comment::SingleLine(" Says hello."))
+        ));
+        assert!(matches!(
+            program.0[1],
+            TopStmt::Use(PathGlob("std:io:{println, print}"))
+        ));
+        assert!(matches!(&program.0[2], TopStmt::FnDecl(decl) if decl.body.0.len() == 2));
+    }
+
+    fn return_type_of(src: &str) -> ReturnType<'_> {
+        match parse_with_errors(src).expect("should parse").1 .0.remove(0) {
+            TopStmt::FnDecl(decl) => decl.ret_ty,
+            other => panic!("expected a function, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_inferred_return_type() {
+        assert!(matches!(return_type_of("fn f() { }"), ReturnType::Inferred));
+    }
+
+    #[test]
+    fn parses_unit_return_type() {
+        assert!(matches!(
+            return_type_of("fn f() -> () { }"),
+            ReturnType::Unit
+        ));
+    }
+
+    #[test]
+    fn parses_explicit_return_type() {
+        assert!(matches!(
+            return_type_of("fn f() -> Int { 1 }"),
+            ReturnType::Explicit(Ty("Int"))
+        ));
+    }
+
+    #[test]
+    fn parses_tuple_return_type() {
+        match return_type_of("fn f() -> (Int, Array<(Str, Bool)>) { }") {
+            ReturnType::Tuple(tys) => assert_eq!(tys, vec![Ty("Int"), Ty("Array<(Str, Bool)>")]),
+            other => panic!("expected a tuple return type, found {:?}", other),
+        }
+
+        assert!(matches!(
+            &return_type_of("fn f() -> (Int,) { }"),
+            ReturnType::Tuple(tys) if tys.len() == 1
+        ));
+        assert_eq!(
+            error_of("fn f() -> (Int) { }"),
+            "1:12: a tuple type with one element needs a trailing comma, as in `(Int,)`"
+        );
+    }
+
+    #[test]
+    fn destructures_a_multi_return_call() {
+        let src = "fn divmod(a: Int, b: Int) -> (Int, Int) { #(a / b, a % b) }\n\
+                   fn main() { #(q, r) = divmod(7, 2); }";
+        let program = parse_with_errors(src).expect("should parse").1;
+        let mut functions = program.functions();
+        let divmod = functions.next().unwrap();
+        assert!(matches!(&divmod.ret_ty, ReturnType::Tuple(tys) if tys.len() == 2));
+
+        match &functions.next().unwrap().body.0[0] {
+            Stmt::VarAssign(assign) => {
+                assert!(matches!(&assign.pattern, Pattern::Tuple(items, None) if items.len() == 2));
+                assert!(matches!(&*assign.rhs, Expr::FnCall(call) if call.name.0 == "divmod"));
+            }
+            other => panic!("expected an assignment, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_item_metadata() {
+        let src = "\
+// Not documentation.
+/// Opens the file.
+///
+/// Only on unix.
+#[when(unix)]
+#[inline]
+fn open() { }
+";
+        let program = parse_with_errors(src).expect("should parse").1;
+        assert!(matches!(
+            program.0[0],
+            TopStmt::Comment(Comment::SingleLine(" Not documentation."))
+        ));
+
+        let meta = program.functions().next().unwrap().meta();
+        assert_eq!(
+            meta.doc.as_deref(),
+            Some("Opens the file.\n\nOnly on unix.")
+        );
+
+        let names: Vec<_> = meta.attrs.iter().map(|attr| attr.name.0).collect();
+        assert_eq!(names, vec!["when", "inline"]);
+    }
+
+    #[test]
+    fn items_without_metadata_have_none() {
+        let program = parse_with_errors("// Just a comment.\nfn f() { }")
+            .expect("should parse")
+            .1;
+        let meta = program.functions().next().unwrap().meta();
+        assert_eq!(meta.doc, None);
+        assert!(meta.attrs.is_empty());
+    }
+
+    #[test]
+    fn doc_comments_need_an_item() {
+        assert_eq!(
+            error_of("/// Dangling.\nuse std:io;"),
+            "2:1: expected a function or type after its doc comment"
+        );
+    }
+
+    #[test]
+    fn attaches_doc_comments_to_statements() {
+        let stmts = body(
+            "\
+fn main() {
+    /// How many tries.
+    /// At least one.
+    tries = 3;
+    /// Nothing follows this one.
+
+    go(tries);
+}",
+        );
+
+        assert_eq!(stmts.len(), 3);
+        assert_eq!(stmts[0].doc(), Some("How many tries.\nAt least one."));
+        assert!(matches!(stmts[0].undocumented(), Stmt::VarAssign(_)));
+
+        // A doc comment with a blank line after it is just a comment.
+        assert!(matches!(
+            stmts[1],
+            Stmt::Comment(Comment::SingleLine("/ Nothing follows this one."))
+        ));
+        assert_eq!(stmts[2].doc(), None);
+    }
+
+    #[test]
+    fn attaches_trailing_comments_to_statements() {
+        let stmts = body(
+            "\
+fn main() {
+    /// How many tries.
+    tries = 3; // at least one
+    // About the call.
+    go(tries);
+    if done { stop(); } // early
+}",
+        );
+
+        assert_eq!(stmts.len(), 4);
+        assert_eq!(stmts[0].doc(), Some("How many tries."));
+        assert_eq!(stmts[0].trailing_comment(), Some(" at least one"));
+        assert!(matches!(stmts[0].without_comments(), Stmt::VarAssign(_)));
+
+        // The next line's comment isn't merged into the trailing one.
+        assert!(matches!(
+            stmts[1],
+            Stmt::Comment(Comment::SingleLine(" About the call."))
+        ));
+        assert_eq!(stmts[2].trailing_comment(), None);
+        assert_eq!(stmts[3].trailing_comment(), Some(" early"));
+    }
+
+    #[test]
+    fn drops_statement_docs_without_comments() {
+        let src = "fn main() {\n    /// Why.\n    x = 1;\n}";
+        let program = parse_with_options(src, ParseOptions::default().keep_comments(false))
+            .expect("should parse");
+        let body = &program.functions().next().unwrap().body;
+
+        assert!(matches!(&body.0[..], [Stmt::VarAssign(_)]));
+    }
+
+    #[test]
+    fn builds_options() {
+        let options = ParseOptions::default()
+            .max_depth(4)
+            .keep_comments(false)
+            .unicode_idents(true);
+
+        assert_eq!(
+            options,
+            ParseOptions {
+                max_depth: Some(4),
+                keep_comments: false,
+                unicode_idents: true,
+                keywords: KeywordConfig::default(),
+                script_mode: false,
+            }
+        );
+    }
+
+    #[test]
+    fn disabled_keywords_are_identifiers() {
+        let keywords = KeywordConfig::default().disable(Keyword::Unless);
+        let options = ParseOptions::default().keywords(keywords);
+        let src = "fn main() { unless = 1; print(unless); }";
+        let program = parse_with_options(src, options).expect("should parse");
+        let body = &program.functions().next().unwrap().body;
+
+        assert!(matches!(
+            &body.0[0],
+            Stmt::VarAssign(VarAssign {
+                pattern: Pattern::Ident(Ident("unless")),
+                ..
+            })
+        ));
+        assert!(parse(src).is_err());
+    }
+
+    #[test]
+    fn renamed_keywords_use_their_new_spelling() {
+        let keywords = KeywordConfig::default()
+            .rename(Keyword::Unless, "if-not")
+            .and_then(|keywords| keywords.rename(Keyword::And, "also"))
+            .expect("should rename");
+        let options = ParseOptions::default().keywords(keywords);
+        let src = "fn main() { if-not a also b { stop(); } }";
+        let program = parse_with_options(src, options).expect("should parse");
+        let body = &program.functions().next().unwrap().body;
+
+        match &body.0[..] {
+            [Stmt::Expr(expr)] => match &**expr {
+                Expr::Unless(cond, _, None) => assert_eq!(sexpr(cond), "(and a b)"),
+                other => panic!("expected an unless, found {:?}", other),
+            },
+            other => panic!("expected one expression, found {:?}", other),
+        }
+
+        // The old spelling is no longer a keyword.
+        let src = "fn main() { unless = 1; }";
+        assert!(parse_with_options(src, options).is_ok());
+    }
+
+    #[test]
+    fn renamed_operators_print_with_their_usual_spelling() {
+        let keywords = KeywordConfig::default()
+            .rename(Keyword::And, "also")
+            .and_then(|keywords| keywords.rename(Keyword::Not, "nope"))
+            .expect("should rename");
+        let options = ParseOptions::default().keywords(keywords);
+        let src = "fn main() { x = a also b; y = nope c; }";
+        let program = parse_with_options(src, options).expect("should parse");
+        let printed = pretty_print(&program);
+
+        assert_eq!(
+            printed,
+            "fn main() {\n    x = a and b;\n    y = not c;\n}\n"
+        );
+        assert_eq!(parse(&printed).expect("should parse"), program);
+
+        let calls: Vec<_> = program
+            .traverse()
+            .filter_map(|(_, node)| match node {
+                Node::Expr(Expr::FnCall(call)) => Some(call),
+                _ => None,
+            })
+            .collect();
+        assert!(calls.iter().all(|call| call.name.is_operator()));
+        assert!(calls[0].short_circuits());
+    }
+
+    #[test]
+    fn disabled_operators_are_plain_names() {
+        let keywords = KeywordConfig::default()
+            .disable(Keyword::And)
+            .disable(Keyword::Not);
+        let options = ParseOptions::default().keywords(keywords);
+        let src = "fn main() { and = 1; not(and); }";
+        let mut program = parse_with_options(src, options).expect("should parse");
+
+        assert!(program.idents().all(|ident| !ident.is_operator()));
+
+        assert_eq!(program.rename("and", "both", RenameScope::Program), 2);
+        assert_eq!(program.rename("not", "negate", RenameScope::Program), 1);
+    }
+
+    #[test]
+    fn options_can_drop_comments() {
+        let src = "// Top.\nfn main() {\n    // Inner.\n    x = 1;\n}\n";
+        let options = ParseOptions::default().keep_comments(false);
+        let program = parse_with_options(src, options).expect("should parse");
+
+        assert_eq!(program.0.len(), 1);
+        assert_eq!(program.functions().next().unwrap().body.0.len(), 1);
+        assert_eq!(parse(src).expect("should parse").0.len(), 2);
+    }
+
+    #[test]
+    fn options_can_limit_depth() {
+        let options = ParseOptions::default().max_depth(2);
+        assert!(parse_with_options("fn main() { x = 1; }", options).is_ok());
+        assert!(parse_with_options("fn main() { x = (1); }", options).is_err());
+        assert!(parse_with_options("fn main() { if x { y(); } }", options).is_err());
+    }
+
+    #[test]
+    fn options_can_allow_unicode_identifiers() {
+        let src = "fn grüße() { naïve = 1; }";
+        assert!(parse(src).is_err());
+
+        let options = ParseOptions::default().unicode_idents(true);
+        let program = parse_with_options(src, options).expect("should parse");
+        assert_eq!(program.functions().next().unwrap().name.0, "grüße");
+
+        // The options only last for that parse.
+        assert!(parse(src).is_err());
+    }
+
+    #[test]
+    fn script_mode_gathers_top_level_statements_into_main() {
+        let src = "use std:io:println;\n\
+                   greeting = \"hi\";\n\
+                   fn shout(s: String) -> String { s }\n\
+                   println(shout(greeting)); // done\n";
+        let options = ParseOptions::default().script_mode(true);
+        let program = parse_with_options(src, options).expect("should parse");
+
+        assert_eq!(
+            crate::print::pretty_print(&program),
+            "use std:io:println;\n\n\
+             fn main() {\n    greeting = \"hi\";\n    println(shout(greeting)); // done\n}\n\n\
+             fn shout(s: String) -> String {\n    s;\n}\n"
+        );
+
+        // Without script mode, statements need to be in a function.
+        assert_eq!(
+            error_of("greeting = \"hi\";"),
+            "1:1: expected a function, type, use statement, or comment"
+        );
+    }
+
+    #[test]
+    fn script_mode_rejects_an_explicit_main_with_top_level_statements() {
+        let options = ParseOptions::default().script_mode(true);
+        let error = with_options(options, || {
+            error_of("fn main() { go(); }\nx = 1;\nprint(x);")
+        });
+        assert_eq!(
+            error,
+            "2:1: a script with top-level statements can't also declare `main`"
+        );
+
+        // A script of only functions is an ordinary program.
+        let program = parse_with_options("fn main() { go(); }", options).expect("should parse");
+        assert_eq!(program.functions().count(), 1);
+    }
+
+    fn only_type(src: &str) -> TyDecl<'_> {
+        match parse_with_errors(src).expect("should parse").1 .0.remove(0) {
+            TopStmt::TyDecl(decl) => decl,
+            other => panic!("expected a type, found {:?}", other),
+        }
+    }
+
+    fn only_alias(src: &str) -> TyAlias<'_> {
+        match parse_with_errors(src).expect("should parse").1 .0.remove(0) {
+            TopStmt::TyAlias(alias) => alias,
+            other => panic!("expected a type alias, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_type_aliases() {
+        let alias = only_alias("type Count = Int;");
+        assert_eq!((alias.name.0, alias.ty.0), ("Count", "Int"));
+
+        let alias = only_alias("/// Who's invited.\ntype Guests = Map<Array<Str>>;");
+        assert_eq!((alias.name.0, alias.ty.0), ("Guests", "Map<Array<Str>>"));
+        assert_eq!(alias.meta.doc.as_deref(), Some("Who's invited."));
+    }
+
+    #[test]
+    fn rejects_self_referential_aliases() {
+        assert_eq!(
+            error_of("type List = Array<List>;"),
+            "1:13: a type alias can't refer to itself"
+        );
+        assert!(parse_with_errors("type Lists = Array<List>;").is_ok());
+        assert_eq!(
+            error_of("type Count = Int"),
+            "1:17: expected `;` after the type alias"
+        );
+    }
+
+    #[test]
+    fn parses_generic_product_types() {
+        let decl = only_type("type Pair<A, B> { first: A, second: B, count: Int }");
+        assert_eq!(decl.name.0, "Pair");
+
+        let params: Vec<_> = decl.params.iter().map(|param| param.0).collect();
+        assert_eq!(params, vec!["A", "B"]);
+
+        match &decl.variants[..] {
+            [TyVariant {
+                name: None,
+                fields: Fields::Named(fields),
+            }] => {
+                let fields: Vec<_> = fields
+                    .iter()
+                    .map(|field| (field.ident.0, decl.is_param(&field.ty)))
+                    .collect();
+                assert_eq!(
+                    fields,
+                    vec![("first", true), ("second", true), ("count", false)]
+                );
+            }
+            other => panic!("expected a single variant, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_generic_sum_types() {
+        let decl = only_type("type Either<L, R> { Left(L), Right { value: R } }");
+        let names: Vec<_> = decl
+            .variants
+            .iter()
+            .map(|variant| variant.name.as_ref().map(|name| name.0))
+            .collect();
+        assert_eq!(names, vec![Some("Left"), Some("Right")]);
+        assert!(matches!(
+            &decl.variants[0].fields,
+            Fields::Anonymous(tys) if decl.is_param(&tys[0])
+        ));
+    }
+
+    #[test]
+    fn parses_variants_without_fields() {
+        let decl = only_type("type Option<T> { Some(T), None, Pending { since: Int } }");
+        let variants: Vec<_> = decl
+            .variants
+            .iter()
+            .map(|variant| (variant.name.as_ref().unwrap().0, variant.fields.is_empty()))
+            .collect();
+
+        assert_eq!(
+            variants,
+            vec![("Some", false), ("None", true), ("Pending", false)]
+        );
+        assert_eq!(decl.variants[1].fields, Fields::Anonymous(vec![]));
+    }
+
+    #[test]
+    fn parses_types_without_parameters() {
+        let decl = only_type("type Point(Int, Int)");
+        assert!(decl.params.is_empty());
+        assert!(matches!(
+            &decl.variants[..],
+            [TyVariant {
+                name: None,
+                fields: Fields::Anonymous(tys),
+            }] if tys.len() == 2
+        ));
+    }
+
+    #[test]
+    fn rejects_repeated_type_parameters() {
+        assert_eq!(
+            error_of("type Pair<A, A>(A, A)"),
+            "1:14: type parameter names must be unique"
+        );
+        assert_eq!(
+            error_of("type Pair<A, B { }"),
+            "1:16: expected `>` to close the type parameters"
+        );
+    }
+
+    #[test]
+    fn parses_bitwise_operators() {
+        assert_eq!(parse_sexpr("a << 2"), "(<< a 2)");
+        assert_eq!(parse_sexpr("a >> 2"), "(>> a 2)");
+        assert_eq!(parse_sexpr("a & b"), "(& a b)");
+        assert_eq!(parse_sexpr("a | b"), "(| a b)");
+        assert_eq!(parse_sexpr("a ~ b"), "(~ a b)");
+    }
+
+    #[test]
+    fn bitwise_operators_bind_looser_than_arithmetic() {
+        assert_eq!(parse_sexpr("1 + 2 << 3 * 4"), "(<< (+ 1 2) (* 3 4))");
+        assert_eq!(parse_sexpr("a | b ~ c & d"), "(| a (~ b (& c d)))");
+        assert_eq!(parse_sexpr("a & b << 1"), "(& a (<< b 1))");
+        assert_eq!(parse_sexpr("a | b == c"), "(== (| a b) c)");
+    }
+
+    #[test]
+    fn shifts_are_not_comparisons() {
+        assert_eq!(parse_sexpr("a < b"), "(< a b)");
+        assert_eq!(parse_sexpr("a <= b"), "(<= a b)");
+        assert_eq!(parse_sexpr("a >> b"), "(>> a b)");
+    }
+
+    #[test]
+    fn parses_utf8_bytes() {
+        let program = parse_bytes(
+            "fn main() { print(\"héllo\"); }".as_bytes(),
+            ParseOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(program.functions().count(), 1);
+    }
+
+    #[test]
+    fn skips_a_byte_order_mark() {
+        let program = parse_bytes(b"\xEF\xBB\xBFfn main() { }", ParseOptions::default()).unwrap();
+        assert_eq!(program.functions().next().unwrap().name.0, "main");
+    }
+
+    #[test]
+    fn writes_parse_errors_to_the_given_output() {
+        let mut out = Vec::new();
+        let result = parse_with_output("fn main() { x = }", ParseOptions::default(), &mut out);
+
+        assert!(result.is_err());
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "error: 1:17: expected an expression after `=`\n   1 | fn main() { x = }\n     |                 ^\n"
+        );
+    }
+
+    #[test]
+    fn parse_failures_carry_a_structured_error() {
+        let src = "fn main() {\n    x = 1\n    y = 2;\n}";
+        let error = parse(src).unwrap_err();
+
+        let failure = match error.downcast_ref::<Error>() {
+            Some(Error::ParseFailed(failure)) => failure,
+            other => panic!("expected a parse failure, found {:?}", other),
+        };
+
+        assert_eq!(failure.message, "expected `;` after the statement");
+        assert_eq!(failure.span, Span::new(26, 26));
+        assert_eq!(failure.context[0], failure.message);
+        assert_eq!(failure.snippet, "    y = 2;");
+        assert_eq!(
+            format!("{:#}", error),
+            "parse failed: expected `;` after the statement"
+        );
+    }
+
+    #[test]
+    fn reports_the_offset_of_invalid_utf8() {
+        let error =
+            parse_bytes(b"\xEF\xBB\xBFfn m\xFFin() { }", ParseOptions::default()).unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<Error>(),
+            Some(Error::InvalidUtf8(7))
+        ));
+        assert_eq!(error.to_string(), "invalid UTF-8 at byte 7");
+    }
+
+    fn error_of(src: &str) -> String {
+        match parse_with_errors(src) {
+            Err(Err::Error(error)) | Err(Err::Failure(error)) => error_message(src, &error),
+            other => panic!("expected an error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_missing_semicolon() {
+        assert_eq!(
+            error_of("fn main() {\n    x = 1\n    y = 2;\n}"),
+            "3:5: expected `;` after the statement"
+        );
+    }
+
+    #[test]
+    fn suggests_compound_assignment_for_increments() {
+        let increment = "there's no `++` operator; use `+= 1` instead";
+        let decrement = "there's no `--` operator; use `-= 1` instead";
+        assert_eq!(
+            error_of("fn main() { x++; }"),
+            format!("1:14: {}", increment)
+        );
+        assert_eq!(
+            error_of("fn main() { ++x; }"),
+            format!("1:13: {}", increment)
+        );
+        assert_eq!(
+            error_of("fn main() { f(x--); }"),
+            format!("1:16: {}", decrement)
+        );
+        assert_eq!(
+            error_of("fn main() { y = x++ }"),
+            format!("1:18: {}", increment)
+        );
+
+        // What the messages suggest parses.
+        assert!(parse("fn main() { x += 1; x -= 1; }").is_ok());
+    }
+
+    #[test]
+    fn double_negation_is_not_a_decrement() {
+        assert_eq!(parse_sexpr("--x"), "(- (- x))");
+        assert_eq!(parse_sexpr("a -- b"), "(- a (- b))");
+    }
+
+    #[test]
+    fn reports_unclosed_argument_list() {
+        assert_eq!(
+            error_of("fn main() { f(1, 2; }"),
+            "1:19: expected `)` to close the argument list"
+        );
+    }
+
+    #[test]
+    fn reports_unclosed_block() {
+        assert_eq!(
+            error_of("fn main() {\n    x = 1;\n"),
+            "3:1: expected `}` to close the block"
+        );
+    }
+
+    #[test]
+    fn reports_missing_expression() {
+        assert_eq!(
+            error_of("fn main() { x = ; }"),
+            "1:17: expected an expression after `=`"
+        );
+        assert_eq!(
+            error_of("fn main() { x = 1 + ; }"),
+            "1:21: expected an expression after the operator"
+        );
+    }
+
+    #[test]
+    fn reports_unterminated_string() {
+        assert_eq!(
+            error_of("fn main() { x = \"oops; }"),
+            "1:25: expected a closing quote"
+        );
+    }
+
+    #[test]
+    fn reports_bad_top_level_statements() {
+        assert_eq!(
+            error_of("x = 1;"),
+            "1:1: expected a function, type, use statement, or comment"
+        );
+        assert_eq!(
+            error_of("fn main { }"),
+            "1:9: expected `(` to start the parameter list"
+        );
+    }
+
+    #[test]
+    fn parses_multiline_arrays_with_comments() {
+        let src = "#[
+    1,
+    // The important one.
+    2, // trailing
+    3,
+]";
+        match value_literal(src).expect("should parse") {
+            ("", Literal::Array(Array(items, None))) => {
+                assert_eq!(items.len(), 3);
+
+                for (item, value) in items.iter().zip(1..) {
+                    crate::assert_int!(**item, value);
+                }
+            }
+            other => panic!("expected an array, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_array_elements_like_standalone_expressions() {
+        assert_eq!(
+            parse_sexpr("#[-1, -2.5, -9223372036854775808]"),
+            "#[-1, (- 2.5), -9223372036854775808]"
+        );
+        assert_eq!(
+            parse_sexpr("#(x + 1, f(y), items[0],)"),
+            "#((+ x 1), (f y), ([] items 0))"
+        );
+        assert_eq!(parse_sexpr("#[#(-a, b), #[]]"), "#[#((- a), b), #[]]");
+    }
+
+    #[test]
+    fn array_elements_take_suffixes_like_standalone_literals() {
+        // There are no typed literal suffixes, so `1i64` is rejected the
+        // same way inside an array as outside one.
+        assert_eq!(
+            error_of("fn main() { x = #[1i64, 2i64]; }"),
+            "1:20: expected `]` to close the array"
+        );
+        assert!(parse_with_errors("fn main() { x = 1i64; }").is_err());
+    }
+
+    #[test]
+    fn parses_typed_empty_collections() {
+        assert_eq!(
+            value_literal("#[]: Array<Int>").expect("should parse"),
+            ("", Literal::Array(Array(vec![], Some(Ty("Int")))))
+        );
+        assert_eq!(
+            value_literal("#{}: Map<Str>").expect("should parse"),
+            ("", Literal::Map(Map(vec![], Some(Ty("Str")))))
+        );
+
+        // Without a type, the element type is unknown.
+        assert_eq!(
+            value_literal("#[]").expect("should parse"),
+            ("", Literal::Array(Array(vec![], None)))
+        );
+    }
+
+    #[test]
+    fn collection_types_need_closing() {
+        assert_eq!(
+            error_of("fn main() { x = #[]: Array<Int; }"),
+            "1:31: expected `>` after the element type"
+        );
+        assert_eq!(
+            error_of("fn main() { x = #[]: Array<>; }"),
+            "1:28: expected an element type"
+        );
+    }
+
+    #[test]
+    fn parses_multiline_maps_with_comments() {
+        let src = "#{
+    // Where to go.
+    host: \"localhost\",
+    port: 80 // default
+    // The last entry has no trailing comma.
+    , secure: false
+}";
+        match value_literal(src).expect("should parse") {
+            ("", Literal::Map(Map(entries, _))) => {
+                let keys: Vec<_> = entries.iter().map(|(key, _)| key.0).collect();
+                assert_eq!(keys, vec!["host", "port", "secure"]);
+            }
+            other => panic!("expected a map, found {:?}", other),
+        }
+    }
+
+    fn raw_string(src: &str) -> (&str, UStr<'_>) {
+        match value_literal(src).expect("should parse") {
+            (rest, Literal::UStr(ustr)) => (rest, ustr),
+            other => panic!("expected a string, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn raw_strings_skip_escapes() {
+        let (rest, ustr) = raw_string(r#"r"C:\path\no\escapes""#);
+        assert_eq!(rest, "");
+        assert_eq!(ustr.0, r"C:\path\no\escapes");
+        assert_eq!(ustr.1, StrStyle::Raw(0));
+        assert_eq!(ustr.to_source(), r#"r"C:\path\no\escapes""#);
+    }
+
+    #[test]
+    fn hashed_raw_strings_contain_quotes() {
+        let (rest, ustr) = raw_string(r###"r##"say "hi"# twice"##;"###);
+        assert_eq!(rest, ";");
+        assert_eq!(ustr.0, r##"say "hi"# twice"##);
+        assert_eq!(ustr.1, StrStyle::Raw(2));
+        assert_eq!(ustr.to_source(), r###"r##"say "hi"# twice"##"###);
+    }
+
+    #[test]
+    fn raw_strings_need_matching_hashes() {
+        assert!(value_literal(r###"r##"unclosed"#"###).is_err());
+        assert_eq!(
+            error_of(r##"fn main() { x = r#"unclosed"; }"##),
+            "1:20: expected a closing quote and matching `#`s"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_byte_escapes() {
+        assert_eq!(
+            error_of(r#"fn main() { x = b"\400"; }"#),
+            "1:20: octal escape out of range"
+        );
+        assert_eq!(
+            error_of(r#"fn main() { x = b"\xG0"; }"#),
+            "1:20: invalid escape sequence"
+        );
+        assert_eq!(
+            error_of(r#"fn main() { x = b"\u{41}"; }"#),
+            "1:20: invalid escape sequence"
+        );
+    }
+
+    #[test]
+    fn parses_unicode_escapes_of_any_length() {
+        let char_of = |src| match char(src).expect("should parse") {
+            ("", Char(c, _)) => c,
+            other => panic!("expected only a character, found {:?}", other),
+        };
+
+        assert_eq!(char_of(r"'\u{0}'"), '\0');
+        assert_eq!(char_of(r"'\u{41}'"), 'A');
+        assert_eq!(char_of(r"'\u{1F600}'"), '😀');
+        assert_eq!(char_of(r"'\u{10FFFF}'"), '\u{10FFFF}');
+        assert_eq!(char_of(r"'\u{00D7FF}'"), '\u{D7FF}');
+    }
+
+    #[test]
+    fn rejects_invalid_unicode_escapes() {
+        let error = |escape| error_of(&format!(r#"fn main() {{ x = "{}"; }}"#, escape));
+
+        assert_eq!(
+            error(r"\u{}"),
+            "1:19: expected 1 to 6 hex digits in the unicode escape"
+        );
+        assert_eq!(
+            error(r"\u{0000041}"),
+            "1:19: expected 1 to 6 hex digits in the unicode escape"
+        );
+        assert_eq!(
+            error(r"\u{41"),
+            "1:23: expected `}` to close the unicode escape"
+        );
+        assert_eq!(
+            error(r"\u{D800}"),
+            "1:19: unicode escapes can't be surrogates"
+        );
+        assert_eq!(
+            error(r"\u{DFFF}"),
+            "1:19: unicode escapes can't be surrogates"
+        );
+        assert_eq!(
+            error(r"\u{110000}"),
+            "1:19: unicode escape is past the last code point, `10FFFF`"
+        );
+
+        // Characters share the escapes of strings.
+        assert_eq!(
+            error_of(r"fn main() { x = '\u{D800}'; }"),
+            "1:19: unicode escapes can't be surrogates"
+        );
+    }
+
+    #[test]
+    fn escaped_strings_are_not_raw() {
+        let (_, ustr) = raw_string(r#""a\tb""#);
+        assert!(!ustr.is_raw());
+        assert_eq!(ustr.to_source(), r#""a\tb""#);
+    }
+
+    #[test]
+    fn iterates_over_top_level_statements() {
+        let src = "// One.\nuse std:io;\n\nfn main() { }\nfn other() { }\n";
+        let stmts: Vec<_> = parse_iter(src, ParseOptions::default())
+            .collect::<Result<_>>()
+            .expect("should parse");
+
+        assert_eq!(stmts.len(), 4);
+        assert!(matches!(stmts[0], TopStmt::Comment(_)));
+        assert!(matches!(stmts[1], TopStmt::Use(PathGlob("std:io"))));
+        assert!(matches!(&stmts[3], TopStmt::FnDecl(decl) if decl.name.0 == "other"));
+    }
+
+    #[test]
+    fn iteration_continues_after_errors() {
+        let src = "fn first() { }\nfn broken() {\n    x = ;\n}\nfn last() { }\n";
+        let mut stmts = parse_iter(src, ParseOptions::default());
+
+        assert!(matches!(stmts.next(), Some(Ok(TopStmt::FnDecl(_)))));
+
+        let error = stmts.next().unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "3:9: expected an expression after `=`");
+
+        match stmts.next() {
+            Some(Ok(TopStmt::FnDecl(decl))) => assert_eq!(decl.name.0, "last"),
+            other => panic!("expected a function, found {:?}", other),
+        }
+
+        assert!(stmts.next().is_none());
+    }
+
+    #[test]
+    fn iteration_and_diagnostics_use_the_options() {
+        let keywords = KeywordConfig::default()
+            .rename(Keyword::Fn, "func")
+            .expect("should rename");
+        let options = ParseOptions::default().keywords(keywords);
+        let src = "func main() { }\n";
+
+        let stmts: Vec<_> = parse_iter(src, options)
+            .collect::<Result<_>>()
+            .expect("should parse");
+        assert!(matches!(&stmts[..], [TopStmt::FnDecl(decl)] if decl.name.0 == "main"));
+        assert!(parse_iter(src, ParseOptions::default()).all(|stmt| stmt.is_err()));
+
+        assert!(parse_with_diagnostics(src, options).0.is_some());
+        assert!(parse_with_diagnostics(src, ParseOptions::default())
+            .0
+            .is_none());
+    }
+
+    #[test]
+    fn iteration_stops_early() {
+        let src = "fn a() { }\nfn b() { }\nfn c() { ";
+        let names: Vec<_> = parse_iter(src, ParseOptions::default())
+            .map_while(|stmt| match stmt {
+                Ok(TopStmt::FnDecl(decl)) => Some(decl.name.0),
+                _ => None,
+            })
+            .take(2)
+            .collect();
+        assert_eq!(names, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn logical_and_bitwise_operators_are_distinct() {
+        for (src, op, short_circuits) in &[
+            ("a and b", "and", true),
+            ("a or b", "or", true),
+            ("a xor b", "xor", false),
+            ("a & b", "&", false),
+            ("a | b", "|", false),
+        ] {
+            match expr(src).expect("should parse").1 {
+                Expr::FnCall(call) => {
+                    assert_eq!(call.name.0, *op);
+                    assert_eq!(call.short_circuits(), *short_circuits, "{}", src);
+                }
+                other => panic!("expected a call, found {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn exponents_are_right_associative() {
+        assert_eq!(parse_sexpr("2 ^ 3 ^ 2"), "(^ 2 (^ 3 2))");
+        assert_eq!(parse_sexpr("a ^ b ^ c ^ d"), "(^ a (^ b (^ c d)))");
+        assert_eq!(parse_sexpr("(a ^ b) ^ c"), "(^ (^ a b) c)");
+        assert_eq!(parse_sexpr("a ^ -b ^ c"), "(^ a (- (^ b c)))");
+        assert_eq!(parse_sexpr("a * b ^ c ^ d"), "(* a (^ b (^ c d)))");
+    }
+
+    #[test]
+    fn parses_identity() {
+        assert_eq!(parse_sexpr("@x"), "(@ x)");
+        assert_eq!(parse_sexpr("@a == @b"), "(== (@ a) (@ b))");
+
+        let (_, expr) = expr("@point.x").expect("should parse");
+        let args = crate::assert_fn_call!(expr, "@", 1);
+        assert!(matches!(*args[0], Expr::Dot(..)));
+    }
+
+    #[test]
+    fn parses_generic_arguments_in_types() {
+        let src = "fn f(m: Map<Str, Array<Int>>) -> Array<Int> { xs: Array<Int> = #[]; }";
+        let program = parse(src).expect("should parse");
+        let decl = program.functions().next().unwrap();
+
+        assert_eq!(decl.args[0].ty, Ty("Map<Str, Array<Int>>"));
+        assert_eq!(decl.ret_ty.ty(), Some(&Ty("Array<Int>")));
+        assert!(matches!(
+            &decl.body.0[..],
+            [Stmt::VarAssign(VarAssign {
+                ty: Some(Ty("Array<Int>")),
+                ..
+            })]
+        ));
+        assert_eq!(
+            error_of("fn f(m: Map<Str) { }"),
+            "1:16: expected `>` to close the type arguments"
+        );
+    }
+
+    #[test]
+    fn angle_brackets_compare_in_expressions() {
+        assert_eq!(parse_sexpr("a < b"), "(< a b)");
+        assert_eq!(parse_sexpr("a <= b"), "(<= a b)");
+        assert_eq!(parse_sexpr("Array < Int"), "(< Array Int)");
+
+        // Never a generic call. A name can't end in `>`, so that's the
+        // comparison.
+        assert_eq!(parse_sexpr("f<a>(b)"), "(> f<a b)");
+    }
+
+    #[test]
+    fn parses_try_as_a_postfix_operator() {
+        assert_eq!(parse_sexpr("foo()?"), "(? (foo ))");
+        assert_eq!(parse_sexpr("a()?.b()?"), "(? (. (? (a )) (b )))");
+        assert_eq!(parse_sexpr("items[0]?"), "(? ([] items 0))");
+    }
+
+    #[test]
+    fn try_applies_after_field_access() {
+        assert_eq!(parse_sexpr("a.b?"), "(? (. a b))");
+        assert_eq!(parse_sexpr("-a.b? + 1"), "(+ (- (? (. a b))) 1)");
+    }
+
+    #[test]
+    fn parses_array_indexing() {
+        assert_eq!(parse_sexpr("items[0]"), "([] items 0)");
+        assert_eq!(parse_sexpr("items[i + 1] * 2"), "(* ([] items (+ i 1)) 2)");
+        assert_eq!(parse_sexpr("-items[0] ^ 2"), "(- (^ ([] items 0) 2))");
+    }
+
+    #[test]
+    fn parses_map_indexing() {
+        assert_eq!(parse_sexpr("config[:name]"), "([] config :name)");
+        assert_eq!(parse_sexpr("@config[:name]"), "(@ ([] config :name))");
+    }
+
+    #[test]
+    fn chained_indexing_is_left_associative() {
+        assert_eq!(parse_sexpr("grid[0][1]"), "([] ([] grid 0) 1)");
+
+        let (_, expr) = expr("rows[0].cells[1]").expect("should parse");
+        match expr {
+            Expr::Index(base, index) => {
+                assert!(matches!(*base, Expr::Dot(..)));
+                crate::assert_int!(*index, 1);
+            }
+            other => panic!("expected an index, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn indexes_need_closing() {
+        assert_eq!(
+            error_of("fn main() { x = items[0; }"),
+            "1:24: expected `]` to close the index"
+        );
+        assert_eq!(
+            error_of("fn main() { x = items[]; }"),
+            "1:23: expected an index after `[`"
+        );
+    }
+
+    #[test]
+    fn identity_needs_a_place() {
+        assert_eq!(
+            error_of("fn main() { i = @42; }"),
+            "1:17: `@` takes the identity of a name, field, or element"
+        );
+        assert_eq!(
+            error_of("fn main() { i = @; }"),
+            "1:18: expected an expression after the operator"
+        );
+        assert!(parse_with_errors("fn main() { i = x @ y; }").is_err());
+    }
+
+    fn only_place(src: &str) -> String {
+        match body(src).remove(0) {
+            Stmt::VarAssign(VarAssign {
+                pattern: Pattern::Place(place),
+                ..
+            }) => sexpr(&place),
+            other => panic!("expected an assignment to a place, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_assignment_to_places() {
+        assert_eq!(
+            only_place("fn main() { items[i + 1] = 0; }"),
+            "([] items (+ i 1))"
+        );
+        assert_eq!(only_place("fn main() { point.x = 1.5; }"), "(. point x)");
+        assert_eq!(
+            only_place("fn main() { grid[y].cells[x] = :wall; }"),
+            "([] (. ([] grid y) cells) x)"
+        );
+
+        // Comparisons and method calls are still expressions.
+        assert!(matches!(
+            &body("fn main() { point.x == 1; }")[0],
+            Stmt::Expr(_)
+        ));
+        assert!(matches!(
+            &body("fn main() { point.move(1); }")[0],
+            Stmt::Expr(_)
+        ));
+    }
+
+    #[test]
+    fn parses_compound_assignments() {
+        let only_compound = |src| match body(src).remove(0) {
+            Stmt::CompoundAssign(assign) => {
+                let target = match assign.target {
+                    Pattern::Ident(ident) => ident.0.to_string(),
+                    Pattern::Place(place) => sexpr(&place),
+                    other => panic!("expected a name or place, found {:?}", other),
+                };
+                format!("{} {}= {}", target, assign.op, sexpr(&assign.rhs))
+            }
+            other => panic!("expected a compound assignment, found {:?}", other),
+        };
+
+        assert_eq!(only_compound("fn main() { x += 1; }"), "x += 1");
+        assert_eq!(
+            only_compound("fn main() { count-=n * 2 }"),
+            "count -= (* n 2)"
+        );
+        assert_eq!(
+            only_compound("fn main() { grid[y][x] ^= f(x); }"),
+            "([] ([] grid y) x) ^= (f x)"
+        );
+        assert_eq!(only_compound("fn main() { p.x /= 2; }"), "(. p x) /= 2");
+
+        assert_eq!(
+            error_of("fn main() { x += ; }"),
+            "1:18: expected an expression after the operator"
+        );
+        assert_eq!(
+            error_of("fn main() { x += 1 y(); }"),
+            "1:20: expected `;` after the statement"
+        );
+    }
+
+    #[test]
+    fn rejects_assignment_to_values() {
+        assert_eq!(
+            error_of("fn main() { 5 = x; }"),
+            "1:13: can't assign to a literal"
+        );
+        assert_eq!(
+            error_of("fn main() { f() = x; }"),
+            "1:13: can't assign to the result of a call"
+        );
+        assert_eq!(
+            error_of("fn main() { point.at(0) = x; }"),
+            "1:13: can't assign to the result of a call"
+        );
+        assert_eq!(
+            error_of("fn main() { a + b = x; }"),
+            "1:13: can't assign to this expression"
+        );
+    }
+
+    fn only_multi_assign(src: &str) -> MultiAssign<'_> {
+        match body(src).remove(0) {
+            Stmt::MultiAssign(assign) => assign,
+            other => panic!("expected a multiple assignment, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_multiple_assignment() {
+        let assign = only_multi_assign("fn main() { a, #(b, c) = 1, pair; }");
+
+        assert!(matches!(
+            &assign.patterns[..],
+            [Pattern::Ident(Ident("a")), Pattern::Tuple(items, None)] if items.len() == 2
+        ));
+        crate::assert_int!(*assign.values[0], 1);
+        crate::assert_ident!(*assign.values[1], "pair");
+    }
+
+    #[test]
+    fn parses_swaps() {
+        let assign = only_multi_assign("fn main() { a, b = b, a; }");
+
+        assert!(matches!(
+            &assign.patterns[..],
+            [Pattern::Ident(Ident("a")), Pattern::Ident(Ident("b"))]
+        ));
+        crate::assert_ident!(*assign.values[0], "b");
+        crate::assert_ident!(*assign.values[1], "a");
+    }
+
+    #[test]
+    fn multiple_assignment_needs_matching_counts() {
+        assert_eq!(
+            error_of("fn main() { a, b = 1; }"),
+            "1:20: expected as many values as names being assigned"
+        );
+        assert_eq!(
+            error_of("fn main() { a, b = 1, 2, 3; }"),
+            "1:20: expected as many values as names being assigned"
+        );
+        assert_eq!(
+            error_of("fn main() { a, b = ; }"),
+            "1:20: expected an expression after `=`"
+        );
+    }
+
+    fn only_match(src: &str) -> Match<'_> {
+        match body(src).remove(0) {
+            Stmt::Expr(expr) => match *expr {
+                Expr::Match(expr) => expr,
+                other => panic!("expected a match, found {:?}", other),
+            },
+            other => panic!("expected an expression, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_matches() {
+        let expr = only_match(
+            "fn main() { match n { 0 => :zero, 1 => { log(n); :one } 2 => :two, other => :many, } }",
+        );
+
+        crate::assert_ident!(*expr.scrutinee, "n");
+        assert_eq!(expr.arms.len(), 4);
+
+        for (arm, value) in expr.arms.iter().zip(0..3) {
+            assert!(matches!(
+                arm.pattern,
+                ArmPattern::Literal(Literal::Int(Int(n, _))) if n == value
+            ));
+        }
+
+        assert_eq!(expr.arms[1].body.0.len(), 2);
+        assert_eq!(expr.arms[3].pattern, ArmPattern::Bind(Ident("other")));
+        assert!(matches!(
+            &expr.arms[3].body.0[..],
+            [Stmt::Expr(body)] if matches!(**body, Expr::Literal(Literal::Symbol(Symbol("many"))))
+        ));
+    }
+
+    #[test]
+    fn parses_variant_patterns() {
+        let expr = only_match("fn main() { match opt { Some(n) => n, None => 0 } }");
+        let patterns: Vec<_> = expr.arms.iter().map(|arm| &arm.pattern).collect();
+
+        assert_eq!(
+            patterns,
+            vec![
+                &ArmPattern::Variant(Ident("Some"), vec![ArmPattern::Bind(Ident("n"))]),
+                &ArmPattern::Variant(Ident("None"), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn match_arms_need_separating() {
+        assert_eq!(
+            error_of("fn main() { match n { 0 => :zero 1 => :one } }"),
+            "1:34: expected `,` or `}` after the arm"
+        );
+        assert_eq!(
+            error_of("fn main() { match n { 0 :zero } }"),
+            "1:25: expected `=>` after the pattern"
+        );
+        assert_eq!(
+            error_of("fn main() { match n { #[] => 0 } }"),
+            "1:23: expected a pattern"
+        );
+    }
+
+    #[test]
+    fn parses_labeled_blocks() {
+        let stmts = body("fn main() { x = :init { if ready { break :init 1; } 2; }; }");
+        let rhs = match &stmts[0] {
+            Stmt::VarAssign(assign) => &*assign.rhs,
+            other => panic!("expected an assignment, found {:?}", other),
+        };
+        let body = match rhs {
+            Expr::Labeled(Label("init"), body) => body,
+            other => panic!("expected a labeled block, found {:?}", other),
+        };
+        let early = match &body.0[0] {
+            Stmt::Expr(expr) => match &**expr {
+                Expr::If(_, body, _) => &body.0[0],
+                other => panic!("expected an if, found {:?}", other),
+            },
+            other => panic!("expected an expression, found {:?}", other),
+        };
+
+        assert!(matches!(
+            early,
+            Stmt::Expr(expr) if matches!(
+                &**expr,
+                Expr::Break(Some(Label("init")), Some(value), _)
+                    if matches!(**value, Expr::Literal(Literal::Int(Int(1, _))))
+            )
+        ));
+    }
+
+    #[test]
+    fn breaks_only_take_labels_of_enclosing_blocks() {
+        let program = parse("fn main() { :a { loop { break :a; } } loop { break (:a); } }")
+            .expect("should parse");
+        let breaks: Vec<_> = program
+            .traverse()
+            .filter_map(|(_, node)| match node {
+                crate::Node::Expr(Expr::Break(label, value, _)) => {
+                    Some((label.as_ref().map(|label| label.0), value.is_some()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(breaks, vec![(Some("a"), false), (None, true)]);
+    }
+
+    #[test]
+    fn rejects_breaks_to_labels_outside_the_function() {
+        let message = "no enclosing block has this label; put a symbol in parentheses to break with it, as in `break (:done)`";
+        assert_eq!(
+            error_of("fn main() { loop { break :a; } }"),
+            format!("1:26: {}", message)
+        );
+        assert_eq!(
+            error_of("fn main() { :a { f = fn() { break :a; }; } }"),
+            format!("1:35: {}", message)
+        );
+        assert!(parse("fn main() { :a { f = fn() { :a { break :a; } }; } }").is_ok());
+    }
+
+    fn only_pattern(src: &str) -> Pattern<'_> {
+        match body(src).remove(0) {
+            Stmt::VarAssign(assign) => assign.pattern,
+            other => panic!("expected an assignment, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_tuple_destructures() {
+        match only_pattern("fn main() { #(a, b) = pair; }") {
+            Pattern::Tuple(items, None) => assert!(matches!(
+                &items[..],
+                [Pattern::Ident(Ident("a")), Pattern::Ident(Ident("b"))]
+            )),
+            other => panic!("expected a tuple pattern, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_array_destructures_with_rest() {
+        match only_pattern("fn main() { #[first, ..rest] = arr; }") {
+            Pattern::Array(items, Some(Rest(Some(Ident("rest"))))) => {
+                assert!(matches!(&items[..], [Pattern::Ident(Ident("first"))]));
+            }
+            other => panic!("expected an array pattern, found {:?}", other),
+        }
+
+        match only_pattern("fn main() { #[#(a, b), ..] = arr; }") {
+            Pattern::Array(items, Some(Rest(None))) => {
+                assert!(matches!(&items[..], [Pattern::Tuple(_, None)]));
+            }
+            other => panic!("expected an array pattern, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_misplaced_rests() {
+        assert_eq!(
+            error_of("fn main() { #[a, ..b, ..c] = arr; }"),
+            "1:25: a pattern may only have one `..`"
+        );
+        assert_eq!(
+            error_of("fn main() { #[..b, c] = arr; }"),
+            "1:20: `..` must be the last element of a pattern"
+        );
+    }
+
+    #[test]
+    fn collection_statements_are_not_patterns() {
+        assert!(matches!(
+            only_expr(body("fn main() { #(a, 1); }")),
+            Expr::Literal(Literal::Tuple(_))
+        ));
+    }
+
+    #[test]
+    fn parses_one_element_tuples() {
+        match only_expr(body("fn main() { #(x,); }")) {
+            Expr::Literal(Literal::Tuple(Tuple(items))) => {
+                assert!(matches!(&items[..], [item] if **item == Expr::Ident(Ident("x"))))
+            }
+            other => panic!("expected a tuple, found {:?}", other),
+        }
+
+        match only_pattern("fn main() { #(x,) = single; }") {
+            Pattern::Tuple(items, None) => {
+                assert!(matches!(&items[..], [Pattern::Ident(Ident("x"))]))
+            }
+            other => panic!("expected a tuple pattern, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn one_element_tuples_need_a_trailing_comma() {
+        let message = "a tuple with one element needs a trailing comma, as in `#(x,)`";
+        assert_eq!(
+            error_of("fn main() { y = #(x); }"),
+            format!("1:19: {}", message)
+        );
+        assert_eq!(
+            error_of("fn main() { #(x) = single; }"),
+            format!("1:15: {}", message)
+        );
+    }
+
+    #[test]
+    fn trailing_commas_may_be_followed_by_comments() {
+        assert!(matches!(
+            only_expr(body("fn main() { #(x, // just one\n); }")),
+            Expr::Literal(Literal::Tuple(Tuple(items))) if items.len() == 1
+        ));
+        assert_eq!(
+            error_of("fn main() { y = #(x // just one\n); }"),
+            "1:19: a tuple with one element needs a trailing comma, as in `#(x,)`"
+        );
+    }
+
+    #[test]
+    fn parentheses_only_group() {
+        crate::assert_ident!(only_expr(body("fn main() { (x); }")), "x");
+        assert!(matches!(
+            only_expr(body("fn main() { #(); }")),
+            Expr::Literal(Literal::Tuple(Tuple(items))) if items.is_empty()
+        ));
+    }
+
+    #[test]
+    fn parses_lambdas_assigned_to_variables() {
+        let stmts = body("fn main() { inc = fn(x: Int) -> Int { x + 1 }; }");
+
+        match &stmts[..] {
+            [Stmt::VarAssign(VarAssign { rhs, .. })] => match &**rhs {
+                Expr::Lambda(lambda) => {
+                    assert!(matches!(
+                        &lambda.args[..],
+                        [TyIdent {
+                            ident: Ident("x"),
+                            ty: Ty("Int")
+                        }]
+                    ));
+                    assert!(matches!(lambda.ret_ty, ReturnType::Explicit(Ty("Int"))));
+                    assert_eq!(lambda.body.0.len(), 1);
+                }
+                other => panic!("expected a lambda, found {:?}", other),
+            },
+            other => panic!("expected an assignment, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_lambdas_as_arguments() {
+        let expr = only_expr(body(
+            "fn main() { map(items, fn(item: Int) { item * 2 }); }",
+        ));
+        let args = crate::assert_fn_call!(expr, "map", 2);
+        crate::assert_ident!(args[0], "items");
+
+        match &*args[1] {
+            Expr::Lambda(lambda) => {
+                assert_eq!(lambda.args.len(), 1);
+                assert!(matches!(lambda.ret_ty, ReturnType::Inferred));
+            }
+            other => panic!("expected a lambda, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn lambdas_need_a_body() {
+        assert_eq!(
+            error_of("fn main() { f = fn(x: Int); }"),
+            "1:27: expected `{` to start the function body"
+        );
+    }
+
+    #[test]
+    fn parses_hex_and_binary_integers() {
+        let stmts = body("fn main() { x = #[0xFF, 0b1010, 0x7fffffffffffffff]; }");
+
+        match &stmts[..] {
+            [Stmt::VarAssign(VarAssign { rhs, .. })] => match &**rhs {
+                Expr::Literal(Literal::Array(Array(items, _))) => {
+                    let ints: Vec<_> = items
+                        .iter()
+                        .map(|item| match &**item {
+                            Expr::Literal(Literal::Int(Int(value, src))) => (*value, *src),
+                            other => panic!("expected an integer, found {:?}", other),
+                        })
+                        .collect();
+                    assert_eq!(
+                        ints,
+                        vec![
+                            (255, "0xFF"),
+                            (10, "0b1010"),
+                            (i64::MAX, "0x7fffffffffffffff")
+                        ]
+                    );
+                }
+                other => panic!("expected an array, found {:?}", other),
+            },
+            other => panic!("expected an assignment, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_hex_and_binary_integers() {
+        assert_eq!(
+            error_of("fn main() { x = 0x; }"),
+            "1:19: expected hexadecimal digits after `0x`"
+        );
+        assert_eq!(
+            error_of("fn main() { x = 0b2; }"),
+            "1:19: expected binary digits after `0b`"
+        );
+        assert_eq!(
+            error_of("fn main() { x = 0x10000000000000000; }"),
+            "1:17: integer literal out of range"
+        );
+    }
+
+    #[test]
+    fn parses_decimal_integers() {
+        let value = |src| {
+            let (rest, Int(value, text)) = int(src).expect("should parse");
+            (value, text, rest)
+        };
+
+        assert_eq!(value("0"), (0, "0", ""));
+        assert_eq!(value("645;"), (645, "645", ";"));
+        assert_eq!(value("12abc"), (12, "12", "abc"));
+        assert_eq!(
+            value("9223372036854775807"),
+            (i64::MAX, "9223372036854775807", "")
+        );
+
+        assert_eq!(value("-42"), (-42, "-42", ""));
+        assert_eq!(value("-0x10"), (-16, "-0x10", ""));
+        assert!(matches!(int("- 42"), Err(Err::Error(_))));
+        assert!(matches!(int("-"), Err(Err::Error(_))));
+
+        // Overflowing is a failure, rather than a reason to try something
+        // else, even past the range of a `u64`.
+        assert!(matches!(int("99999999999999999999"), Err(Err::Failure(_))));
+        assert_eq!(
+            error_of("fn main() { x = 99999999999999999999; }"),
+            "1:17: integer literal out of range"
+        );
+    }
+
+    #[test]
+    fn parses_the_smallest_integer() {
+        let (_, min) = expr("-9223372036854775808").expect("should parse");
+        crate::assert_int!(min, i64::MIN);
+
+        let (_, sub) = expr("x - -0x8000000000000000").expect("should parse");
+        let args = crate::assert_fn_call!(sub, "-", 2);
+        crate::assert_int!(*args[1], i64::MIN);
+
+        // The `-` is only part of the literal when it's right before the
+        // digits, and nothing binds tighter to them.
+        assert_eq!(parse_sexpr("-42"), "-42");
+        assert_eq!(parse_sexpr("- 42"), "(- 42)");
+        assert_eq!(parse_sexpr("-2 ^ 2"), "(- (^ 2 2))");
+        assert_eq!(parse_sexpr("-x"), "(- x)");
+    }
+
+    #[test]
+    fn rejects_integers_past_the_limits() {
+        assert_eq!(
+            error_of("fn main() { x = -9223372036854775809; }"),
+            "1:17: integer literal out of range"
+        );
+        assert_eq!(
+            error_of("fn main() { x = 9223372036854775808; }"),
+            "1:17: integer literal out of range"
+        );
+        assert_eq!(
+            error_of("fn main() { x = -9223372036854775808 ^ 2; }"),
+            "1:18: integer literal out of range"
+        );
+    }
+
+    #[test]
+    fn parses_float_notation_edge_cases() {
+        let value = |src| float(src).expect("should parse").1 .0;
+
+        assert_eq!(value("1E10"), 1e10);
+        assert_eq!(value("2.5E-3"), 0.0025);
+        assert_eq!(value("1e007"), 1e7);
+        assert_eq!(value("0.0").to_bits(), 0.0f64.to_bits());
+        assert_eq!(float("1.5.abs").expect("should parse").0, ".abs");
+    }
+
+    #[test]
+    fn rejects_floats_that_run_on() {
+        assert_eq!(
+            error_of("fn main() { x = 1e1e1; }"),
+            "1:17: invalid float literal"
+        );
+        assert_eq!(
+            error_of("fn main() { x = 1.2.3; }"),
+            "1:17: invalid float literal"
+        );
+    }
+
+    #[test]
+    fn rejects_infinite_float_literals() {
+        assert_eq!(
+            error_of("fn main() { x = 1e999; }"),
+            "1:17: float literal out of range"
+        );
+    }
+
+    #[test]
+    fn returns_warnings_for_clean_parses() {
+        let src = "fn main() {\n    x = 007;\n}\n";
+        let (program, diagnostics) = parse_with_diagnostics(src, ParseOptions::default());
+
+        assert!(program.is_some());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert_eq!(diagnostics[0].span, Span::new(20, 23));
+    }
+
+    #[test]
+    fn returns_errors_for_failed_parses() {
+        let src = "fn main() {\n    x = ;\n}\n";
+        let (program, diagnostics) = parse_with_diagnostics(src, ParseOptions::default());
+
+        assert!(program.is_none());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].span, Span::new(20, 20));
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "error[E0001]: expected an expression after `=`"
+        );
+    }
+
+    #[test]
+    fn parses_while_else() {
+        let src = "fn main() { while x < 10 { x = x + 1; } else { done(); } }";
+
+        match only_expr(body(src)) {
+            Expr::While(_, body, Some(els)) => {
+                assert_eq!(body.0.len(), 1);
+                assert!(
+                    matches!(&els.0[..], [Stmt::Expr(call)] if matches!(**call, Expr::FnCall(_)))
+                );
+            }
+            other => panic!("expected a while-else, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_until_else() {
+        let src = "fn main() { until done { step(); } else { skip(); } }";
+        assert!(matches!(only_expr(body(src)), Expr::Until(_, _, Some(_))));
+    }
+
+    #[test]
+    fn parses_for_else() {
+        let src = "fn main() { for item in items { use-item(item); } else { empty(); } }";
+
+        match only_expr(body(src)) {
+            Expr::For(item, _, _, Some(els)) => {
+                assert!(matches!(*item, Expr::Ident(Ident("item"))));
+                assert_eq!(els.0.len(), 1);
+            }
+            other => panic!("expected a for-else, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn loops_without_else() {
+        let src = "fn main() { while x { x = false; } }";
+        assert!(matches!(only_expr(body(src)), Expr::While(_, _, None)));
+    }
+
+    #[test]
+    fn loop_else_needs_a_block() {
+        assert!(parse_with_errors("fn main() { while x { } else if y { } }").is_err());
+    }
 }
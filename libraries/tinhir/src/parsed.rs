@@ -0,0 +1,177 @@
+//! A parsed program bundled with the source it came from.
+
+use crate::diagnostic::Diagnostic;
+use crate::hir::Program;
+use crate::options::ParseOptions;
+use crate::parse::parse_with_options;
+use crate::span::{ColumnMode, LineMap};
+use anyhow::Result;
+
+/// A program together with its source, and a map of the source's lines.
+///
+/// Diagnostics refer to the source by offset, so passes which report them
+/// need all three. Keeping them together means they can't get out of step.
+#[derive(Debug)]
+pub struct Parsed<'a> {
+    source: &'a str,
+    program: Program<'a>,
+    line_map: LineMap,
+    column_mode: ColumnMode,
+}
+
+/// Parse the input with the given options, bundling the program with its
+/// source, or report why it didn't parse.
+pub fn parse_bundled(input: &str, options: ParseOptions) -> Result<Parsed<'_>> {
+    Ok(parse_with_options(input, options)?.with_source(input))
+}
+
+impl<'a> Program<'a> {
+    /// Bundle the program with the source it was parsed from.
+    pub fn with_source(self, source: &'a str) -> Parsed<'a> {
+        Parsed {
+            source,
+            program: self,
+            line_map: LineMap::new(source),
+            column_mode: ColumnMode::default(),
+        }
+    }
+}
+
+impl<'a> Parsed<'a> {
+    /// The source the program was parsed from.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The parsed program.
+    pub fn program(&self) -> &Program<'a> {
+        &self.program
+    }
+
+    /// The map of the source's lines.
+    pub fn line_map(&self) -> &LineMap {
+        &self.line_map
+    }
+
+    /// Count columns with the given mode, for `line_column` and `render`.
+    ///
+    /// This is `ColumnMode::Chars` by default.
+    pub fn with_column_mode(mut self, mode: ColumnMode) -> Parsed<'a> {
+        self.column_mode = mode;
+        self
+    }
+
+    /// Take the program, leaving the rest of the bundle.
+    pub fn into_program(self) -> Program<'a> {
+        self.program
+    }
+
+    /// Get the one-based line and column of an offset in the source.
+    ///
+    /// Columns are counted by the column mode, and never in bytes.
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        self.line_map
+            .line_column(self.source, offset, self.column_mode)
+    }
+
+    /// Render a diagnostic with the line it's on, marking the part of the
+    /// line it covers.
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let span = diagnostic.span;
+        let (line, column) = self.line_column(span.start);
+        let start = self.line_map.line_start(line).unwrap_or(0);
+        let text = self.source[start..].lines().next().unwrap_or("");
+
+        // Mark at least one character, and no further than the end of the
+        // line, which comes before the `\r` of a `\r\n`.
+        let line_end = start + text.len();
+        let from = span.start.min(line_end);
+        let to = span.end.max(from).min(line_end);
+        let width = self.column_mode.measure(&self.source[from..to]).max(1);
+
+        format!(
+            "{}: {}:{}: {}\n{:>4} | {}\n     | {}{}",
+            diagnostic.heading(),
+            line,
+            column,
+            diagnostic.message,
+            line,
+            text,
+            " ".repeat(column - 1),
+            "^".repeat(width)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::lint;
+    use crate::span::Span;
+
+    #[test]
+    fn bundles_the_program_with_its_source() {
+        let src = "fn main() {\n    x = 1;\n}\n";
+        let parsed = parse_bundled(src, ParseOptions::default()).expect("should parse");
+
+        assert_eq!(parsed.source(), src);
+        assert_eq!(parsed.program().functions().count(), 1);
+        assert_eq!(parsed.line_map().lines(), 4);
+        assert_eq!(parsed.line_column(16), (2, 5));
+        assert_eq!(parsed.into_program().functions().count(), 1);
+    }
+
+    #[test]
+    fn counts_columns_by_display_width() {
+        let src = "fn main() {\n    s = \"漢字\" + a+b;\n    t = \"e\u{301}\" + c+d;\n}\n";
+        let parsed = parse_bundled(src, ParseOptions::default()).expect("should parse");
+        let offset = src.find("a+b").unwrap();
+        let combined = src.find("c+d").unwrap();
+
+        assert_eq!(parsed.line_column(offset), (2, 16));
+        assert_eq!(parsed.line_column(combined), (3, 16));
+
+        let parsed = parsed.with_column_mode(ColumnMode::Width);
+        assert_eq!(parsed.line_column(offset), (2, 18));
+        assert_eq!(parsed.line_column(combined), (3, 15));
+
+        let diagnostics = lint(parsed.program(), parsed.source());
+        assert_eq!(
+            parsed.render(&diagnostics[0]),
+            "\
+warning[W0001]: 2:18: `a+b` is a single identifier; put spaces around operators to use them
+   2 |     s = \"漢字\" + a+b;
+     |                  ^^^"
+        );
+    }
+
+    #[test]
+    fn renders_diagnostics_with_a_snippet() {
+        let src = "fn main() {\n    s = \"é\" + a+b;\n}\n";
+        let parsed = parse_bundled(src, ParseOptions::default()).expect("should parse");
+        let diagnostics = lint(parsed.program(), parsed.source());
+
+        assert_eq!(
+            parsed.render(&diagnostics[0]),
+            "\
+warning[W0001]: 2:15: `a+b` is a single identifier; put spaces around operators to use them
+   2 |     s = \"é\" + a+b;
+     |               ^^^"
+        );
+    }
+
+    #[test]
+    fn renders_diagnostics_at_the_end_of_crlf_lines() {
+        let src = "fn main() {}\r\nfn other() {}\r\n";
+        let parsed = parse_bundled(src, ParseOptions::default()).expect("should parse");
+        let diagnostic = Diagnostic::error(Span::new(12, 12), "expected a newline");
+
+        assert_eq!(
+            parsed.render(&diagnostic),
+            "\
+error: 1:13: expected a newline
+   1 | fn main() {}
+     |             ^"
+        );
+    }
+}
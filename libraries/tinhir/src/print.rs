@@ -0,0 +1,1159 @@
+//! Printing the HIR back out as source code.
+//!
+//! The output is in a single canonical style, with four-space indentation
+//! and one statement per line. Operations are only wrapped in parentheses
+//! where precedence or associativity needs them, so the output still
+//! parses back into the same tree.
+
+use crate::hir::*;
+use crate::parse::{BINARY, COMPARISON, NEGATION, POSTFIX, POWER, PREFIX};
+use crate::source_map::{SourceMap, SourceMapBuilder};
+use crate::span::Span;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Print a program as source code.
+pub fn pretty_print(program: &Program<'_>) -> String {
+    let mut printer = Printer::new(None);
+    printer.program(program);
+    printer.out
+}
+
+/// Print a program as source code, mapping the output back to the source
+/// the program was parsed from.
+///
+/// Names, literals, types, keywords, and comments are mapped.
+pub fn pretty_print_mapped(program: &Program<'_>, source: &str) -> (String, SourceMap) {
+    let mut printer = Printer::new(Some(source));
+    printer.program(program);
+    (printer.out, printer.map.build())
+}
+
+/// Print a program with its literals written by value, so programs which
+/// differ only in how their literals are spelled print the same.
+pub(crate) fn print_by_value(program: &Program<'_>) -> String {
+    let mut printer = Printer::new(None);
+    printer.by_value = true;
+    printer.program(program);
+    printer.out
+}
+
+/// Literals are displayed as the source code which produces them, as the
+/// pretty-printer writes them: integers in their original base, strings
+/// with their escapes, and collections with the values inside them.
+impl<'prgrm> Display for Literal<'prgrm> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let mut printer = Printer::new(None);
+        printer.literal(self);
+        f.write_str(&printer.out)
+    }
+}
+
+fn precedence(table: &[(&str, u8)], op: &str) -> Option<u8> {
+    table
+        .iter()
+        .find(|(name, _)| *name == op)
+        .map(|(_, level)| *level)
+}
+
+struct Printer<'src> {
+    out: String,
+    indent: usize,
+    source: Option<&'src str>,
+    map: SourceMapBuilder,
+    /// Write literals by their value rather than their spelling, so `0x10`
+    /// is written as `16`. The output is for comparing, not for parsing.
+    by_value: bool,
+}
+
+impl<'src> Printer<'src> {
+    fn new(source: Option<&'src str>) -> Printer<'src> {
+        Printer {
+            out: String::new(),
+            indent: 0,
+            source,
+            map: SourceMapBuilder::default(),
+            by_value: false,
+        }
+    }
+
+    fn program(&mut self, program: &Program<'_>) {
+        let mut prev: Option<&TopStmt<'_>> = None;
+
+        for stmt in &program.0 {
+            // Items are separated by blank lines, except that comments stay
+            // attached to what follows them, and uses are grouped together.
+            let gap = !matches!(
+                (prev, stmt),
+                (None, _)
+                    | (Some(TopStmt::Comment(_)), _)
+                    | (Some(TopStmt::Use(_)), TopStmt::Use(_))
+            );
+
+            if gap {
+                self.out.push('\n');
+            }
+
+            self.top_stmt(stmt);
+            prev = Some(stmt);
+        }
+    }
+
+    fn top_stmt(&mut self, stmt: &TopStmt<'_>) {
+        match stmt {
+            TopStmt::Comment(comment) => self.comment(comment),
+            TopStmt::Use(path) => {
+                self.text("use ");
+                self.leaf(path.0, path.0);
+                self.text(";\n");
+            }
+            TopStmt::FnDecl(decl) => self.fn_decl(decl),
+            TopStmt::TyDecl(decl) => self.ty_decl(decl),
+            TopStmt::TyAlias(alias) => {
+                self.meta(&alias.meta);
+                self.text("type ");
+                self.ident(&alias.name);
+                self.text(" = ");
+                self.ty(&alias.ty);
+                self.text(";\n");
+            }
+        }
+    }
+
+    fn fn_decl(&mut self, decl: &FnDecl<'_>) {
+        self.meta(&decl.meta);
+        self.text("fn ");
+        self.ident(&decl.name);
+        self.signature(&decl.args, &decl.ret_ty);
+        self.text(" ");
+        self.block(&decl.body);
+        self.text("\n");
+    }
+
+    /// Print a function's parameters and return type.
+    fn signature(&mut self, args: &[TyIdent<'_>], ret_ty: &ReturnType<'_>) {
+        self.text("(");
+
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
+                self.text(", ");
+            }
+
+            self.ty_ident(arg);
+        }
+
+        self.text(")");
+
+        match ret_ty {
+            ReturnType::Inferred => {}
+            ReturnType::Unit => self.text(" -> ()"),
+            ReturnType::Explicit(ty) => {
+                self.text(" -> ");
+                self.ty(ty);
+            }
+            ReturnType::Tuple(tys) => {
+                self.text(" -> (");
+
+                for (i, ty) in tys.iter().enumerate() {
+                    if i > 0 {
+                        self.text(", ");
+                    }
+
+                    self.ty(ty);
+                }
+
+                if tys.len() == 1 {
+                    self.text(",");
+                }
+
+                self.text(")");
+            }
+        }
+    }
+
+    fn meta(&mut self, meta: &ItemMeta<'_>) {
+        if let Some(doc) = &meta.doc {
+            self.doc(doc);
+        }
+
+        for attr in &meta.attrs {
+            self.text("#[");
+            self.ident(&attr.name);
+
+            if !attr.args.is_empty() {
+                self.text("(");
+                self.exprs(&attr.args);
+                self.text(")");
+            }
+
+            self.text("]\n");
+        }
+    }
+
+    fn ty_decl(&mut self, decl: &TyDecl<'_>) {
+        self.meta(&decl.meta);
+        self.text("type ");
+        self.ident(&decl.name);
+
+        if !decl.params.is_empty() {
+            self.text("<");
+
+            for (i, param) in decl.params.iter().enumerate() {
+                if i > 0 {
+                    self.text(", ");
+                }
+
+                self.ident(param);
+            }
+
+            self.text(">");
+        }
+
+        match &decl.variants[..] {
+            // A single unnamed variant lists its fields directly.
+            [TyVariant { name: None, fields }] => match fields {
+                Fields::Named(fields) => {
+                    self.text(" {\n");
+                    self.indent += 1;
+
+                    for field in fields {
+                        self.line_start();
+                        self.ty_ident(field);
+                        self.text(",\n");
+                    }
+
+                    self.indent -= 1;
+                    self.text("}\n");
+                }
+                Fields::Anonymous(_) => {
+                    self.fields(fields);
+                    self.text("\n");
+                }
+            },
+            variants => {
+                self.text(" {\n");
+                self.indent += 1;
+
+                for variant in variants {
+                    self.line_start();
+
+                    if let Some(name) = &variant.name {
+                        self.ident(name);
+                    }
+
+                    // Named variants without fields are written bare, like
+                    // `None`.
+                    if variant.name.is_none() || !variant.fields.is_empty() {
+                        self.fields(&variant.fields);
+                    }
+                    self.text(",\n");
+                }
+
+                self.indent -= 1;
+                self.text("}\n");
+            }
+        }
+    }
+
+    /// Print the fields of a variant, after its name.
+    fn fields(&mut self, fields: &Fields<'_>) {
+        match fields {
+            Fields::Named(fields) => {
+                self.text(" { ");
+
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.text(", ");
+                    }
+
+                    self.ty_ident(field);
+                }
+
+                self.text(" }");
+            }
+            Fields::Anonymous(fields) => {
+                self.text("(");
+
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.text(", ");
+                    }
+
+                    self.ty(field);
+                }
+
+                self.text(")");
+            }
+        }
+    }
+
+    fn ty_ident(&mut self, ty_ident: &TyIdent<'_>) {
+        self.ident(&ty_ident.ident);
+        self.text(": ");
+        self.ty(&ty_ident.ty);
+    }
+
+    fn ty(&mut self, ty: &Ty<'_>) {
+        self.leaf(ty.0, ty.0);
+    }
+
+    fn ident(&mut self, ident: &Ident<'_>) {
+        self.leaf(ident.0, ident.0);
+    }
+
+    /// Print a doc comment, leaving the next line indented.
+    fn doc(&mut self, doc: &str) {
+        for line in doc.lines() {
+            self.text("///");
+
+            if !line.is_empty() {
+                self.text(" ");
+                self.text(line);
+            }
+
+            self.text("\n");
+            self.line_start();
+        }
+    }
+
+    fn comment(&mut self, comment: &Comment<'_>) {
+        let lines = match comment {
+            Comment::SingleLine(line) => vec![*line],
+            Comment::MultiLine(lines) => lines.clone(),
+        };
+
+        for (i, line) in lines.into_iter().enumerate() {
+            if i > 0 {
+                self.line_start();
+            }
+
+            let start = self.out.len();
+            self.text("//");
+            self.text(line);
+            self.map_source(start, line, 2, 0);
+            self.text("\n");
+        }
+    }
+
+    /// Print a block, starting at the current position and ending after
+    /// its closing brace.
+    fn block(&mut self, block: &Block<'_>) {
+        if block.0.is_empty() {
+            self.text("{}");
+            return;
+        }
+
+        self.text("{\n");
+        self.indent += 1;
+
+        for stmt in &block.0 {
+            self.line_start();
+            self.stmt(stmt);
+        }
+
+        self.indent -= 1;
+        self.line_start();
+        self.text("}");
+    }
+
+    fn stmt(&mut self, stmt: &Stmt<'_>) {
+        match stmt {
+            Stmt::Comment(comment) => self.comment(comment),
+            Stmt::Documented(doc, stmt) => {
+                self.doc(doc);
+                self.stmt(stmt);
+            }
+            Stmt::Trailed(stmt, comment) => {
+                self.stmt(stmt);
+                // Put the comment before the newline ending the statement.
+                self.out.pop();
+                self.text(" //");
+                let start = self.out.len();
+                self.text(comment);
+                self.map_source(start, comment, 2, 0);
+                self.text("\n");
+            }
+            Stmt::VarAssign(assign) => {
+                self.pattern(&assign.pattern);
+
+                if let Some(ty) = &assign.ty {
+                    self.text(": ");
+                    self.ty(ty);
+                }
+
+                self.text(" = ");
+                self.expr(&assign.rhs, 0);
+                self.text(";\n");
+            }
+            Stmt::MultiAssign(assign) => {
+                for (i, pattern) in assign.patterns.iter().enumerate() {
+                    if i > 0 {
+                        self.text(", ");
+                    }
+
+                    self.pattern(pattern);
+                }
+
+                self.text(" = ");
+                self.exprs(&assign.values);
+                self.text(";\n");
+            }
+            Stmt::CompoundAssign(assign) => {
+                self.pattern(&assign.target);
+                self.text(" ");
+                self.text(assign.op);
+                self.text("= ");
+                self.expr(&assign.rhs, 0);
+                self.text(";\n");
+            }
+            Stmt::Expr(expr) => {
+                self.expr(expr, 0);
+
+                if ends_with_block(expr) {
+                    self.text("\n");
+                } else {
+                    self.text(";\n");
+                }
+            }
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern<'_>) {
+        let (open, items, rest, close) = match pattern {
+            Pattern::Ident(ident) => return self.ident(ident),
+            Pattern::Place(place) => return self.expr(place, POSTFIX),
+            Pattern::Tuple(items, rest) => ("#(", items, rest, ")"),
+            Pattern::Array(items, rest) => ("#[", items, rest, "]"),
+        };
+
+        self.text(open);
+
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.text(", ");
+            }
+
+            self.pattern(item);
+        }
+
+        if let (Pattern::Tuple(..), [_], None) = (pattern, &items[..], rest) {
+            self.text(",");
+        }
+
+        if let Some(rest) = rest {
+            if !items.is_empty() {
+                self.text(", ");
+            }
+
+            self.text("..");
+
+            if let Some(name) = &rest.0 {
+                self.ident(name);
+            }
+        }
+
+        self.text(close);
+    }
+
+    /// Print an expression, wrapping operations in parentheses if they bind
+    /// more loosely than the `min` precedence the position needs.
+    fn expr(&mut self, expr: &Expr<'_>, min: u8) {
+        match expr {
+            Expr::If(cond, then, els) => self.cond("if", cond, then, els.as_ref(), true),
+            Expr::Unless(cond, then, els) => self.cond("unless", cond, then, els.as_ref(), true),
+            Expr::While(cond, body, els) => self.cond("while", cond, body, els.as_ref(), false),
+            Expr::Until(cond, body, els) => self.cond("until", cond, body, els.as_ref(), false),
+            Expr::Loop(body) => {
+                self.text("loop ");
+                self.block(body);
+            }
+            Expr::For(item, iter, body, els) => {
+                self.text("for ");
+                self.expr(item, 0);
+                self.text(" in ");
+                self.expr(iter, 0);
+                self.text(" ");
+                self.block(body);
+                self.else_block(els.as_ref(), false);
+            }
+            Expr::Match(expr) => self.match_expr(expr),
+            Expr::Continue(kw) => self.leaf("continue", kw),
+            Expr::Break(label, value, kw) => {
+                self.leaf("break", kw);
+
+                if let Some(label) = label {
+                    self.text(" ");
+                    self.label(label);
+                }
+
+                if let Some(value) = value {
+                    // A symbol right after `break` would be read as a label.
+                    let wrap = label.is_none() && starts_with_symbol(value);
+                    self.text(" ");
+                    self.open(wrap);
+                    self.expr(value, 0);
+                    self.close(wrap);
+                }
+            }
+            Expr::Labeled(label, body) => {
+                self.label(label);
+                self.text(" ");
+                self.block(body);
+            }
+            Expr::Return(value, kw) => self.jump("return", value.as_deref(), kw),
+            Expr::FnCall(call) => self.call(call, min),
+            Expr::Ident(ident) => self.ident(ident),
+            // Negative integers are written with their `-`.
+            Expr::Literal(literal @ Literal::Int(Int(value, _))) if *value < 0 => {
+                self.open(NEGATION < min);
+                self.literal(literal);
+                self.close(NEGATION < min);
+            }
+            Expr::Literal(literal) => self.literal(literal),
+            Expr::Dot(lhs, rhs) => {
+                self.expr(lhs, POSTFIX);
+                self.text(".");
+                self.expr(rhs, POSTFIX);
+            }
+            Expr::Index(base, index) => {
+                self.expr(base, POSTFIX);
+                self.text("[");
+                self.expr(index, 0);
+                self.text("]");
+            }
+            Expr::Try(expr) => {
+                self.expr(expr, POSTFIX);
+                self.text("?");
+            }
+            Expr::Lambda(lambda) => {
+                self.text("fn");
+                self.signature(&lambda.args, &lambda.ret_ty);
+                self.text(" ");
+                self.block(&lambda.body);
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[BExpr<'_>]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.text(", ");
+            }
+
+            self.expr(expr, 0);
+        }
+    }
+
+    fn cond(
+        &mut self,
+        kw: &str,
+        cond: &Expr<'_>,
+        body: &Block<'_>,
+        els: Option<&Block<'_>>,
+        chains: bool,
+    ) {
+        self.text(kw);
+        self.text(" ");
+        self.expr(cond, 0);
+        self.text(" ");
+        self.block(body);
+        self.else_block(els, chains);
+    }
+
+    /// Print an `else`, which may be chained into an `if` or `unless`.
+    fn else_block(&mut self, els: Option<&Block<'_>>, chains: bool) {
+        let els = match els {
+            Some(els) => els,
+            None => return,
+        };
+
+        self.text(" else ");
+
+        match &els.0[..] {
+            [Stmt::Expr(expr)] if chains && matches!(**expr, Expr::If(..) | Expr::Unless(..)) => {
+                self.expr(expr, 0);
+            }
+            _ => self.block(els),
+        }
+    }
+
+    /// Print a `match`, writing arms whose body is a single expression
+    /// without braces.
+    fn match_expr(&mut self, expr: &Match<'_>) {
+        self.text("match ");
+        self.expr(&expr.scrutinee, 0);
+        self.text(" {\n");
+        self.indent += 1;
+
+        for arm in &expr.arms {
+            self.line_start();
+            self.arm_pattern(&arm.pattern);
+            self.text(" => ");
+
+            match &arm.body.0[..] {
+                [Stmt::Expr(body)] => {
+                    self.expr(body, 0);
+                    self.text(",\n");
+                }
+                _ => {
+                    self.block(&arm.body);
+                    self.text("\n");
+                }
+            }
+        }
+
+        self.indent -= 1;
+        self.line_start();
+        self.text("}");
+    }
+
+    fn arm_pattern(&mut self, pattern: &ArmPattern<'_>) {
+        match pattern {
+            ArmPattern::Literal(literal) => self.literal(literal),
+            ArmPattern::Bind(name) => self.ident(name),
+            ArmPattern::Variant(name, fields) => {
+                self.ident(name);
+
+                if !fields.is_empty() {
+                    self.text("(");
+
+                    for (i, field) in fields.iter().enumerate() {
+                        if i > 0 {
+                            self.text(", ");
+                        }
+
+                        self.arm_pattern(field);
+                    }
+
+                    self.text(")");
+                }
+            }
+        }
+    }
+
+    fn label(&mut self, label: &Label<'_>) {
+        let start = self.out.len();
+        self.text(":");
+        self.text(label.0);
+        self.map_source(start, label.0, 1, 0);
+    }
+
+    fn jump(&mut self, kw: &str, value: Option<&Expr<'_>>, src: &str) {
+        self.leaf(kw, src);
+
+        if let Some(value) = value {
+            self.text(" ");
+            self.expr(value, 0);
+        }
+    }
+
+    fn call(&mut self, call: &FnCall<'_>, min: u8) {
+        let op = call.name.0;
+
+        match (
+            &call.args[..],
+            precedence(BINARY, op),
+            precedence(PREFIX, op),
+        ) {
+            ([lhs, rhs], Some(level), _) => {
+                // Operators chain to the left, except that comparisons don't
+                // chain at all, and the base of a power can't be an operation.
+                let (lhs_min, rhs_min) = match level {
+                    COMPARISON => (level + 1, level + 1),
+                    POWER => (POSTFIX, NEGATION),
+                    _ => (level, level + 1),
+                };
+
+                self.open(level < min);
+                self.expr(lhs, lhs_min);
+                self.text(" ");
+                self.ident(&call.name);
+                self.text(" ");
+                self.expr(rhs, rhs_min);
+                self.close(level < min);
+            }
+            ([operand], _, Some(level)) => {
+                self.open(level < min);
+                self.ident(&call.name);
+
+                if op == "not" {
+                    self.text(" ");
+                }
+
+                // Identity is only taken of names and fields.
+                self.expr(operand, if op == "@" { POSTFIX } else { level });
+                self.close(level < min);
+            }
+            _ => {
+                self.ident(&call.name);
+                self.text("(");
+                self.exprs(&call.args);
+                self.text(")");
+            }
+        }
+    }
+
+    fn open(&mut self, wrap: bool) {
+        if wrap {
+            self.text("(");
+        }
+    }
+
+    fn close(&mut self, wrap: bool) {
+        if wrap {
+            self.text(")");
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal<'_>) {
+        if self.by_value {
+            let value = match literal {
+                Literal::Bool(Bool(value, _)) => Some(value.to_string()),
+                Literal::Int(Int(value, _)) => Some(value.to_string()),
+                Literal::UStr(ustr) => Some(format!("{:?}", ustr.value())),
+                Literal::BStr(bstr) => Some(format!("b{:?}", bstr.value())),
+                Literal::Char(Char(value, _)) => Some(format!("{:?}", value)),
+                _ => None,
+            };
+
+            if let Some(value) = value {
+                self.text(&value);
+                return;
+            }
+        }
+
+        match literal {
+            Literal::Bool(Bool(_, src)) => self.leaf(src, src),
+            Literal::Int(Int(_, src)) => self.leaf(src, src),
+            Literal::Float(float) => self.leaf(&float.to_string(), float.1),
+            Literal::UStr(ustr) => {
+                let start = self.out.len();
+                self.text(&ustr.to_source());
+
+                let (open, close) = match ustr.1 {
+                    StrStyle::Escaped => (1, 1),
+                    StrStyle::Raw(hashes) => (2 + hashes, 1 + hashes),
+                };
+
+                self.map_source(start, ustr.0, open, close);
+            }
+            Literal::BStr(BStr(_, src)) => self.leaf(src, src),
+            Literal::Char(Char(_, src)) => self.leaf(src, src),
+            Literal::Symbol(Symbol(name)) => {
+                let start = self.out.len();
+                self.text(":");
+                self.text(name);
+                self.map_source(start, name, 1, 0);
+            }
+            Literal::Array(Array(items, ty)) => {
+                self.literals("#[", items, "]");
+                self.collection_ty("Array", ty.as_ref());
+            }
+            Literal::Tuple(Tuple(items)) => self.literals("#(", items, ")"),
+            Literal::Map(Map(entries, ty)) => {
+                self.text("#{");
+
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    self.text(if i > 0 { ", " } else { " " });
+                    self.text(key.0);
+                    self.text(": ");
+                    self.expr(value, 0);
+                }
+
+                self.text(if entries.is_empty() { "}" } else { " }" });
+                self.collection_ty("Map", ty.as_ref());
+            }
+        }
+    }
+
+    /// The element type given for a collection, like `: Array<Int>`.
+    fn collection_ty(&mut self, kind: &str, ty: Option<&Ty<'_>>) {
+        if let Some(ty) = ty {
+            self.text(": ");
+            self.text(kind);
+            self.text("<");
+            self.ty(ty);
+            self.text(">");
+        }
+    }
+
+    fn literals(&mut self, open: &str, items: &[BExpr<'_>], close: &str) {
+        self.text(open);
+        self.exprs(items);
+
+        if open == "#(" && items.len() == 1 {
+            self.text(",");
+        }
+
+        self.text(close);
+    }
+
+    /// Write indentation at the start of a line.
+    fn line_start(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        self.out.push_str(text);
+    }
+
+    /// Write text which came from a slice of the source.
+    fn leaf(&mut self, text: &str, src: &str) {
+        let start = self.out.len();
+        self.text(text);
+        self.map_source(start, src, 0, 0);
+    }
+
+    /// Map the output written since `start` to the given slice of the
+    /// source, widened by the delimiters around it.
+    fn map_source(&mut self, start: usize, src: &str, open: usize, close: usize) {
+        let source = match self.source.and_then(|source| Span::of(src, source)) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let output = Span::new(start, self.out.len());
+        let source = Span::new(source.start.saturating_sub(open), source.end + close);
+        self.map.add(output, source);
+    }
+}
+
+fn ends_with_block(expr: &Expr<'_>) -> bool {
+    use Expr::*;
+
+    matches!(
+        expr,
+        If(..) | Unless(..) | Loop(..) | While(..) | Until(..) | For(..) | Match(..) | Labeled(..)
+    )
+}
+
+/// Whether an expression is written starting with a symbol.
+fn starts_with_symbol(expr: &Expr<'_>) -> bool {
+    match expr {
+        Expr::Literal(Literal::Symbol(_)) => true,
+        Expr::FnCall(call) if call.args.len() == 2 && precedence(BINARY, call.name.0).is_some() => {
+            starts_with_symbol(&call.args[0])
+        }
+        Expr::Dot(lhs, _) | Expr::Index(lhs, _) | Expr::Try(lhs) => starts_with_symbol(lhs),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::span::source_of;
+
+    const SRC: &str = "\
+// Says hello.
+use std:io:println;
+use std:io:print;
+type Pair<A, B> { first: A, second: B }
+type Shape { Circle(Float), Rect { w: Float, h: Float }, }
+///   Greets.
+#[when(unix)]
+fn main(name: String) -> () {
+    greeting = r\"hi\" + 1*(2+3);
+    if not done { println(greeting, :ok); } else if x { stop(); }
+    for item in items { break; }
+    point = #{ a: -1, b: #[1, 2.5] }.a;
+    #(first, #[second, ..]) = pairs;
+    #(only,) = #(1 ,);
+    each(items, fn(item: Int) -> () { print(item) });
+}
+";
+
+    const PRINTED: &str = "\
+// Says hello.
+use std:io:println;
+use std:io:print;
+
+type Pair<A, B> {
+    first: A,
+    second: B,
+}
+
+type Shape {
+    Circle(Float),
+    Rect { w: Float, h: Float },
+}
+
+///   Greets.
+#[when(unix)]
+fn main(name: String) -> () {
+    greeting = r\"hi\" + 1 * (2 + 3);
+    if not done {
+        println(greeting, :ok);
+    } else if x {
+        stop();
+    }
+    for item in items {
+        break;
+    }
+    point = #{ a: -1, b: #[1, 2.5] }.a;
+    #(first, #[second, ..]) = pairs;
+    #(only,) = #(1,);
+    each(items, fn(item: Int) -> () {
+        print(item);
+    });
+}
+";
+
+    #[test]
+    fn prints_a_program() {
+        let program = parse(SRC).expect("should parse");
+        assert_eq!(pretty_print(&program), PRINTED);
+    }
+
+    #[test]
+    fn printing_is_stable() {
+        let program = parse(PRINTED).expect("printed output should parse");
+        assert_eq!(pretty_print(&program), PRINTED);
+    }
+
+    #[test]
+    fn prints_floats_so_they_round_trip() {
+        let program = parse("fn main() { x = 1.50 + 0.1e1 + 12345e-2 + 1.7976931348623157e308; }")
+            .expect("should parse");
+        let printed = pretty_print(&program);
+        assert_eq!(
+            printed,
+            "fn main() {\n    x = 1.5 + 1.0 + 123.45 + 1.7976931348623157e308;\n}\n"
+        );
+    }
+
+    #[test]
+    fn prints_multiple_assignment() {
+        let program = parse("fn main() { a,#(b,c)=c  +  1,  pair; }").expect("should parse");
+        assert_eq!(
+            pretty_print(&program),
+            "fn main() {\n    a, #(b, c) = c + 1, pair;\n}\n"
+        );
+    }
+
+    #[test]
+    fn prints_matches() {
+        let src =
+            "fn main() { match n { 0 => :zero, Some(n) => { log(n); n } other => { other } } }";
+        let program = parse(src).expect("should parse");
+        let printed = "\
+fn main() {
+    match n {
+        0 => :zero,
+        Some(n) => {
+            log(n);
+            n;
+        }
+        other => other,
+    }
+}
+";
+        assert_eq!(pretty_print(&program), printed);
+        assert_eq!(parse(printed).expect("should parse"), program);
+    }
+
+    #[test]
+    fn prints_labeled_blocks() {
+        let src = "fn main() { x = :init { if ready { break :init 1; } 2 }; :a { break :a; } \
+                   loop { break (:done); } loop { break (:done) == x; } }";
+        let program = parse(src).expect("should parse");
+        let printed = "\
+fn main() {
+    x = :init {
+        if ready {
+            break :init 1;
+        }
+        2;
+    };
+    :a {
+        break :a;
+    }
+    loop {
+        break (:done);
+    }
+    loop {
+        break (:done == x);
+    }
+}
+";
+        assert_eq!(pretty_print(&program), printed);
+        assert_eq!(parse(printed).expect("should parse"), program);
+    }
+
+    #[test]
+    fn prints_trailing_comments() {
+        let src = "fn main() {\n    x = 1;   // one\n    for item in items { break; } // just one\n    y = 2;\n}\n";
+        let program = parse(src).expect("should parse");
+        let printed = "\
+fn main() {
+    x = 1; // one
+    for item in items {
+        break;
+    } // just one
+    y = 2;
+}
+";
+        assert_eq!(pretty_print(&program), printed);
+        assert_eq!(parse(printed).expect("should parse"), program);
+    }
+
+    #[test]
+    fn prints_collection_types() {
+        let src = "fn main() { xs = #[]:Array< Int >; m = #{ }: Map<Str>; ys = #[1]; }";
+        let program = parse(src).expect("should parse");
+        let printed =
+            "fn main() {\n    xs = #[]: Array<Int>;\n    m = #{}: Map<Str>;\n    ys = #[1];\n}\n";
+        assert_eq!(pretty_print(&program), printed);
+        assert_eq!(parse(printed).expect("should parse"), program);
+    }
+
+    #[test]
+    fn prints_variants_without_fields() {
+        let program = parse("type Option<T> { Some(T), None() }").expect("should parse");
+        let printed = "type Option<T> {\n    Some(T),\n    None,\n}\n";
+        assert_eq!(pretty_print(&program), printed);
+        assert_eq!(parse(printed).expect("should parse"), program);
+    }
+
+    #[test]
+    fn prints_statement_docs() {
+        let printed = "\
+fn main() {
+    /// How many tries.
+    ///
+    /// At least one.
+    tries = 3;
+}
+";
+        let program = parse(printed).expect("should parse");
+        assert_eq!(pretty_print(&program), printed);
+    }
+
+    #[test]
+    fn prints_try() {
+        assert_eq!(print_expr("a()?.b() ?"), "a()?.b()?");
+        assert_eq!(print_expr("(-a)?"), "(-a)?");
+    }
+
+    #[test]
+    fn prints_integers_in_their_original_base() {
+        let program = parse("fn main() { mask = 0xFF ~ 0b1010 + 010 + 7; }").expect("should parse");
+        assert_eq!(
+            pretty_print(&program),
+            "fn main() {\n    mask = 0xFF ~ 0b1010 + 010 + 7;\n}\n"
+        );
+    }
+
+    #[test]
+    fn displays_literals_as_source() {
+        for src in &[
+            "true",
+            "0x1F",
+            "0b101",
+            "2.5",
+            "1e300",
+            r#""tab\tquote\"""#,
+            r###"r#"raw "quoted""#"###,
+            r#"b"bytes\n""#,
+            r"'\n'",
+            ":key",
+            "#[1, -2, x + 1]",
+            "#[]: Array<Int>",
+            "#(1,)",
+            "#('a', b\"b\")",
+            "#{ x: 1, y: #[2] }",
+            "#{}: Map<Float>",
+        ] {
+            let (rest, expr) = crate::parse::expr(src).expect("should parse");
+            assert_eq!(rest, "");
+
+            let literal = match &expr {
+                Expr::Literal(literal) => literal,
+                other => panic!("expected a literal, found {:?}", other),
+            };
+
+            let displayed = literal.to_string();
+            assert_eq!(&displayed, src);
+            assert_eq!(tree_of(&displayed), tree_of(src));
+        }
+    }
+
+    /// Print a single expression, as the right side of an assignment.
+    fn print_expr(src: &str) -> String {
+        let src = format!("fn main() {{ x = {}; }}", src);
+        let program = parse(&src).expect("should parse");
+        let printed = pretty_print(&program);
+        printed["fn main() {\n    x = ".len()..printed.len() - ";\n}\n".len()].to_string()
+    }
+
+    #[test]
+    fn parenthesizes_only_where_needed() {
+        assert_eq!(print_expr("(a + b) * c"), "(a + b) * c");
+        assert_eq!(print_expr("a + (b * c)"), "a + b * c");
+        assert_eq!(print_expr("(a - b) - c"), "a - b - c");
+        assert_eq!(print_expr("a - (b - c)"), "a - (b - c)");
+        assert_eq!(print_expr("a ^ (b ^ c)"), "a ^ b ^ c");
+        assert_eq!(print_expr("(a ^ b) ^ c"), "(a ^ b) ^ c");
+        assert_eq!(print_expr("a ^ (-b)"), "a ^ -b");
+        assert_eq!(print_expr("(-a) ^ b"), "(-a) ^ b");
+        assert_eq!(print_expr("-(a + b)"), "-(a + b)");
+        assert_eq!(print_expr("(a < b) == c"), "(a < b) == c");
+        assert_eq!(
+            print_expr("not (a == b) and (c or d)"),
+            "not a == b and (c or d)"
+        );
+        assert_eq!(print_expr("(a + b).len()"), "(a + b).len()");
+        assert_eq!(print_expr("(a + b)[i * 2][:key]"), "(a + b)[i * 2][:key]");
+        assert_eq!(
+            print_expr("(-9223372036854775808) ^ 2 - -9223372036854775808"),
+            "(-9223372036854775808) ^ 2 - -9223372036854775808"
+        );
+        assert_eq!(print_expr("f((a + b)) - -(c)"), "f(a + b) - -c");
+    }
+
+    /// The structure of an expression, for comparing parses.
+    fn tree_of(src: &str) -> String {
+        let (rest, expr) = crate::parse::expr(src).expect("should parse");
+        assert_eq!(rest, "");
+        format!("{:?}", expr)
+    }
+
+    #[test]
+    fn minimal_parentheses_round_trip() {
+        for src in &[
+            "(a or b) and not (c xor d)",
+            "a | b ~ c & (d << e) >> f",
+            "((a - b) - (c - d)) / (e % f)",
+            "-(-a) ^ (b ^ -c)",
+        ] {
+            let printed = print_expr(src);
+            assert_eq!(print_expr(&printed), printed);
+            assert_eq!(tree_of(src), tree_of(&printed));
+        }
+    }
+
+    #[test]
+    fn maps_output_to_source() {
+        let program = parse(SRC).expect("should parse");
+        let (out, map) = pretty_print_mapped(&program, SRC);
+        assert_eq!(out, PRINTED);
+
+        let origin_of = |text: &str| {
+            let offset = out.find(text).expect("should be in the output");
+            let span = map.origin(offset).expect("should be mapped");
+            source_of(span, SRC).expect("should be in range")
+        };
+
+        assert_eq!(origin_of("greeting"), "greeting");
+        assert_eq!(origin_of("r\"hi\""), "r\"hi\"");
+        assert_eq!(origin_of(":ok"), ":ok");
+        assert_eq!(origin_of("// Says"), "// Says hello.");
+        assert_eq!(origin_of("2.5"), "2.5");
+
+        // Punctuation is generated, and so has no origin.
+        let brace = out.find('{').unwrap();
+        assert_eq!(map.origin(brace), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! Renaming variables and functions, as for an editor's "rename".
+
+use crate::hir::*;
+
+/// Where `Program::rename` renames a name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameScope<'a> {
+    /// Everywhere in the program, including the names of functions.
+    Program,
+    /// Only in the function with the given name, including its parameters.
+    Function(&'a str),
+}
+
+impl<'prgrm> Program<'prgrm> {
+    /// Rename every use of a name within the scope, returning how many
+    /// identifiers were changed.
+    ///
+    /// Assignments don't shadow, since assigning to a name that's already
+    /// in scope changes the existing variable. The parameters of lambdas,
+    /// the variables of `for` loops, and names bound by `match` arms do
+    /// shadow, so a binding with the same name there, and everything that
+    /// refers to it, is left alone.
+    pub fn rename(&mut self, from: &str, to: &'prgrm str, scope: RenameScope<'_>) -> usize {
+        let mut renamer = Renamer { from, to, count: 0 };
+
+        for stmt in &mut self.0 {
+            let decl = match stmt {
+                TopStmt::FnDecl(decl) => decl,
+                _ => continue,
+            };
+
+            match scope {
+                RenameScope::Program => renamer.ident(&mut decl.name),
+                RenameScope::Function(name) if decl.name.0 == name => {}
+                RenameScope::Function(_) => continue,
+            }
+
+            for arg in &mut decl.args {
+                renamer.ident(&mut arg.ident);
+            }
+
+            renamer.block(&mut decl.body);
+        }
+
+        renamer.count
+    }
+}
+
+struct Renamer<'a, 'prgrm> {
+    from: &'a str,
+    to: &'prgrm str,
+    count: usize,
+}
+
+impl<'prgrm> Renamer<'_, 'prgrm> {
+    fn ident(&mut self, ident: &mut Ident<'prgrm>) {
+        if ident.0 == self.from {
+            ident.0 = self.to;
+            self.count += 1;
+        }
+    }
+
+    fn block(&mut self, block: &mut Block<'prgrm>) {
+        for stmt in &mut block.0 {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &mut Stmt<'prgrm>) {
+        match stmt {
+            Stmt::Comment(_) => {}
+            Stmt::VarAssign(assign) => {
+                self.pattern(&mut assign.pattern);
+                self.expr(&mut assign.rhs);
+            }
+            Stmt::MultiAssign(assign) => {
+                assign
+                    .patterns
+                    .iter_mut()
+                    .for_each(|pattern| self.pattern(pattern));
+                assign.values.iter_mut().for_each(|value| self.expr(value));
+            }
+            Stmt::CompoundAssign(assign) => {
+                self.pattern(&mut assign.target);
+                self.expr(&mut assign.rhs);
+            }
+            Stmt::Expr(expr) => self.expr(expr),
+            Stmt::Documented(_, stmt) | Stmt::Trailed(stmt, _) => self.stmt(stmt),
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern<'prgrm>) {
+        match pattern {
+            Pattern::Ident(ident) => self.ident(ident),
+            Pattern::Place(place) => self.expr(place),
+            Pattern::Tuple(items, rest) | Pattern::Array(items, rest) => {
+                items.iter_mut().for_each(|item| self.pattern(item));
+
+                if let Some(Rest(Some(ident))) = rest {
+                    self.ident(ident);
+                }
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &mut [BExpr<'prgrm>]) {
+        exprs.iter_mut().for_each(|expr| self.expr(expr));
+    }
+
+    fn expr(&mut self, expr: &mut Expr<'prgrm>) {
+        match expr {
+            Expr::If(cond, body, els)
+            | Expr::Unless(cond, body, els)
+            | Expr::While(cond, body, els)
+            | Expr::Until(cond, body, els) => {
+                self.expr(cond);
+                self.block(body);
+                els.iter_mut().for_each(|els| self.block(els));
+            }
+            Expr::Loop(body) | Expr::Labeled(_, body) => self.block(body),
+            Expr::For(pattern, iter, body, els) => {
+                self.expr(iter);
+                els.iter_mut().for_each(|els| self.block(els));
+
+                if !self.binds(pattern) {
+                    self.block(body);
+                }
+            }
+            Expr::Continue(_) => {}
+            Expr::Break(_, value, _) | Expr::Return(value, _) => {
+                value.iter_mut().for_each(|value| self.expr(value));
+            }
+            Expr::Match(expr) => {
+                self.expr(&mut expr.scrutinee);
+
+                for arm in &mut expr.arms {
+                    if !arm_binds(&arm.pattern, self.from) {
+                        self.block(&mut arm.body);
+                    }
+                }
+            }
+            Expr::FnCall(call) => {
+                if !call.name.is_operator() {
+                    self.ident(&mut call.name);
+                }
+
+                self.exprs(&mut call.args);
+            }
+            Expr::Ident(ident) => self.ident(ident),
+            Expr::Literal(Literal::Array(Array(items, _)))
+            | Expr::Literal(Literal::Tuple(Tuple(items))) => self.exprs(items),
+            Expr::Literal(Literal::Map(Map(entries, _))) => {
+                entries.iter_mut().for_each(|(_, value)| self.expr(value));
+            }
+            Expr::Literal(_) => {}
+            // The right side names a field or method, not a variable, though
+            // a method's arguments are still renamed.
+            Expr::Dot(lhs, rhs) => {
+                self.expr(lhs);
+
+                if let Expr::FnCall(call) = &mut **rhs {
+                    self.exprs(&mut call.args);
+                }
+            }
+            Expr::Index(base, index) => {
+                self.expr(base);
+                self.expr(index);
+            }
+            Expr::Try(expr) => self.expr(expr),
+            Expr::Lambda(lambda) => {
+                if !lambda.args.iter().any(|arg| arg.ident.0 == self.from) {
+                    self.block(&mut lambda.body);
+                }
+            }
+        }
+    }
+
+    /// Whether the variable of a `for` loop binds the name being renamed.
+    fn binds(&self, pattern: &Expr<'_>) -> bool {
+        match pattern {
+            Expr::Ident(ident) => ident.0 == self.from,
+            Expr::Literal(Literal::Tuple(Tuple(items)))
+            | Expr::Literal(Literal::Array(Array(items, _))) => {
+                items.iter().any(|item| self.binds(item))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Whether a `match` arm binds the name.
+fn arm_binds(pattern: &ArmPattern<'_>, name: &str) -> bool {
+    match pattern {
+        ArmPattern::Literal(_) => false,
+        ArmPattern::Bind(ident) => ident.0 == name,
+        ArmPattern::Variant(_, fields) => fields.iter().any(|field| arm_binds(field, name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::print::pretty_print;
+
+    #[test]
+    fn renames_every_use_of_a_variable() {
+        let src = "fn main(count: Int) { total = count + 1; print(total, count); }\n\
+                   fn other() { count = 2; }";
+        let mut program = parse(src).expect("should parse");
+
+        assert_eq!(
+            program.rename("count", "n", RenameScope::Function("main")),
+            3
+        );
+        assert_eq!(
+            pretty_print(&program),
+            "fn main(n: Int) {\n    total = n + 1;\n    print(total, n);\n}\n\n\
+             fn other() {\n    count = 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn leaves_shadowing_bindings_alone() {
+        let src = "fn main() {\n\
+                   x = 1;\n\
+                   f = fn(x: Int) { x + 1 };\n\
+                   for x in xs { print(x); }\n\
+                   match y { x => x, 0 => x }\n\
+                   print(x);\n\
+                   }";
+        let mut program = parse(src).expect("should parse");
+
+        assert_eq!(program.rename("x", "first", RenameScope::Program), 3);
+        assert_eq!(
+            pretty_print(&program),
+            "fn main() {\n    first = 1;\n    f = fn(x: Int) {\n        x + 1;\n    };\n\
+             \x20   for x in xs {\n        print(x);\n    }\n\
+             \x20   match y {\n        x => x,\n        0 => first,\n    }\n\
+             \x20   print(first);\n}\n"
+        );
+    }
+}
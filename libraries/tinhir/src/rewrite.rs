@@ -0,0 +1,299 @@
+//! Rewriting the HIR by mapping over it.
+//!
+//! These take the tree by value and build a new one, which is often simpler
+//! than working through `&mut` references for passes that replace nodes.
+
+use crate::hir::*;
+use std::mem;
+
+impl<'prgrm> Program<'prgrm> {
+    /// Replace each top-level statement with the result of `f`.
+    pub fn rewrite<F>(self, f: F) -> Program<'prgrm>
+    where
+        F: FnMut(TopStmt<'prgrm>) -> TopStmt<'prgrm>,
+    {
+        Program(self.0.into_iter().map(f).collect())
+    }
+}
+
+impl<'prgrm> Block<'prgrm> {
+    /// Rewrite every expression in the block, including those in nested
+    /// blocks, with `Expr::rewrite`.
+    ///
+    /// The names assigned to are offered to `f` as identifiers too, along
+    /// with the fields and elements assigned to.
+    pub fn rewrite<F>(self, f: &mut F) -> Block<'prgrm>
+    where
+        F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+    {
+        self.into_iter().map(|stmt| rewrite_stmt(stmt, f)).collect()
+    }
+}
+
+fn rewrite_stmt<'prgrm, F>(stmt: Stmt<'prgrm>, f: &mut F) -> Stmt<'prgrm>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    match stmt {
+        Stmt::VarAssign(assign) => Stmt::VarAssign(VarAssign {
+            pattern: rewrite_pattern(assign.pattern, f),
+            rhs: rewrite_boxed(assign.rhs, f),
+            ..assign
+        }),
+        Stmt::MultiAssign(assign) => Stmt::MultiAssign(MultiAssign {
+            patterns: rewrite_patterns(assign.patterns, f),
+            values: assign
+                .values
+                .into_iter()
+                .map(|value| rewrite_boxed(value, f))
+                .collect(),
+        }),
+        Stmt::CompoundAssign(assign) => Stmt::CompoundAssign(CompoundAssign {
+            target: rewrite_pattern(assign.target, f),
+            rhs: rewrite_boxed(assign.rhs, f),
+            ..assign
+        }),
+        Stmt::Expr(expr) => Stmt::Expr(rewrite_boxed(expr, f)),
+        Stmt::Comment(comment) => Stmt::Comment(comment),
+        Stmt::Documented(doc, stmt) => Stmt::Documented(doc, Box::new(rewrite_stmt(*stmt, f))),
+        Stmt::Trailed(stmt, comment) => Stmt::Trailed(Box::new(rewrite_stmt(*stmt, f)), comment),
+    }
+}
+
+fn rewrite_pattern<'prgrm, F>(pattern: Pattern<'prgrm>, f: &mut F) -> Pattern<'prgrm>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    match pattern {
+        Pattern::Ident(name) => Pattern::Ident(rewrite_name(name, f)),
+        Pattern::Tuple(items, rest) => {
+            Pattern::Tuple(rewrite_patterns(items, f), rewrite_rest(rest, f))
+        }
+        Pattern::Array(items, rest) => {
+            Pattern::Array(rewrite_patterns(items, f), rewrite_rest(rest, f))
+        }
+        Pattern::Place(place) => Pattern::Place(rewrite_boxed(place, f)),
+    }
+}
+
+fn rewrite_patterns<'prgrm, F>(patterns: Vec<Pattern<'prgrm>>, f: &mut F) -> Vec<Pattern<'prgrm>>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    patterns
+        .into_iter()
+        .map(|pattern| rewrite_pattern(pattern, f))
+        .collect()
+}
+
+fn rewrite_rest<'prgrm, F>(rest: Option<Rest<'prgrm>>, f: &mut F) -> Option<Rest<'prgrm>>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    rest.map(|Rest(name)| Rest(name.map(|name| rewrite_name(name, f))))
+}
+
+/// Offer a name to `f` as an identifier. Only identifiers can stand in
+/// for a name, so if `f` returns anything else the name is kept.
+fn rewrite_name<'prgrm, F>(name: Ident<'prgrm>, f: &mut F) -> Ident<'prgrm>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    let original = name.0;
+
+    match f(Expr::Ident(name)) {
+        Expr::Ident(name) => name,
+        _ => Ident(original),
+    }
+}
+
+impl<'prgrm> Expr<'prgrm> {
+    /// Rewrite the expression from the bottom up, replacing each expression
+    /// in the tree with the result of `f`.
+    ///
+    /// Children are rewritten before their parents, so `f` always sees
+    /// expressions whose children are already done. The names of called
+    /// functions are offered to `f` as identifiers, before their arguments.
+    pub fn rewrite<F>(self, f: &mut F) -> Expr<'prgrm>
+    where
+        F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+    {
+        let expr = match self {
+            Expr::If(cond, then, els) => Expr::If(
+                rewrite_boxed(cond, f),
+                then.rewrite(f),
+                rewrite_else(els, f),
+            ),
+            Expr::Unless(cond, then, els) => Expr::Unless(
+                rewrite_boxed(cond, f),
+                then.rewrite(f),
+                rewrite_else(els, f),
+            ),
+            Expr::Loop(body) => Expr::Loop(body.rewrite(f)),
+            Expr::While(cond, body, els) => Expr::While(
+                rewrite_boxed(cond, f),
+                body.rewrite(f),
+                rewrite_else(els, f),
+            ),
+            Expr::Until(cond, body, els) => Expr::Until(
+                rewrite_boxed(cond, f),
+                body.rewrite(f),
+                rewrite_else(els, f),
+            ),
+            Expr::For(item, iter, body, els) => Expr::For(
+                rewrite_boxed(item, f),
+                rewrite_boxed(iter, f),
+                body.rewrite(f),
+                rewrite_else(els, f),
+            ),
+            Expr::Match(expr) => Expr::Match(Match {
+                scrutinee: rewrite_boxed(expr.scrutinee, f),
+                arms: expr
+                    .arms
+                    .into_iter()
+                    .map(|arm| Arm {
+                        body: arm.body.rewrite(f),
+                        ..arm
+                    })
+                    .collect(),
+            }),
+            Expr::Break(label, value, src) => {
+                Expr::Break(label, value.map(|value| rewrite_boxed(value, f)), src)
+            }
+            Expr::Labeled(label, body) => Expr::Labeled(label, body.rewrite(f)),
+            Expr::Return(value, src) => {
+                Expr::Return(value.map(|value| rewrite_boxed(value, f)), src)
+            }
+            Expr::FnCall(FnCall { name, args }) => Expr::FnCall(FnCall {
+                name: rewrite_name(name, f),
+                args: args.into_iter().map(|arg| rewrite_boxed(arg, f)).collect(),
+            }),
+            Expr::Dot(lhs, rhs) => Expr::Dot(rewrite_boxed(lhs, f), rewrite_boxed(rhs, f)),
+            Expr::Try(expr) => Expr::Try(rewrite_boxed(expr, f)),
+            Expr::Index(base, index) => {
+                Expr::Index(rewrite_boxed(base, f), rewrite_boxed(index, f))
+            }
+            Expr::Lambda(lambda) => Expr::Lambda(Lambda {
+                body: lambda.body.rewrite(f),
+                ..lambda
+            }),
+            Expr::Literal(Literal::Map(Map(entries, ty))) => Expr::Literal(Literal::Map(Map(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, rewrite_boxed(value, f)))
+                    .collect(),
+                ty,
+            ))),
+            Expr::Literal(Literal::Array(Array(items, ty))) => {
+                Expr::Literal(Literal::Array(Array(rewrite_all(items, f), ty)))
+            }
+            Expr::Literal(Literal::Tuple(Tuple(items))) => {
+                Expr::Literal(Literal::Tuple(Tuple(rewrite_all(items, f))))
+            }
+            expr @ Expr::Continue(_) | expr @ Expr::Ident(_) | expr @ Expr::Literal(_) => expr,
+        };
+
+        f(expr)
+    }
+}
+
+fn rewrite_else<'prgrm, F>(els: Option<Block<'prgrm>>, f: &mut F) -> Option<Block<'prgrm>>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    els.map(|els| els.rewrite(f))
+}
+
+fn rewrite_all<'prgrm, F>(exprs: Vec<BExpr<'prgrm>>, f: &mut F) -> Vec<BExpr<'prgrm>>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    exprs
+        .into_iter()
+        .map(|expr| rewrite_boxed(expr, f))
+        .collect()
+}
+
+/// Rewrite a boxed expression, reusing its box.
+fn rewrite_boxed<'prgrm, F>(mut expr: BExpr<'prgrm>, f: &mut F) -> BExpr<'prgrm>
+where
+    F: FnMut(Expr<'prgrm>) -> Expr<'prgrm>,
+{
+    let placeholder = Expr::Continue("");
+    *expr = mem::replace(&mut *expr, placeholder).rewrite(f);
+    expr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+    use crate::print::pretty_print;
+
+    #[test]
+    fn rewrites_identifiers() {
+        let src = "\
+// Doubles.
+fn main() {
+    x = y + 1;
+    if x > limit { print(#{ a: x }, y.len()); }
+    #(x, ..y) = split(x);
+    x += y;
+}
+";
+        let program = parse(src).expect("should parse");
+
+        let mut rename = |expr| match expr {
+            Expr::Ident(Ident("x")) => Expr::Ident(Ident("renamed")),
+            Expr::Ident(Ident("y")) => Expr::Ident(Ident("other")),
+            Expr::Ident(Ident("print")) => Expr::Ident(Ident("show")),
+            expr => expr,
+        };
+
+        let program = program.rewrite(|stmt| match stmt {
+            TopStmt::FnDecl(decl) => TopStmt::FnDecl(FnDecl {
+                body: decl.body.rewrite(&mut rename),
+                ..decl
+            }),
+            stmt => stmt,
+        });
+
+        assert_eq!(
+            pretty_print(&program),
+            "\
+// Doubles.
+fn main() {
+    renamed = other + 1;
+    if renamed > limit {
+        show(#{ a: renamed }, other.len());
+    }
+    #(renamed, ..other) = split(renamed);
+    renamed += other;
+}
+"
+        );
+    }
+
+    #[test]
+    fn rewrites_children_first() {
+        let program = parse("fn main() { f(g(1)); }").expect("should parse");
+        let expr = match program.0.into_iter().next() {
+            Some(TopStmt::FnDecl(decl)) => match decl.body.0.into_iter().next() {
+                Some(Stmt::Expr(expr)) => *expr,
+                other => panic!("expected an expression, found {:?}", other),
+            },
+            other => panic!("expected a function, found {:?}", other),
+        };
+
+        let mut seen = Vec::new();
+        expr.rewrite(&mut |expr| {
+            if let Expr::FnCall(call) = &expr {
+                seen.push(call.name.0);
+            }
+
+            expr
+        });
+
+        assert_eq!(seen, vec!["g", "f"]);
+    }
+}
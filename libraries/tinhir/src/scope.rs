@@ -0,0 +1,152 @@
+//! The names declared and used in a scope.
+
+use crate::hir::*;
+use crate::visit::Node;
+
+impl<'prgrm> Block<'prgrm> {
+    /// The names declared directly in this block, in source order.
+    ///
+    /// Declarations come from assignments, including every name bound by a
+    /// destructuring pattern. Names declared in nested blocks aren't
+    /// included, since they're only in scope there.
+    pub fn declared_names(&self) -> Vec<&Ident<'prgrm>> {
+        self.0
+            .iter()
+            .filter_map(|stmt| match stmt.without_comments() {
+                Stmt::VarAssign(assign) => Some(assign.pattern.names()),
+                Stmt::MultiAssign(assign) => {
+                    Some(assign.patterns.iter().flat_map(Pattern::names).collect())
+                }
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// The names used in this block or any block nested in it, in source
+    /// order, with one entry per use.
+    ///
+    /// This includes variables and the names of called functions, but not
+    /// operators, fields, or methods, and not the names being declared.
+    pub fn referenced_names(&self) -> Vec<&Ident<'prgrm>> {
+        let mut names = Vec::new();
+        references(Node::Block(self), &mut names);
+        names
+    }
+}
+
+impl<'prgrm> Pattern<'prgrm> {
+    /// Every name bound by the pattern, in source order.
+    pub fn names(&self) -> Vec<&Ident<'prgrm>> {
+        match self {
+            Pattern::Ident(ident) => vec![ident],
+            Pattern::Place(_) => vec![],
+            Pattern::Tuple(items, rest) | Pattern::Array(items, rest) => items
+                .iter()
+                .flat_map(Pattern::names)
+                .chain(rest.iter().filter_map(|rest| rest.0.as_ref()))
+                .collect(),
+        }
+    }
+}
+
+fn references<'a, 'prgrm>(node: Node<'a, 'prgrm>, names: &mut Vec<&'a Ident<'prgrm>>) {
+    match node {
+        // Patterns and types don't refer to variables, except for places,
+        // which use the value they're part of.
+        Node::Pattern(Pattern::Place(place)) => references(Node::Expr(place), names),
+        // Updating a variable uses its value, so its name is a reference.
+        Node::CompoundAssign(assign) => {
+            match &assign.target {
+                Pattern::Ident(ident) => names.push(ident),
+                target => references(Node::Pattern(target), names),
+            }
+
+            references(Node::Expr(&assign.rhs), names);
+        }
+        Node::Pattern(_) | Node::ArmPattern(_) | Node::Ty(_) => {}
+        Node::Ident(ident) => names.push(ident),
+        Node::Expr(Expr::FnCall(call)) => {
+            if !call.name.is_operator() {
+                names.push(&call.name);
+            }
+
+            for arg in &call.args {
+                references(Node::Expr(arg), names);
+            }
+        }
+        Node::Expr(Expr::Dot(lhs, rhs)) => {
+            references(Node::Expr(lhs), names);
+
+            // The right side names a field or method, not a variable, though
+            // a method's arguments are still used.
+            if let Expr::FnCall(call) = &**rhs {
+                for arg in &call.args {
+                    references(Node::Expr(arg), names);
+                }
+            }
+        }
+        // A lambda's parameters are declared, not used.
+        Node::Expr(Expr::Lambda(lambda)) => references(Node::Block(&lambda.body), names),
+        Node::Expr(Expr::For(_, iter, body, els)) => {
+            references(Node::Expr(iter), names);
+            references(Node::Block(body), names);
+
+            if let Some(els) = els {
+                references(Node::Block(els), names);
+            }
+        }
+        node => {
+            for child in node.children() {
+                references(child, names);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse;
+
+    fn names<'a>(idents: Vec<&'a Ident<'_>>) -> Vec<&'a str> {
+        idents.into_iter().map(|ident| ident.0).collect()
+    }
+
+    const SRC: &str = "\
+fn main() {
+    total = start + 1;
+    #(a, #[b, ..rest]) = pairs;
+    if total > limit {
+        inner = total;
+        print(inner, point.x, point.scale(factor));
+    }
+    for item in items { use-item(item); }
+}
+";
+
+    fn body<'a>(program: &'a Program<'_>) -> &'a Block<'a> {
+        &program.functions().next().unwrap().body
+    }
+
+    #[test]
+    fn lists_declared_names() {
+        let program = parse(SRC).expect("should parse");
+        assert_eq!(
+            names(body(&program).declared_names()),
+            vec!["total", "a", "b", "rest"]
+        );
+    }
+
+    #[test]
+    fn lists_referenced_names() {
+        let program = parse(SRC).expect("should parse");
+        assert_eq!(
+            names(body(&program).referenced_names()),
+            vec![
+                "start", "pairs", "total", "limit", "total", "print", "inner", "point", "point",
+                "factor", "items", "use-item", "item",
+            ]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+//! Mapping generated output back to the source it came from.
+//!
+//! Passes which generate new text, like the pretty-printer, record where
+//! each piece of their output came from, so errors found in the output can
+//! point at the original source.
+
+use crate::span::Span;
+
+/// Mappings from spans of generated output to spans of the source.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceMap {
+    /// Pairs of output and source spans, sorted by output span.
+    mappings: Vec<(Span, Span)>,
+}
+
+impl SourceMap {
+    /// Start building a new source map.
+    pub fn builder() -> SourceMapBuilder {
+        SourceMapBuilder::default()
+    }
+
+    /// Find the source span for an offset in the output.
+    ///
+    /// If several mappings cover the offset, the narrowest one wins.
+    pub fn origin(&self, offset: usize) -> Option<Span> {
+        self.mappings
+            .iter()
+            .filter(|(output, _)| output.start <= offset && offset < output.end)
+            .min_by_key(|(output, _)| output.len())
+            .map(|(_, source)| *source)
+    }
+
+    /// Iterate over the pairs of output and source spans, in output order.
+    pub fn mappings(&self) -> impl Iterator<Item = &(Span, Span)> {
+        self.mappings.iter()
+    }
+
+    /// The number of mappings.
+    pub fn len(&self) -> usize {
+        self.mappings.len()
+    }
+
+    /// Whether there are no mappings.
+    pub fn is_empty(&self) -> bool {
+        self.mappings.is_empty()
+    }
+}
+
+/// Collects mappings while output is generated.
+#[derive(Debug, Default, Clone)]
+pub struct SourceMapBuilder {
+    mappings: Vec<(Span, Span)>,
+}
+
+impl SourceMapBuilder {
+    /// Record that a span of the output came from a span of the source.
+    pub fn add(&mut self, output: Span, source: Span) -> &mut SourceMapBuilder {
+        self.mappings.push((output, source));
+        self
+    }
+
+    /// Finish building the source map.
+    pub fn build(mut self) -> SourceMap {
+        self.mappings.sort();
+        SourceMap {
+            mappings: self.mappings,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_narrowest_origin() {
+        let mut builder = SourceMap::builder();
+        builder
+            .add(Span::new(0, 10), Span::new(100, 120))
+            .add(Span::new(2, 4), Span::new(105, 107));
+        let map = builder.build();
+
+        assert_eq!(map.origin(0), Some(Span::new(100, 120)));
+        assert_eq!(map.origin(3), Some(Span::new(105, 107)));
+        assert_eq!(map.origin(4), Some(Span::new(100, 120)));
+        assert_eq!(map.origin(10), None);
+    }
+}
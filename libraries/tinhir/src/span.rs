@@ -0,0 +1,199 @@
+use crate::charset::display_width;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// A range of bytes in the source code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Span {
+    /// The offset of the first byte in the span.
+    pub start: usize,
+    /// The offset one past the last byte in the span.
+    pub end: usize,
+}
+
+impl Span {
+    /// Create a new span covering `start..end`.
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// The number of bytes covered by the span.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Whether the span covers no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Find the span of a slice of the source, like the text stored in a
+    /// literal, or `None` if it isn't part of the source.
+    pub fn of(part: &str, src: &str) -> Option<Span> {
+        let start = (part.as_ptr() as usize).checked_sub(src.as_ptr() as usize)?;
+        let end = start + part.len();
+
+        if end > src.len() {
+            return None;
+        }
+
+        Some(Span::new(start, end))
+    }
+}
+
+/// Find the one-based line and column of a byte offset in the source.
+///
+/// Columns count characters, not bytes.
+pub(crate) fn line_column(src: &str, offset: usize) -> (usize, usize) {
+    LineMap::new(src).line_column(src, offset, ColumnMode::Chars)
+}
+
+/// How to count columns when turning an offset into a line and column.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnMode {
+    /// Count characters. This is fast, and what editors usually report.
+    #[default]
+    Chars,
+    /// Count the columns a terminal shows, so combining marks take none and
+    /// wide characters like CJK ideographs take two. Carets under a line
+    /// rendered this way line up with the text above them.
+    Width,
+}
+
+impl ColumnMode {
+    /// How many columns the text takes up.
+    pub fn measure(self, text: &str) -> usize {
+        match self {
+            ColumnMode::Chars => text.chars().count(),
+            ColumnMode::Width => text.chars().map(display_width).sum(),
+        }
+    }
+}
+
+/// The offsets where each line of the source starts, for finding the line
+/// of an offset without rescanning the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMap {
+    starts: Vec<usize>,
+}
+
+impl LineMap {
+    /// Find where each line of the source starts.
+    pub fn new(src: &str) -> LineMap {
+        let newlines = src.match_indices('\n').map(|(offset, _)| offset + 1);
+        LineMap {
+            starts: std::iter::once(0).chain(newlines).collect(),
+        }
+    }
+
+    /// The number of lines in the source.
+    pub fn lines(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// The one-based line an offset is on.
+    pub fn line(&self, offset: usize) -> usize {
+        match self.starts.binary_search(&offset) {
+            Ok(line) => line + 1,
+            Err(next) => next,
+        }
+    }
+
+    /// The offset where a one-based line starts, if there is such a line.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.starts.get(line.checked_sub(1)?).copied()
+    }
+
+    /// The one-based line and column of an offset in the source the map
+    /// was made from, counting columns by the given mode.
+    pub fn line_column(&self, src: &str, offset: usize, mode: ColumnMode) -> (usize, usize) {
+        let line = self.line(offset);
+        let start = self.line_start(line).unwrap_or(0);
+        (line, mode.measure(&src[start..offset]) + 1)
+    }
+}
+
+/// Get the source text covered by a span.
+///
+/// This is `None` if the span is out of range, or doesn't start and end
+/// on character boundaries.
+pub fn source_of(span: Span, src: &str) -> Option<&str> {
+    src.get(span.start..span.end)
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hir::*;
+    use crate::lex::lex;
+    use crate::options::ParseOptions;
+    use crate::parse::parse;
+
+    const SRC: &str = "fn main() {\n    x = 42;\n}\n\nfn other() { \"é\" }\n";
+
+    #[test]
+    fn extracts_a_function() {
+        let tokens = lex(SRC, ParseOptions::default());
+        let close = tokens.iter().position(|token| token.text == "}").unwrap();
+        let span = Span::new(tokens[0].span.start, tokens[close].span.end);
+
+        assert_eq!(source_of(span, SRC), Some("fn main() {\n    x = 42;\n}"));
+    }
+
+    #[test]
+    fn extracts_a_literal() {
+        let program = parse(SRC).expect("should parse");
+
+        let value = match &program.0[0] {
+            TopStmt::FnDecl(decl) => match &decl.body.0[0] {
+                Stmt::VarAssign(assign) => match &*assign.rhs {
+                    Expr::Literal(Literal::Int(Int(_, src))) => *src,
+                    other => panic!("expected an int, found {:?}", other),
+                },
+                other => panic!("expected an assignment, found {:?}", other),
+            },
+            other => panic!("expected a function, found {:?}", other),
+        };
+
+        let span = Span::of(value, SRC).expect("should be in the source");
+        assert_eq!(span, Span::new(20, 22));
+        assert_eq!(source_of(span, SRC), Some("42"));
+    }
+
+    #[test]
+    fn rejects_out_of_range_spans() {
+        assert_eq!(source_of(Span::new(0, SRC.len() + 1), SRC), None);
+        assert_eq!(
+            source_of(Span::new(SRC.len() + 1, SRC.len() + 2), SRC),
+            None
+        );
+        assert_eq!(Span::of("elsewhere", SRC), None);
+    }
+
+    #[test]
+    fn maps_offsets_to_lines() {
+        let map = LineMap::new(SRC);
+
+        assert_eq!(map.lines(), 6);
+        assert_eq!(map.line(0), 1);
+        assert_eq!(map.line(11), 1);
+        assert_eq!(map.line(12), 2);
+        assert_eq!(map.line(SRC.len()), 6);
+        assert_eq!(map.line_start(2), Some(12));
+        assert_eq!(map.line_start(0), None);
+        assert_eq!(map.line_start(7), None);
+    }
+
+    #[test]
+    fn rejects_spans_splitting_characters() {
+        let e = SRC.find('é').unwrap();
+        assert_eq!(source_of(Span::new(e, e + 2), SRC), Some("é"));
+        assert_eq!(source_of(Span::new(e, e + 1), SRC), None);
+        assert_eq!(source_of(Span::new(e + 1, e + 2), SRC), None);
+    }
+}
@@ -0,0 +1,147 @@
+//! Macros for asserting the shape of HIR nodes in tests.
+//!
+//! These are always available to this crate's tests, and to other crates
+//! with the `test-support` feature enabled.
+
+/// Assert that an expression is an integer literal with the given value.
+#[macro_export]
+macro_rules! assert_int {
+    ($expr:expr, $value:expr) => {{
+        let expr: &$crate::Expr<'_> = &$expr;
+        let expected: i64 = $value;
+
+        match expr {
+            $crate::Expr::Literal($crate::Literal::Int($crate::Int(value, _))) => assert_eq!(
+                *value, expected,
+                "expected the integer {}, found {}",
+                expected, value
+            ),
+            other => panic!("expected the integer {}, found {:?}", expected, other),
+        }
+    }};
+}
+
+/// Assert that an expression is an identifier with the given name.
+#[macro_export]
+macro_rules! assert_ident {
+    ($expr:expr, $name:expr) => {{
+        let expr: &$crate::Expr<'_> = &$expr;
+        let expected: &str = $name;
+
+        match expr {
+            $crate::Expr::Ident($crate::Ident(name)) => assert_eq!(
+                *name, expected,
+                "expected the identifier `{}`, found `{}`",
+                expected, name
+            ),
+            other => panic!("expected the identifier `{}`, found {:?}", expected, other),
+        }
+    }};
+}
+
+/// Assert that an expression is a call to the named function with the given
+/// number of arguments, evaluating to the arguments.
+///
+/// Operators are calls too, so `assert_fn_call!(expr, "+", 2)` checks for
+/// an addition.
+#[macro_export]
+macro_rules! assert_fn_call {
+    ($expr:expr, $name:expr, $arity:expr) => {{
+        let expr: &$crate::Expr<'_> = &$expr;
+        let expected: &str = $name;
+        let arity: usize = $arity;
+
+        match expr {
+            $crate::Expr::FnCall($crate::FnCall { name, args }) => {
+                assert_eq!(
+                    name.0, expected,
+                    "expected a call to `{}`, found a call to `{}`",
+                    expected, name.0
+                );
+                assert_eq!(
+                    args.len(),
+                    arity,
+                    "expected `{}` to have {} arguments, found {}",
+                    expected,
+                    arity,
+                    args.len()
+                );
+                args
+            }
+            other => panic!("expected a call to `{}`, found {:?}", expected, other),
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hir::*;
+
+    fn int(value: i64) -> Expr<'static> {
+        Expr::Literal(Literal::Int(Int(value, "")))
+    }
+
+    fn call(name: &'static str, args: Vec<Expr<'static>>) -> Expr<'static> {
+        Expr::FnCall(FnCall {
+            name: Ident(name),
+            args: args.into_iter().map(Box::new).collect(),
+        })
+    }
+
+    #[test]
+    fn asserts_ints() {
+        assert_int!(int(5), 5);
+        assert_int!(Box::new(int(-1)), -1);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected the integer 6, found 5")]
+    fn reports_wrong_ints() {
+        assert_int!(int(5), 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected the integer 5, found Ident(Ident(\"x\"))")]
+    fn reports_non_ints() {
+        assert_int!(Expr::Ident(Ident("x")), 5);
+    }
+
+    #[test]
+    fn asserts_idents() {
+        assert_ident!(Expr::Ident(Ident("x")), "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "expected the identifier `y`, found `x`")]
+    fn reports_wrong_idents() {
+        assert_ident!(Expr::Ident(Ident("x")), "y");
+    }
+
+    #[test]
+    fn asserts_calls_and_returns_their_arguments() {
+        let expr = call("+", vec![int(1), call("foo", vec![int(2), int(3)])]);
+        let args = assert_fn_call!(expr, "+", 2);
+        assert_int!(args[0], 1);
+
+        let inner = assert_fn_call!(args[1], "foo", 2);
+        assert_int!(inner[1], 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a call to `foo`, found a call to `bar`")]
+    fn reports_wrong_call_names() {
+        assert_fn_call!(call("bar", vec![]), "foo", 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected `foo` to have 2 arguments, found 1")]
+    fn reports_wrong_call_arities() {
+        assert_fn_call!(call("foo", vec![int(1)]), "foo", 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a call to `foo`, found Literal(Int(Int(1, \"\")))")]
+    fn reports_non_calls() {
+        assert_fn_call!(int(1), "foo", 0);
+    }
+}
@@ -0,0 +1,144 @@
+//! Comments and blank lines, collected separately from the HIR.
+//!
+//! Formatters need to know where every comment and blank line is, even
+//! ones which don't appear in the HIR, so they can be put back.
+
+use crate::lex::{lex, TokenKind};
+use crate::options::ParseOptions;
+use crate::span::Span;
+
+/// All the trivia in a source file, in source order.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Trivia<'src>(pub Vec<TriviaItem<'src>>);
+
+/// A single comment line or run of blank lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriviaItem<'src> {
+    /// Whether this is a comment or blank lines.
+    pub kind: TriviaKind,
+    /// Where the trivia is in the source.
+    pub span: Span,
+    /// The text of the trivia.
+    pub text: &'src str,
+}
+
+/// The kinds of trivia.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A `//` comment line, including the slashes.
+    Comment,
+    /// Whitespace containing at least one empty line.
+    Blank,
+}
+
+impl<'src> Trivia<'src> {
+    /// Collect the trivia in the input.
+    pub fn collect(input: &'src str) -> Trivia<'src> {
+        let mut items = Vec::new();
+        let mut last_end = 0;
+
+        for token in lex(input, ParseOptions::default()) {
+            push_blank(input, last_end, token.span.start, &mut items);
+
+            if token.kind == TokenKind::Comment {
+                items.push(TriviaItem {
+                    kind: TriviaKind::Comment,
+                    span: token.span,
+                    text: token.text,
+                });
+            }
+
+            last_end = token.span.end;
+        }
+
+        push_blank(input, last_end, input.len(), &mut items);
+        Trivia(items)
+    }
+
+    /// Iterate over the comments.
+    pub fn comments(&self) -> impl Iterator<Item = &TriviaItem<'src>> {
+        self.0
+            .iter()
+            .filter(|item| item.kind == TriviaKind::Comment)
+    }
+
+    /// Get the trivia which falls entirely within the span.
+    pub fn within(&self, span: Span) -> &[TriviaItem<'src>] {
+        let start = self.0.partition_point(|item| item.span.start < span.start);
+        let end = self.0.partition_point(|item| item.span.end <= span.end);
+        &self.0[start..end.max(start)]
+    }
+}
+
+/// Record the gap between two tokens if it contains an empty line.
+fn push_blank<'src>(input: &'src str, start: usize, end: usize, items: &mut Vec<TriviaItem<'src>>) {
+    let text = &input[start..end];
+
+    if text.matches('\n').count() >= 2 {
+        items.push(TriviaItem {
+            kind: TriviaKind::Blank,
+            span: Span::new(start, end),
+            text,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_all;
+
+    const SRC: &str = "\
+// Entry point.
+// Does nothing useful.
+
+fn main() {
+    x = \"// not a comment\"; // trailing
+}
+";
+
+    #[test]
+    fn captures_every_comment() {
+        let (program, trivia) = parse_all(SRC, ParseOptions::default()).expect("should parse");
+        assert_eq!(program.0.len(), 2);
+
+        let comments: Vec<_> = trivia
+            .comments()
+            .map(|item| (item.span, item.text))
+            .collect();
+
+        assert_eq!(
+            comments,
+            vec![
+                (Span::new(0, 15), "// Entry point."),
+                (Span::new(16, 39), "// Does nothing useful."),
+                (Span::new(81, 92), "// trailing"),
+            ]
+        );
+
+        for (span, text) in comments {
+            assert_eq!(&SRC[span.start..span.end], text);
+        }
+    }
+
+    #[test]
+    fn captures_blank_runs() {
+        let trivia = Trivia::collect(SRC);
+        let blanks: Vec<_> = trivia
+            .0
+            .iter()
+            .filter(|item| item.kind == TriviaKind::Blank)
+            .map(|item| item.span)
+            .collect();
+
+        assert_eq!(blanks, vec![Span::new(39, 41)]);
+    }
+
+    #[test]
+    fn finds_trivia_within_a_span() {
+        let trivia = Trivia::collect(SRC);
+        let inside = trivia.within(Span::new(41, SRC.len()));
+        assert_eq!(inside.len(), 1);
+        assert_eq!(inside[0].text, "// trailing");
+    }
+}
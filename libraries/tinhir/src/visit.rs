@@ -0,0 +1,432 @@
+//! Traversal of the HIR.
+//!
+//! Every node in the tree can be borrowed as a `Node`, which knows how
+//! to list its own children. Traversals are built on top of that, so they
+//! never need to clone any part of the tree.
+
+use crate::hir::*;
+
+/// A borrowed reference to any node in the HIR.
+///
+/// Enum wrappers like `TopStmt` and `Stmt` don't get nodes of their own;
+/// they're represented by whatever they wrap.
+#[derive(Debug, Clone, Copy)]
+pub enum Node<'a, 'prgrm> {
+    /// The root of the tree.
+    Program(&'a Program<'prgrm>),
+    /// A comment block.
+    Comment(&'a Comment<'prgrm>),
+    /// A use statement.
+    PathGlob(&'a PathGlob<'prgrm>),
+    /// A function declaration.
+    FnDecl(&'a FnDecl<'prgrm>),
+    /// A type declaration.
+    TyDecl(&'a TyDecl<'prgrm>),
+    /// A type alias.
+    TyAlias(&'a TyAlias<'prgrm>),
+    /// A single variant of a type declaration.
+    TyVariant(&'a TyVariant<'prgrm>),
+    /// An identifier with a type.
+    TyIdent(&'a TyIdent<'prgrm>),
+    /// A block of statements.
+    Block(&'a Block<'prgrm>),
+    /// A variable assignment.
+    VarAssign(&'a VarAssign<'prgrm>),
+    /// An assignment of several values at once.
+    MultiAssign(&'a MultiAssign<'prgrm>),
+    /// An assignment which updates its target with an operator.
+    CompoundAssign(&'a CompoundAssign<'prgrm>),
+    /// The pattern on the left of an assignment.
+    Pattern(&'a Pattern<'prgrm>),
+    /// A single arm of a `match`.
+    Arm(&'a Arm<'prgrm>),
+    /// The pattern of a `match` arm.
+    ArmPattern(&'a ArmPattern<'prgrm>),
+    /// An expression.
+    Expr(&'a Expr<'prgrm>),
+    /// A literal value.
+    Literal(&'a Literal<'prgrm>),
+    /// An identifier.
+    Ident(&'a Ident<'prgrm>),
+    /// A type.
+    Ty(&'a Ty<'prgrm>),
+}
+
+impl<'a, 'prgrm> Node<'a, 'prgrm> {
+    /// Get the direct children of the node, in source order.
+    pub fn children(&self) -> Vec<Node<'a, 'prgrm>> {
+        let mut children = Vec::new();
+
+        match *self {
+            Node::Program(program) => children.extend(program.0.iter().map(Node::from)),
+            Node::Comment(_) | Node::PathGlob(_) | Node::Ident(_) | Node::Ty(_) => {}
+            Node::FnDecl(decl) => {
+                children.push(Node::Ident(&decl.name));
+                children.extend(decl.args.iter().map(Node::TyIdent));
+                children.extend(decl.ret_ty.tys().iter().map(Node::Ty));
+                children.push(Node::Block(&decl.body));
+            }
+            Node::TyDecl(decl) => {
+                children.push(Node::Ident(&decl.name));
+                children.extend(decl.params.iter().map(Node::Ident));
+                children.extend(decl.variants.iter().map(Node::TyVariant));
+            }
+            Node::TyAlias(alias) => {
+                children.push(Node::Ident(&alias.name));
+                children.push(Node::Ty(&alias.ty));
+            }
+            Node::TyVariant(variant) => {
+                children.extend(variant.name.iter().map(Node::Ident));
+
+                match &variant.fields {
+                    Fields::Named(fields) => children.extend(fields.iter().map(Node::TyIdent)),
+                    Fields::Anonymous(fields) => children.extend(fields.iter().map(Node::Ty)),
+                }
+            }
+            Node::TyIdent(ty_ident) => {
+                children.push(Node::Ident(&ty_ident.ident));
+                children.push(Node::Ty(&ty_ident.ty));
+            }
+            Node::Block(block) => children.extend(block.0.iter().map(Node::from)),
+            Node::VarAssign(assign) => {
+                children.push(Node::Pattern(&assign.pattern));
+                children.extend(assign.ty.iter().map(Node::Ty));
+                children.push(Node::Expr(&assign.rhs));
+            }
+            Node::MultiAssign(assign) => {
+                children.extend(assign.patterns.iter().map(Node::Pattern));
+                children.extend(assign.values.iter().map(|value| Node::Expr(value)));
+            }
+            Node::CompoundAssign(assign) => {
+                children.push(Node::Pattern(&assign.target));
+                children.push(Node::Expr(&assign.rhs));
+            }
+            Node::Pattern(pattern) => match pattern {
+                Pattern::Ident(ident) => children.push(Node::Ident(ident)),
+                Pattern::Place(place) => children.push(Node::Expr(place)),
+                Pattern::Tuple(items, rest) | Pattern::Array(items, rest) => {
+                    children.extend(items.iter().map(Node::Pattern));
+                    children.extend(
+                        rest.iter()
+                            .filter_map(|rest| rest.0.as_ref())
+                            .map(Node::Ident),
+                    );
+                }
+            },
+            Node::Arm(arm) => {
+                children.push(Node::ArmPattern(&arm.pattern));
+                children.push(Node::Block(&arm.body));
+            }
+            Node::ArmPattern(pattern) => match pattern {
+                ArmPattern::Literal(literal) => children.push(Node::Literal(literal)),
+                ArmPattern::Bind(name) => children.push(Node::Ident(name)),
+                ArmPattern::Variant(name, fields) => {
+                    children.push(Node::Ident(name));
+                    children.extend(fields.iter().map(Node::ArmPattern));
+                }
+            },
+            Node::Expr(expr) => expr_children(expr, &mut children),
+            Node::Literal(literal) => literal_children(literal, &mut children),
+        }
+
+        children
+    }
+
+    /// Iterate over this node and all of its descendants, depth-first.
+    pub fn traverse(self) -> Traverse<'a, 'prgrm> {
+        Traverse {
+            stack: vec![(NodePath::default(), self)],
+        }
+    }
+
+    /// Find the descendant at the given path, relative to this node.
+    pub fn at(self, path: &NodePath) -> Option<Node<'a, 'prgrm>> {
+        path.0
+            .iter()
+            .try_fold(self, |node, &index| node.children().get(index).copied())
+    }
+}
+
+fn expr_children<'a, 'prgrm>(expr: &'a Expr<'prgrm>, children: &mut Vec<Node<'a, 'prgrm>>) {
+    use Expr::*;
+
+    match expr {
+        If(cond, then, els) | Unless(cond, then, els) => {
+            children.push(Node::Expr(cond));
+            children.push(Node::Block(then));
+            children.extend(els.iter().map(Node::Block));
+        }
+        Loop(body) => children.push(Node::Block(body)),
+        While(cond, body, els) | Until(cond, body, els) => {
+            children.push(Node::Expr(cond));
+            children.push(Node::Block(body));
+            children.extend(els.iter().map(Node::Block));
+        }
+        For(item, iter, body, els) => {
+            children.push(Node::Expr(item));
+            children.push(Node::Expr(iter));
+            children.push(Node::Block(body));
+            children.extend(els.iter().map(Node::Block));
+        }
+        Match(expr) => {
+            children.push(Node::Expr(&expr.scrutinee));
+            children.extend(expr.arms.iter().map(Node::Arm));
+        }
+        Continue(_) => {}
+        Labeled(_, body) => children.push(Node::Block(body)),
+        Break(_, value, _) | Return(value, _) => {
+            children.extend(value.iter().map(|value| Node::Expr(value)))
+        }
+        FnCall(call) => {
+            children.push(Node::Ident(&call.name));
+            children.extend(call.args.iter().map(|arg| Node::Expr(arg)));
+        }
+        Ident(ident) => children.push(Node::Ident(ident)),
+        Literal(literal) => children.push(Node::Literal(literal)),
+        Try(expr) => children.push(Node::Expr(expr)),
+        Dot(lhs, rhs) | Index(lhs, rhs) => {
+            children.push(Node::Expr(lhs));
+            children.push(Node::Expr(rhs));
+        }
+        Lambda(lambda) => {
+            children.extend(lambda.args.iter().map(Node::TyIdent));
+            children.extend(lambda.ret_ty.tys().iter().map(Node::Ty));
+            children.push(Node::Block(&lambda.body));
+        }
+    }
+}
+
+fn literal_children<'a, 'prgrm>(
+    literal: &'a Literal<'prgrm>,
+    children: &mut Vec<Node<'a, 'prgrm>>,
+) {
+    use Literal::*;
+
+    match literal {
+        Array(self::Array(elems, ty)) => {
+            children.extend(elems.iter().map(|elem| Node::Expr(elem)));
+            children.extend(ty.iter().map(Node::Ty));
+        }
+        Tuple(self::Tuple(elems)) => children.extend(elems.iter().map(|elem| Node::Expr(elem))),
+        Map(map) => {
+            children.extend(map.0.iter().map(|(_, value)| Node::Expr(value)));
+            children.extend(map.1.iter().map(Node::Ty));
+        }
+        Bool(_) | Int(_) | Float(_) | UStr(_) | BStr(_) | Char(_) | Symbol(_) => {}
+    }
+}
+
+impl<'a, 'prgrm> From<&'a TopStmt<'prgrm>> for Node<'a, 'prgrm> {
+    fn from(stmt: &'a TopStmt<'prgrm>) -> Self {
+        match stmt {
+            TopStmt::Comment(comment) => Node::Comment(comment),
+            TopStmt::Use(path) => Node::PathGlob(path),
+            TopStmt::FnDecl(decl) => Node::FnDecl(decl),
+            TopStmt::TyDecl(decl) => Node::TyDecl(decl),
+            TopStmt::TyAlias(alias) => Node::TyAlias(alias),
+        }
+    }
+}
+
+impl<'a, 'prgrm> From<&'a Stmt<'prgrm>> for Node<'a, 'prgrm> {
+    fn from(stmt: &'a Stmt<'prgrm>) -> Self {
+        match stmt {
+            Stmt::Comment(comment) => Node::Comment(comment),
+            Stmt::VarAssign(assign) => Node::VarAssign(assign),
+            Stmt::MultiAssign(assign) => Node::MultiAssign(assign),
+            Stmt::CompoundAssign(assign) => Node::CompoundAssign(assign),
+            Stmt::Expr(expr) => Node::Expr(expr),
+            Stmt::Documented(_, stmt) | Stmt::Trailed(stmt, _) => Node::from(&**stmt),
+        }
+    }
+}
+
+/// The location of a node, as the child index taken at each level
+/// on the way down from the root.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct NodePath(pub Vec<usize>);
+
+impl NodePath {
+    /// Get the path of the `index`th child of the node at this path.
+    fn child(&self, index: usize) -> NodePath {
+        let mut path = self.0.clone();
+        path.push(index);
+        NodePath(path)
+    }
+}
+
+/// A depth-first, pre-order traversal yielding each node with its path.
+///
+/// Created by `Node::traverse` or `Program::traverse`.
+#[derive(Debug)]
+pub struct Traverse<'a, 'prgrm> {
+    stack: Vec<(NodePath, Node<'a, 'prgrm>)>,
+}
+
+impl<'a, 'prgrm> Iterator for Traverse<'a, 'prgrm> {
+    type Item = (NodePath, Node<'a, 'prgrm>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, node) = self.stack.pop()?;
+
+        // Push in reverse so the first child is visited next.
+        let children = node.children().into_iter().enumerate().rev();
+        self.stack
+            .extend(children.map(|(index, child)| (path.child(index), child)));
+
+        Some((path, node))
+    }
+}
+
+impl<'prgrm> Program<'prgrm> {
+    /// Iterate over every node in the program, paired with its path.
+    pub fn traverse(&self) -> Traverse<'_, 'prgrm> {
+        Node::Program(self).traverse()
+    }
+
+    /// Find the node at the given path.
+    pub fn node_at(&self, path: &NodePath) -> Option<Node<'_, 'prgrm>> {
+        Node::Program(self).at(path)
+    }
+
+    /// Thread an accumulator through every node in the program, in the same
+    /// order as `traverse`, returning its final value.
+    pub fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, Node<'_, 'prgrm>) -> B,
+    {
+        self.traverse().fold(init, |acc, (_, node)| f(acc, node))
+    }
+
+    /// Iterate over every identifier in the program, in source order.
+    ///
+    /// This includes declared names, parameters, names bound by patterns,
+    /// the names of called functions and operators, and the names in types,
+    /// which are split out of the types as written. Each is a slice of the
+    /// source, so `Span::of` finds it.
+    pub fn idents(&self) -> impl Iterator<Item = Ident<'prgrm>> + '_ {
+        self.traverse().flat_map(|(_, node)| {
+            let names = match node {
+                Node::Ident(ident) => vec![ident.0],
+                Node::Ty(ty) => ty.names().collect(),
+                _ => Vec::new(),
+            };
+
+            names.into_iter().map(Ident)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(value: i64, src: &str) -> BExpr<'_> {
+        Box::new(Expr::Literal(Literal::Int(Int(value, src))))
+    }
+
+    fn program() -> Program<'static> {
+        let body = Block(vec![
+            Stmt::VarAssign(VarAssign {
+                pattern: Pattern::Ident(Ident("x")),
+                ty: None,
+                rhs: int(1, "1"),
+            }),
+            Stmt::Expr(Box::new(Expr::FnCall(FnCall {
+                name: Ident("print"),
+                args: vec![
+                    int(2, "2"),
+                    Box::new(Expr::Literal(Literal::Array(Array(
+                        vec![
+                            int(3, "3"),
+                            Box::new(Expr::Literal(Literal::Bool(Bool(true, "true")))),
+                        ],
+                        None,
+                    )))),
+                ],
+            }))),
+        ]);
+
+        Program(vec![
+            TopStmt::Comment(Comment::SingleLine("// entry point")),
+            TopStmt::FnDecl(FnDecl {
+                meta: ItemMeta::default(),
+                name: Ident("main"),
+                args: Vec::new(),
+                ret_ty: ReturnType::Inferred,
+                body,
+            }),
+        ])
+    }
+
+    #[test]
+    fn collects_paths_of_int_literals() {
+        let program = program();
+
+        let ints: Vec<_> = program
+            .traverse()
+            .filter_map(|(path, node)| match node {
+                Node::Literal(Literal::Int(Int(value, _))) => Some((path, *value)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            ints,
+            vec![
+                (NodePath(vec![1, 1, 0, 1, 0]), 1),
+                (NodePath(vec![1, 1, 1, 1, 0]), 2),
+                (NodePath(vec![1, 1, 1, 2, 0, 0, 0]), 3),
+            ]
+        );
+
+        for (path, value) in ints {
+            match program.node_at(&path) {
+                Some(Node::Literal(Literal::Int(Int(found, _)))) => assert_eq!(*found, value),
+                other => panic!("expected an int at {:?}, found {:?}", path, other),
+            }
+        }
+    }
+
+    #[test]
+    fn folds_over_every_node() {
+        let sum = program().fold(0, |sum, node| match node {
+            Node::Literal(Literal::Int(Int(value, _))) => sum + value,
+            _ => sum,
+        });
+        assert_eq!(sum, 6);
+
+        let count = program().fold(0, |count, _| count + 1);
+        assert_eq!(count, program().traverse().count());
+    }
+
+    #[test]
+    fn yields_every_ident() {
+        let src = "type Pair<T> { first: T, second: T }\n\
+                   fn swap(p: Pair<Int>) -> (Int, Int) { #(a, b) = p; f = fn(x: Int) { x; }; make(b, a); }";
+        let program = crate::parse::parse(src).expect("should parse");
+        let names: Vec<_> = program.idents().map(|ident| ident.0).collect();
+
+        assert!(program
+            .idents()
+            .all(|ident| crate::span::Span::of(ident.0, src).is_some()));
+
+        assert_eq!(
+            names,
+            vec![
+                "Pair", "T", "first", "T", "second", "T", "swap", "p", "Pair", "Int", "Int", "Int",
+                "a", "b", "p", "f", "x", "Int", "x", "make", "b", "a"
+            ]
+        );
+    }
+
+    #[test]
+    fn node_at_rejects_missing_paths() {
+        let program = program();
+        assert!(program.node_at(&NodePath(vec![5])).is_none());
+        assert!(matches!(
+            program.node_at(&NodePath::default()),
+            Some(Node::Program(_))
+        ));
+    }
+}
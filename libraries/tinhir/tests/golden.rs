@@ -0,0 +1,53 @@
+//! Golden-file tests for the parser.
+//!
+//! Each `.tin` file in `tests/fixtures` is parsed, and the debug output of
+//! the resulting program is compared against the `.expected` file next to
+//! it. Run with `TIN_BLESS=1` to write the current output as the new
+//! expectation instead.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn fixtures_match_expectations() {
+    let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let bless = env::var_os("TIN_BLESS").is_some();
+
+    let mut inputs: Vec<_> = fs::read_dir(&fixtures)
+        .expect("fixtures directory should exist")
+        .map(|entry| entry.expect("fixture should be readable").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tin"))
+        .collect();
+    inputs.sort();
+    assert!(!inputs.is_empty(), "no fixtures found");
+
+    let mut failures = Vec::new();
+
+    for input in &inputs {
+        let source = fs::read_to_string(input).expect("fixture should be readable");
+        let actual = match tinhir::parse(&source) {
+            Ok(program) => format!("{:#?}\n", program),
+            Err(error) => format!("error: {}\n", error),
+        };
+
+        let expected_path = input.with_extension("expected");
+
+        if bless {
+            fs::write(&expected_path, &actual).expect("expectation should be writable");
+            continue;
+        }
+
+        match fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(_) => failures.push(format!("{}: output differs", input.display())),
+            Err(_) => failures.push(format!("{}: no expectation", input.display())),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "golden tests failed (rerun with TIN_BLESS=1 to update):\n{}",
+        failures.join("\n")
+    );
+}
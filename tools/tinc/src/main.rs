@@ -1,12 +1,43 @@
 use anyhow::Result;
 use std::env::args_os;
-use std::fs::read_to_string;
-use tinhir::{parse, Error};
+use std::process::exit;
+use tinhir::{
+    parse_with_diagnostics, read_input, summarize_diagnostics, Error, Input, ParseOptions,
+};
 
 fn main() -> Result<()> {
-    let file_name = args_os().nth(1).ok_or(Error::NoFile)?;
-    let contents = read_to_string(file_name)?;
-    let result = parse(&contents)?;
+    // The shared flags, like `--explain`, may ask for something else.
+    let contents = match read_input(args_os().skip(1), ParseOptions::default()) {
+        Ok(Input::Source(contents)) => contents,
+        Ok(Input::Explained(explanation)) => {
+            println!("{}", explanation);
+            return Ok(());
+        }
+        Ok(Input::Tokens(tokens)) => {
+            print!("{}", tokens);
+            return Ok(());
+        }
+        Err(error @ Error::UnknownCode(_)) => {
+            eprintln!("error: {}", error);
+            exit(1);
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let (result, diagnostics) = parse_with_diagnostics(&contents, ParseOptions::default());
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    eprintln!("{}", summarize_diagnostics(&diagnostics));
+
+    // The diagnostics already say why it didn't parse.
+    let result = match result {
+        Some(result) => result,
+        None => exit(1),
+    };
+
     println!("{result:#?}");
     Ok(())
 }
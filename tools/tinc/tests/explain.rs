@@ -0,0 +1,31 @@
+use std::process::Command;
+
+fn tinc(args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_tinc"))
+        .args(args)
+        .output()
+        .expect("should run tinc");
+
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn explains_a_diagnostic_code() {
+    let (success, stdout, _) = tinc(&["--explain", "E0002"]);
+
+    assert!(success);
+    assert_eq!(stdout.trim_end(), tinhir::explain("E0002").unwrap());
+}
+
+#[test]
+fn rejects_an_unknown_code() {
+    let (success, stdout, stderr) = tinc(&["--explain", "E9999"]);
+
+    assert!(!success);
+    assert!(stdout.is_empty());
+    assert_eq!(stderr, "error: there's no diagnostic code `E9999`\n");
+}
@@ -1,3 +1,29 @@
-fn main() {
-    println!("Hello, world!");
+use anyhow::Result;
+use std::env::args_os;
+use std::io::stderr;
+use std::process::exit;
+use tinhir::{parse_with_output, pretty_print, read_input, Error, Input, ParseOptions};
+
+fn main() -> Result<()> {
+    // The shared flags, like `--explain`, may ask for something else.
+    let contents = match read_input(args_os().skip(1), ParseOptions::default()) {
+        Ok(Input::Source(contents)) => contents,
+        Ok(Input::Explained(explanation)) => {
+            println!("{}", explanation);
+            return Ok(());
+        }
+        Ok(Input::Tokens(tokens)) => {
+            print!("{}", tokens);
+            return Ok(());
+        }
+        Err(error @ Error::UnknownCode(_)) => {
+            eprintln!("error: {}", error);
+            exit(1);
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    let program = parse_with_output(&contents, ParseOptions::default(), &mut stderr())?;
+    print!("{}", pretty_print(&program));
+    Ok(())
 }
@@ -0,0 +1,31 @@
+use std::process::Command;
+
+fn tinfmt(args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_tinfmt"))
+        .args(args)
+        .output()
+        .expect("should run tinfmt");
+
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn explains_a_diagnostic_code() {
+    let (success, stdout, _) = tinfmt(&["--explain", "E0002"]);
+
+    assert!(success);
+    assert_eq!(stdout.trim_end(), tinhir::explain("E0002").unwrap());
+}
+
+#[test]
+fn rejects_an_unknown_code() {
+    let (success, stdout, stderr) = tinfmt(&["--explain", "E9999"]);
+
+    assert!(!success);
+    assert!(stdout.is_empty());
+    assert_eq!(stderr, "error: there's no diagnostic code `E9999`\n");
+}